@@ -0,0 +1,362 @@
+use crate::graphics::*;
+use std::collections::HashMap;
+
+///Remaps the progress between two keys to speed up or slow down the motion
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Easing {
+    Linear,
+    ///Starts slow
+    EaseIn,
+    ///Ends slow
+    EaseOut,
+    ///Starts and ends slow
+    EaseInOut,
+}
+
+impl Easing {
+    ///u-progress, from 0 to 1
+    pub fn apply(self, u: f64) -> f64 {
+        match self {
+            Easing::Linear => u,
+            Easing::EaseIn => u * u * u,
+            Easing::EaseOut => 1.0 - (1.0 - u).powi(3),
+            Easing::EaseInOut => {
+                if u < 0.5 {
+                    4.0 * u * u * u
+                } else {
+                    1.0 - (-2.0 * u + 2.0).powi(3) / 2.0
+                }
+            }
+        }
+    }
+
+    fn parse(name: &str) -> Option<Easing> {
+        match name {
+            "ease-in" => Some(Easing::EaseIn),
+            "ease-out" => Some(Easing::EaseOut),
+            "ease-in-out" => Some(Easing::EaseInOut),
+            _ => None,
+        }
+    }
+}
+
+///How values change from a key to the next one
+#[derive(Debug, Clone, PartialEq)]
+pub enum Interpolation {
+    ///Holds the value until the next key
+    Step,
+    Linear,
+    ///Cubic Bezier curve from this key's value to the next one's with the given control values
+    Bezier { out_handle: Vec<f64>, in_handle: Vec<f64> },
+}
+
+#[derive(Debug, Clone)]
+pub struct Keyframe {
+    time: f64,
+    ///Components of the value, e.g. three for a vector
+    value: Vec<f64>,
+    ///Applies between this key and the next one
+    interpolation: Interpolation,
+    easing: Easing,
+}
+
+impl Keyframe {
+    ///Linear interpolation without easing towards the next key
+    pub fn new(time: f64, value: &[f64]) -> Self {
+        Keyframe {
+            time,
+            value: value.to_vec(),
+            interpolation: Interpolation::Linear,
+            easing: Easing::Linear,
+        }
+    }
+
+    pub fn with_interpolation(mut self, interpolation: Interpolation) -> Self {
+        self.interpolation = interpolation;
+        self
+    }
+
+    pub fn with_easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    pub fn get_time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn get_value(&self) -> &[f64] {
+        &self.value
+    }
+}
+
+///Keys of a single animated value sorted by time
+#[derive(Debug, Clone, Default)]
+pub struct Track {
+    keys: Vec<Keyframe>,
+}
+
+impl Track {
+    pub fn new() -> Self {
+        Track { keys: Vec::new() }
+    }
+
+    ///Replaces any key at the same time
+    ///Err if its value has a different number of components than the other keys
+    pub fn add_key(&mut self, key: Keyframe) -> Result<(), String> {
+        if let Interpolation::Bezier {
+            out_handle,
+            in_handle,
+        } = &key.interpolation
+        {
+            if out_handle.len() != key.value.len() || in_handle.len() != key.value.len() {
+                return Err(format!("bezier handles need {} values each", key.value.len()));
+            }
+        }
+        if let Some(other) = self.keys.iter().find(|k| k.time != key.time) {
+            if other.value.len() != key.value.len() {
+                return Err(format!(
+                    "key has {} values but the track's keys have {}",
+                    key.value.len(),
+                    other.value.len()
+                ));
+            }
+        }
+        match self.keys.iter().position(|k| k.time >= key.time) {
+            Some(index) if self.keys[index].time == key.time => self.keys[index] = key,
+            Some(index) => self.keys.insert(index, key),
+            None => self.keys.push(key),
+        }
+        Ok(())
+    }
+
+    pub fn get_keys(&self) -> &[Keyframe] {
+        &self.keys
+    }
+
+    ///Value at a time, holding the first and last values outside the keys
+    ///None if the track has no keys
+    pub fn evaluate(&self, time: f64) -> Option<Vec<f64>> {
+        let first = self.keys.first()?;
+        if time <= first.time {
+            return Some(first.value.clone());
+        }
+        let next_index = match self.keys.iter().position(|k| k.time > time) {
+            Some(index) => index,
+            None => return Some(self.keys.last()?.value.clone()),
+        };
+        let (key, next) = (&self.keys[next_index - 1], &self.keys[next_index]);
+        let u = key.easing.apply((time - key.time) / (next.time - key.time));
+        let lerp = |a: &[f64], b: &[f64], u: f64| -> Vec<f64> {
+            a.iter().zip(b).map(|(a, b)| a + (b - a) * u).collect()
+        };
+        Some(match &key.interpolation {
+            Interpolation::Step => key.value.clone(),
+            Interpolation::Linear => lerp(&key.value, &next.value, u),
+            Interpolation::Bezier {
+                out_handle,
+                in_handle,
+            } => {
+                //De Casteljau
+                let (a, b, c) = (
+                    lerp(&key.value, out_handle, u),
+                    lerp(out_handle, in_handle, u),
+                    lerp(in_handle, &next.value, u),
+                );
+                let (d, e) = (lerp(&a, &b, u), lerp(&b, &c, u));
+                lerp(&d, &e, u)
+            }
+        })
+    }
+}
+
+///Named tracks for everything animated in a scene, e.g. "camera.pixel_size",
+///"robot/arm.rotate" or "floor.albedo"
+///
+///Timelines can be loaded from text files with one key per line:
+///
+///`key <track> <time> <values...> [step|linear|bezier <out values...> <in values...>] [easing]`
+///
+///where easing is one of linear, ease-in, ease-out or ease-in-out. Everything after
+///a # is ignored. All keys of a track need the same number of values.
+#[derive(Debug, Clone, Default)]
+pub struct Timeline {
+    tracks: HashMap<String, Track>,
+}
+
+impl Timeline {
+    pub fn new() -> Self {
+        Timeline {
+            tracks: HashMap::new(),
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Timeline::parse(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut timeline = Timeline::new();
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            parse_key(line)
+                .and_then(|(name, key)| timeline.add_key(name, key))
+                .map_err(|e| format!("line {}: {}", line_no + 1, e))?;
+        }
+        Ok(timeline)
+    }
+
+    pub fn add_key(&mut self, track: &str, key: Keyframe) -> Result<(), String> {
+        self.tracks
+            .entry(track.to_string())
+            .or_default()
+            .add_key(key)
+    }
+
+    pub fn get_track(&self, name: &str) -> Option<&Track> {
+        self.tracks.get(name)
+    }
+
+    ///Raw components of a track's value, None if the track doesn't exist
+    pub fn evaluate(&self, name: &str, time: f64) -> Option<Vec<f64>> {
+        self.tracks.get(name)?.evaluate(time)
+    }
+
+    ///First component of a track or the default if there's no such track
+    pub fn get_f64(&self, name: &str, time: f64, default: f64) -> f64 {
+        self.evaluate(name, time)
+            .and_then(|value| value.first().cloned())
+            .unwrap_or(default)
+    }
+
+    ///A single component is used for all three
+    pub fn get_vector(&self, name: &str, time: f64) -> Option<Vector3D> {
+        let value = self.evaluate(name, time)?;
+        Some(match value.as_slice() {
+            [x, y, z, ..] => Vector3D::new(*x, *y, *z),
+            [s] => Vector3D::new(*s, *s, *s),
+            _ => return None,
+        })
+    }
+
+    pub fn get_point(&self, name: &str, time: f64) -> Option<Point3D> {
+        self.get_vector(name, time)
+            .map(|v| Point3D::origin() + v)
+    }
+
+    pub fn get_color(&self, name: &str, time: f64) -> Option<RGBColor> {
+        self.get_vector(name, time).map(RGBColor::from)
+    }
+
+    ///Whether any of the tracks get_transform reads exists
+    pub fn animates(&self, prefix: &str) -> bool {
+        ["translate", "rotate", "scale"]
+            .iter()
+            .any(|part| self.tracks.contains_key(&format!("{}.{}", prefix, part)))
+    }
+
+    ///Transform from the `<prefix>.translate`, `<prefix>.rotate` and `<prefix>.scale` tracks
+    ///Rotation is in radians about x, then y, then z, missing tracks are left out
+    pub fn get_transform(&self, prefix: &str, time: f64) -> Transform {
+        let translate = self.get_vector(&format!("{}.translate", prefix), time);
+        let rotate = self.get_vector(&format!("{}.rotate", prefix), time);
+        let scale = self.get_vector(&format!("{}.scale", prefix), time);
+
+        let mut transform = Transform::identity();
+        if let Some(s) = scale {
+            transform = Transform::scale(s.x(), s.y(), s.z()) * transform;
+        }
+        if let Some(r) = rotate {
+            transform = Transform::rotate_z(r.z())
+                * Transform::rotate_y(r.y())
+                * Transform::rotate_x(r.x())
+                * transform;
+        }
+        if let Some(t) = translate {
+            transform = Transform::translate(t) * transform;
+        }
+        transform
+    }
+
+    ///Motion of a transform over an interval, for motion blur
+    pub fn get_animated_transform(
+        &self,
+        prefix: &str,
+        start_time: f64,
+        end_time: f64,
+    ) -> AnimatedTransform {
+        AnimatedTransform::new(
+            self.get_transform(prefix, start_time),
+            start_time,
+            self.get_transform(prefix, end_time),
+            end_time,
+        )
+    }
+}
+
+///Parses numbers until the next word, at most count of them if given
+fn parse_numbers<'a>(
+    tokens: &mut std::iter::Peekable<impl Iterator<Item = &'a str>>,
+    count: Option<usize>,
+) -> Vec<f64> {
+    let mut values = Vec::new();
+    while count.is_none_or(|count| values.len() < count) {
+        match tokens.peek().and_then(|token| token.parse::<f64>().ok()) {
+            Some(value) => {
+                values.push(value);
+                tokens.next();
+            }
+            None => break,
+        }
+    }
+    values
+}
+
+fn parse_key(line: &str) -> Result<(&str, Keyframe), String> {
+    let mut tokens = line.split_whitespace().peekable();
+    if tokens.next() != Some("key") {
+        return Err("expected a line starting with key".to_string());
+    }
+    let name = tokens.next().ok_or("missing track name")?;
+    let time: f64 = tokens
+        .next()
+        .ok_or("missing time")?
+        .parse()
+        .map_err(|_| "time is not a number")?;
+    let value = parse_numbers(&mut tokens, None);
+    if value.is_empty() {
+        return Err("missing value".to_string());
+    }
+
+    let mut key = Keyframe::new(time, &value);
+    while let Some(word) = tokens.next() {
+        key = match word {
+            "step" => key.with_interpolation(Interpolation::Step),
+            //Linear is the default for both interpolation and easing
+            "linear" => key,
+            "bezier" => {
+                let mut out_handle = parse_numbers(&mut tokens, Some(2 * value.len()));
+                if out_handle.len() != 2 * value.len() {
+                    return Err(format!(
+                        "bezier needs {} values for its handles",
+                        2 * value.len()
+                    ));
+                }
+                let in_handle = out_handle.split_off(value.len());
+                key.with_interpolation(Interpolation::Bezier {
+                    out_handle,
+                    in_handle,
+                })
+            }
+            _ => match Easing::parse(word) {
+                Some(easing) => key.with_easing(easing),
+                None => return Err(format!("unknown keyword {}", word)),
+            },
+        };
+    }
+    Ok((name, key))
+}
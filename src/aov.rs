@@ -0,0 +1,440 @@
+//! Arbitrary output variables: what the camera rays saw at their first hit, for
+//! compositing and denoising
+
+use crate::exr::Channel;
+use crate::film::{gamma_correct, get_overlap, read_f64, read_u32};
+use crate::graphics::{Normal3D, Point3D, RGBColor, Vector3D};
+use image::{DynamicImage, GenericImage, Pixel};
+use std::io::{self, Read, Write};
+
+///One of the auxiliary images a film can record besides the color
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Aov {
+    ///Distance of the first hit from the eye along the view direction
+    Depth,
+    ///Normalized world space normal of the first hit
+    Normal,
+    ///Material albedo at the first hit, the background's color for rays which miss
+    Albedo,
+    ///World space point of the first hit
+    Position,
+    ///Index of the hit object in the world's objects
+    ObjectId,
+    ///Index of the hit object's material in `World::get_materials`
+    MaterialId,
+    ///Light emitted by the first surface plus what reaches it straight from an emitter
+    ///or the background, or the background for rays which miss
+    Direct,
+    ///The rest of the color, light which bounced more than once
+    Indirect,
+}
+
+impl Aov {
+    pub const ALL: [Aov; 8] = [
+        Aov::Depth,
+        Aov::Normal,
+        Aov::Albedo,
+        Aov::Position,
+        Aov::ObjectId,
+        Aov::MaterialId,
+        Aov::Direct,
+        Aov::Indirect,
+    ];
+
+    ///Lower case name, used for file names and EXR layers
+    pub fn get_name(self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Normal => "normal",
+            Aov::Albedo => "albedo",
+            Aov::Position => "position",
+            Aov::ObjectId => "object_id",
+            Aov::MaterialId => "material_id",
+            Aov::Direct => "direct",
+            Aov::Indirect => "indirect",
+        }
+    }
+}
+
+///Surface a camera ray hit first
+#[derive(Debug, Copy, Clone)]
+pub struct Surface {
+    pub depth: f64,
+    pub normal: Normal3D,
+    pub position: Point3D,
+    pub object_id: u32,
+    pub material_id: u32,
+}
+
+///What one camera ray saw, direct and indirect add up to its color
+#[derive(Debug, Copy, Clone)]
+pub struct AovSample {
+    ///None for rays which miss everything
+    pub surface: Option<Surface>,
+    pub albedo: RGBColor,
+    pub direct: RGBColor,
+    pub indirect: RGBColor,
+}
+
+///Running sums of the AOV samples of each pixel, kept by a `Film`
+///Depth, normal and position are averaged over the samples which hit something, the
+///rest over all of them, and the IDs are those of the first sample which hit something
+#[derive(Debug, Clone)]
+pub struct AovFilm {
+    width: u32,
+    height: u32,
+    samples: Vec<u32>,
+    hits: Vec<u32>,
+    depth: Vec<f64>,
+    normal: Vec<Vector3D>,
+    position: Vec<Vector3D>,
+    ids: Vec<Option<(u32, u32)>>,
+    albedo: Vec<RGBColor>,
+    direct: Vec<RGBColor>,
+    indirect: Vec<RGBColor>,
+}
+
+impl AovFilm {
+    pub fn new(width: u32, height: u32) -> Self {
+        let len = (width * height) as usize;
+        let black = RGBColor::new(0.0, 0.0, 0.0);
+        AovFilm {
+            width,
+            height,
+            samples: vec![0; len],
+            hits: vec![0; len],
+            depth: vec![0.0; len],
+            normal: vec![Vector3D::null(); len],
+            position: vec![Vector3D::null(); len],
+            ids: vec![None; len],
+            albedo: vec![black; len],
+            direct: vec![black; len],
+            indirect: vec![black; len],
+        }
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        (y * self.width + x) as usize
+    }
+
+    pub fn add_sample(&mut self, x: u32, y: u32, sample: &AovSample) {
+        let index = self.index(x, y);
+        self.samples[index] += 1;
+        self.albedo[index] = self.albedo[index] + sample.albedo;
+        self.direct[index] = self.direct[index] + sample.direct;
+        self.indirect[index] = self.indirect[index] + sample.indirect;
+        if let Some(surface) = &sample.surface {
+            self.hits[index] += 1;
+            self.depth[index] += surface.depth;
+            self.normal[index] = self.normal[index] + Vector3D::from(surface.normal);
+            self.position[index] = self.position[index] + (surface.position - Point3D::origin());
+            if self.ids[index].is_none() {
+                self.ids[index] = Some((surface.object_id, surface.material_id));
+            }
+        }
+    }
+
+    ///Adds the samples of a film covering the pixels from (x,y) onwards, which were
+    ///taken after those of this film, the parts of it outside this film are left out
+    pub fn add_film(&mut self, x: i32, y: i32, other: &AovFilm) {
+        let overlap = get_overlap(x, y, self.width, self.height, other.width, other.height);
+        for (other_x, other_y) in overlap {
+            let (index, other_index) = (
+                self.index((x + other_x as i32) as u32, (y + other_y as i32) as u32),
+                other.index(other_x, other_y),
+            );
+            self.samples[index] += other.samples[other_index];
+            self.hits[index] += other.hits[other_index];
+            self.depth[index] += other.depth[other_index];
+            self.normal[index] = self.normal[index] + other.normal[other_index];
+            self.position[index] = self.position[index] + other.position[other_index];
+            self.ids[index] = self.ids[index].or(other.ids[other_index]);
+            self.albedo[index] = self.albedo[index] + other.albedo[other_index];
+            self.direct[index] = self.direct[index] + other.direct[other_index];
+            self.indirect[index] = self.indirect[index] + other.indirect[other_index];
+        }
+    }
+
+    fn get_hit_mean(&self, index: usize) -> Option<f64> {
+        match self.hits[index] {
+            0 => None,
+            n => Some(1.0 / f64::from(n)),
+        }
+    }
+
+    fn get_mean(&self, sums: &[RGBColor], x: u32, y: u32) -> RGBColor {
+        let index = self.index(x, y);
+        match self.samples[index] {
+            0 => RGBColor::new(0.0, 0.0, 0.0),
+            n => sums[index] * (1.0 / f64::from(n)),
+        }
+    }
+
+    ///None if no sample hit anything
+    pub fn get_depth(&self, x: u32, y: u32) -> Option<f64> {
+        let index = self.index(x, y);
+        self.get_hit_mean(index)
+            .map(|scale| self.depth[index] * scale)
+    }
+
+    ///Normalized mean normal, None if no sample hit anything
+    pub fn get_normal(&self, x: u32, y: u32) -> Option<Vector3D> {
+        let index = self.index(x, y);
+        self.get_hit_mean(index).map(|_| {
+            let normal = self.normal[index];
+            if normal.magnitude_sqr() > 0.0 {
+                normal.normalize()
+            } else {
+                normal
+            }
+        })
+    }
+
+    pub fn get_position(&self, x: u32, y: u32) -> Option<Point3D> {
+        let index = self.index(x, y);
+        self.get_hit_mean(index)
+            .map(|scale| Point3D::origin() + self.position[index] * scale)
+    }
+
+    pub fn get_object_id(&self, x: u32, y: u32) -> Option<u32> {
+        self.ids[self.index(x, y)].map(|(object_id, _)| object_id)
+    }
+
+    pub fn get_material_id(&self, x: u32, y: u32) -> Option<u32> {
+        self.ids[self.index(x, y)].map(|(_, material_id)| material_id)
+    }
+
+    pub fn get_albedo(&self, x: u32, y: u32) -> RGBColor {
+        self.get_mean(&self.albedo, x, y)
+    }
+
+    pub fn get_direct(&self, x: u32, y: u32) -> RGBColor {
+        self.get_mean(&self.direct, x, y)
+    }
+
+    pub fn get_indirect(&self, x: u32, y: u32) -> RGBColor {
+        self.get_mean(&self.indirect, x, y)
+    }
+
+    ///8 bit image to look at, pixels which hit nothing are black for the geometric AOVs
+    ///Depth goes from white for the nearest pixel to black for the farthest, normals
+    ///map -1..1 to 0..255, positions are scaled to the box around them, IDs get a
+    ///color each and the colors are gamma corrected like the film's
+    pub fn to_image(&self, aov: Aov) -> DynamicImage {
+        let pixels = (0..self.height).flat_map(|y| (0..self.width).map(move |x| (x, y)));
+        let hits: Vec<(u32, u32)> = pixels
+            .clone()
+            .filter(|&(x, y)| self.get_depth(x, y).is_some())
+            .collect();
+        let (mut low, mut high) = (
+            Vector3D::new(f64::MAX, f64::MAX, f64::MAX),
+            Vector3D::new(f64::MIN, f64::MIN, f64::MIN),
+        );
+        for &(x, y) in &hits {
+            let value = match aov {
+                Aov::Depth => {
+                    let depth = self.get_depth(x, y).unwrap();
+                    Vector3D::new(depth, depth, depth)
+                }
+                _ => self.get_position(x, y).unwrap() - Point3D::origin(),
+            };
+            low = Vector3D::new(
+                low.x().min(value.x()),
+                low.y().min(value.y()),
+                low.z().min(value.z()),
+            );
+            high = Vector3D::new(
+                high.x().max(value.x()),
+                high.y().max(value.y()),
+                high.z().max(value.z()),
+            );
+        }
+        let scale = |value: f64, axis: usize| {
+            let range = high.axis(axis) - low.axis(axis);
+            if range > 0.0 {
+                (value - low.axis(axis)) / range
+            } else {
+                1.0
+            }
+        };
+        let black = RGBColor::new(0.0, 0.0, 0.0);
+        let mut img = DynamicImage::new_rgb8(self.width, self.height);
+        for (x, y) in pixels {
+            let color = match aov {
+                Aov::Depth => self.get_depth(x, y).map_or(black, |depth| {
+                    let value = 1.0 - scale(depth, 0);
+                    RGBColor::new(value, value, value)
+                }),
+                Aov::Normal => self.get_normal(x, y).map_or(black, |normal| {
+                    RGBColor::from(normal * 0.5 + Vector3D::new(0.5, 0.5, 0.5))
+                }),
+                Aov::Position => self.get_position(x, y).map_or(black, |position| {
+                    RGBColor::new(
+                        scale(position.x(), 0),
+                        scale(position.y(), 1),
+                        scale(position.z(), 2),
+                    )
+                }),
+                Aov::ObjectId => self.get_object_id(x, y).map_or(black, get_id_color),
+                Aov::MaterialId => self.get_material_id(x, y).map_or(black, get_id_color),
+                Aov::Albedo => gamma_correct(self.get_albedo(x, y)),
+                Aov::Direct => gamma_correct(self.get_direct(x, y)),
+                Aov::Indirect => gamma_correct(self.get_indirect(x, y)),
+            };
+            img.put_pixel(
+                x,
+                y,
+                image::Rgba::from_channels(
+                    color.r_in_8_bit(),
+                    color.g_in_8_bit(),
+                    color.b_in_8_bit(),
+                    255,
+                ),
+            );
+        }
+        img
+    }
+
+    pub fn save(&self, aov: Aov, path: &str) -> io::Result<()> {
+        self.to_image(aov).save(path)
+    }
+
+    ///Linear values for an EXR: a layer per AOV, "depth.Z" and "object_id.id" and the
+    ///like, pixels which hit nothing get an infinite depth, a zero normal and position
+    ///and IDs of -1
+    pub(crate) fn get_channels(&self) -> Vec<Channel> {
+        let pixels: Vec<(u32, u32)> = (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .collect();
+        let channel = |name: String, value: &dyn Fn(u32, u32) -> f64| {
+            Channel::new(
+                &name,
+                pixels.iter().map(|&(x, y)| value(x, y) as f32).collect(),
+            )
+        };
+        let null = Vector3D::null();
+        let id = |id: Option<u32>| id.map_or(-1.0, f64::from);
+        let mut channels = Vec::new();
+        for aov in Aov::ALL.iter() {
+            let name = aov.get_name();
+            match aov {
+                Aov::Depth => channels.push(channel(format!("{}.Z", name), &|x, y| {
+                    self.get_depth(x, y).unwrap_or(f64::INFINITY)
+                })),
+                Aov::ObjectId => channels.push(channel(format!("{}.id", name), &|x, y| {
+                    id(self.get_object_id(x, y))
+                })),
+                Aov::MaterialId => channels.push(channel(format!("{}.id", name), &|x, y| {
+                    id(self.get_material_id(x, y))
+                })),
+                Aov::Normal | Aov::Position => {
+                    let vector = |x, y| match aov {
+                        Aov::Normal => self.get_normal(x, y).unwrap_or(null),
+                        _ => self
+                            .get_position(x, y)
+                            .map_or(null, |position| position - Point3D::origin()),
+                    };
+                    for (axis, suffix) in ["X", "Y", "Z"].iter().enumerate() {
+                        channels.push(channel(format!("{}.{}", name, suffix), &|x, y| {
+                            vector(x, y).axis(axis)
+                        }));
+                    }
+                }
+                Aov::Albedo | Aov::Direct | Aov::Indirect => {
+                    let color = |x, y| match aov {
+                        Aov::Albedo => self.get_albedo(x, y),
+                        Aov::Direct => self.get_direct(x, y),
+                        _ => self.get_indirect(x, y),
+                    };
+                    channels.push(channel(format!("{}.R", name), &|x, y| color(x, y).r));
+                    channels.push(channel(format!("{}.G", name), &|x, y| color(x, y).g));
+                    channels.push(channel(format!("{}.B", name), &|x, y| color(x, y).b));
+                }
+            }
+        }
+        channels
+    }
+
+    ///Writes the sums exactly, little endian, for every pixel row by row: the sample and
+    ///hit counts as u32, the depth, normal, position, albedo, direct and indirect sums
+    ///as f64 and the object and material IDs as u32, u32::MAX for none
+    pub fn write(&self, writer: &mut dyn Write) -> io::Result<()> {
+        for index in 0..self.samples.len() {
+            writer.write_all(&self.samples[index].to_le_bytes())?;
+            writer.write_all(&self.hits[index].to_le_bytes())?;
+            let (normal, position) = (self.normal[index], self.position[index]);
+            let (albedo, direct, indirect) =
+                (self.albedo[index], self.direct[index], self.indirect[index]);
+            let values = [
+                self.depth[index],
+                normal.x(),
+                normal.y(),
+                normal.z(),
+                position.x(),
+                position.y(),
+                position.z(),
+                albedo.r,
+                albedo.g,
+                albedo.b,
+                direct.r,
+                direct.g,
+                direct.b,
+                indirect.r,
+                indirect.g,
+                indirect.b,
+            ];
+            for value in values.iter() {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+            let (object_id, material_id) = self.ids[index].unwrap_or((u32::MAX, u32::MAX));
+            writer.write_all(&object_id.to_le_bytes())?;
+            writer.write_all(&material_id.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    ///Reads the AOVs of a film of this size written by write
+    pub fn read(reader: &mut dyn Read, width: u32, height: u32) -> io::Result<AovFilm> {
+        let mut film = AovFilm::new(width, height);
+        let read_vector = |reader: &mut dyn Read| -> io::Result<Vector3D> {
+            Ok(Vector3D::new(
+                read_f64(reader)?,
+                read_f64(reader)?,
+                read_f64(reader)?,
+            ))
+        };
+        let read_color = |reader: &mut dyn Read| -> io::Result<RGBColor> {
+            Ok(RGBColor::new(
+                read_f64(reader)?,
+                read_f64(reader)?,
+                read_f64(reader)?,
+            ))
+        };
+        for index in 0..film.samples.len() {
+            film.samples[index] = read_u32(reader)?;
+            film.hits[index] = read_u32(reader)?;
+            film.depth[index] = read_f64(reader)?;
+            film.normal[index] = read_vector(reader)?;
+            film.position[index] = read_vector(reader)?;
+            film.albedo[index] = read_color(reader)?;
+            film.direct[index] = read_color(reader)?;
+            film.indirect[index] = read_color(reader)?;
+            film.ids[index] = match (read_u32(reader)?, read_u32(reader)?) {
+                (u32::MAX, u32::MAX) => None,
+                ids => Some(ids),
+            };
+        }
+        Ok(film)
+    }
+}
+
+///Distinct, fairly bright color for an ID
+fn get_id_color(id: u32) -> RGBColor {
+    //Golden ratio steps around the hue circle keep neighbouring IDs apart
+    let hue = (f64::from(id) * 0.618_033_988_75).fract() * 6.0;
+    let channel = |offset: f64| {
+        let distance = ((hue - offset).rem_euclid(6.0) - 3.0).abs();
+        (distance - 1.0).clamp(0.0, 1.0) * 0.8 + 0.2
+    };
+    RGBColor::new(channel(0.0), channel(2.0), channel(4.0))
+}
@@ -0,0 +1,265 @@
+use crate::film::{read_f64, read_u32, read_u64, Film};
+use crate::graphics::{Filter, FilterKind, SamplerKind, ViewPlane};
+use crate::integrators::Integrator;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+
+const MAGIC: &[u8; 4] = b"RPCK";
+const VERSION: u32 = 7;
+///Longer integrator names are taken for a damaged file
+const MAX_NAME_LENGTH: usize = 64;
+
+///State of a render between two passes, enough to carry on where it stopped
+///The samplers derive every random number from the seed, the pixel and the sample
+///index, so a render resumed from a checkpoint gives the same image as one which
+///never stopped, as long as the scene and the integrator are the same
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    pub film: Film,
+    ///Passes done
+    pub samples: u32,
+    ///The view plane's, the stratified sampler splits each dimension into this many strata
+    pub samples_per_pixel: u32,
+    pub seed: u64,
+    pub sampler: SamplerKind,
+    ///The film's sums are weighted by it
+    pub filter: Filter,
+    ///See `Integrator::get_name`
+    pub integrator: String,
+    ///The integrator's, see `Integrator::get_max_depth`
+    pub max_depth: Option<u32>,
+    pub roulette_depth: Option<u32>,
+}
+
+impl Checkpoint {
+    pub fn new(
+        film: Film,
+        samples: u32,
+        view_plane: &ViewPlane,
+        integrator: &dyn Integrator,
+    ) -> Self {
+        Checkpoint {
+            film,
+            samples,
+            samples_per_pixel: view_plane.get_samples(),
+            seed: view_plane.get_seed(),
+            sampler: view_plane.get_sampler(),
+            filter: view_plane.get_filter(),
+            integrator: integrator.get_name().to_string(),
+            max_depth: integrator.get_max_depth(),
+            roulette_depth: integrator.get_roulette_depth(),
+        }
+    }
+
+    ///Whether a render of the view plane with the integrator can carry on from this
+    ///checkpoint
+    pub fn check(&self, view_plane: &ViewPlane, integrator: &dyn Integrator) -> Result<(), String> {
+        if (self.film.get_width(), self.film.get_height())
+            != (view_plane.get_hres(), view_plane.get_vres())
+        {
+            return Err(format!(
+                "checkpoint is {}x{}, the view plane {}x{}",
+                self.film.get_width(),
+                self.film.get_height(),
+                view_plane.get_hres(),
+                view_plane.get_vres()
+            ));
+        }
+        if self.samples_per_pixel != view_plane.get_samples() {
+            return Err(format!(
+                "checkpoint was rendered with {} samples per pixel, the view plane has {}",
+                self.samples_per_pixel,
+                view_plane.get_samples()
+            ));
+        }
+        if (self.seed, self.sampler) != (view_plane.get_seed(), view_plane.get_sampler()) {
+            return Err(format!(
+                "checkpoint was rendered with the {:?} sampler and seed {}, the view plane uses the {:?} sampler and seed {}",
+                self.sampler,
+                self.seed,
+                view_plane.get_sampler(),
+                view_plane.get_seed()
+            ));
+        }
+        if self.filter != view_plane.get_filter() {
+            return Err(format!(
+                "checkpoint was rendered with a {:?} filter of radius {}, the view plane uses a {:?} filter of radius {}",
+                self.filter.get_kind(),
+                self.filter.get_radius(),
+                view_plane.get_filter().get_kind(),
+                view_plane.get_filter().get_radius()
+            ));
+        }
+        if self.integrator != integrator.get_name() {
+            return Err(format!(
+                "checkpoint was rendered with the {} integrator, not {}",
+                self.integrator,
+                integrator.get_name()
+            ));
+        }
+        let depths = (integrator.get_max_depth(), integrator.get_roulette_depth());
+        if (self.max_depth, self.roulette_depth) != depths {
+            return Err(format!(
+                "checkpoint was rendered with a max depth of {} and a roulette depth of {}, the integrator has {} and {}",
+                describe_depth(self.max_depth),
+                describe_depth(self.roulette_depth),
+                describe_depth(depths.0),
+                describe_depth(depths.1)
+            ));
+        }
+        Ok(())
+    }
+
+    ///Little endian: the magic bytes "RPCK", the format version, the passes done and the
+    ///samples per pixel as u32, the seed as u64, the sampler as u8, the filter as u8 and
+    ///its radius as f64, the integrator's name as its length in bytes as u32 followed by
+    ///the UTF-8 bytes, the max and roulette depths each as a u8 which is 1 if there's
+    ///a depth followed by the depth as u32, and then the film, see `Film::write`
+    pub fn write(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&self.samples.to_le_bytes())?;
+        writer.write_all(&self.samples_per_pixel.to_le_bytes())?;
+        writer.write_all(&self.seed.to_le_bytes())?;
+        writer.write_all(&[sampler_to_byte(self.sampler)])?;
+        writer.write_all(&[filter_to_byte(self.filter.get_kind())])?;
+        writer.write_all(&self.filter.get_radius().to_le_bytes())?;
+        writer.write_all(&(self.integrator.len() as u32).to_le_bytes())?;
+        writer.write_all(self.integrator.as_bytes())?;
+        write_depth(writer, self.max_depth)?;
+        write_depth(writer, self.roulette_depth)?;
+        self.film.write(writer)
+    }
+
+    pub fn read(reader: &mut dyn Read) -> io::Result<Checkpoint> {
+        let invalid =
+            |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a checkpoint"));
+        }
+        if read_u32(reader)? != VERSION {
+            return Err(invalid("unsupported checkpoint version"));
+        }
+        let samples = read_u32(reader)?;
+        let samples_per_pixel = read_u32(reader)?;
+        let seed = read_u64(reader)?;
+        let mut sampler = [0; 1];
+        reader.read_exact(&mut sampler)?;
+        let sampler = sampler_from_byte(sampler[0]).ok_or_else(|| invalid("unknown sampler"))?;
+        let mut filter = [0; 1];
+        reader.read_exact(&mut filter)?;
+        let filter_kind = filter_from_byte(filter[0]).ok_or_else(|| invalid("unknown filter"))?;
+        let filter = Filter::new(filter_kind, read_f64(reader)?);
+        let name_length = read_u32(reader)? as usize;
+        if name_length > MAX_NAME_LENGTH {
+            return Err(invalid("integrator name too long"));
+        }
+        let mut integrator = vec![0; name_length];
+        reader.read_exact(&mut integrator)?;
+        let integrator =
+            String::from_utf8(integrator).map_err(|_| invalid("integrator name isn't UTF-8"))?;
+        let max_depth = read_depth(reader)?;
+        let roulette_depth = read_depth(reader)?;
+        let film = Film::read(reader)?;
+        Ok(Checkpoint {
+            film,
+            samples,
+            samples_per_pixel,
+            seed,
+            sampler,
+            filter,
+            integrator,
+            max_depth,
+            roulette_depth,
+        })
+    }
+
+    ///Writes to a temporary file first so a crash while saving leaves the last
+    ///checkpoint intact
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let temporary_path = format!("{}.tmp", path);
+        {
+            let mut writer = BufWriter::new(File::create(&temporary_path)?);
+            self.write(&mut writer)?;
+            writer.flush()?;
+        }
+        std::fs::rename(&temporary_path, path)
+    }
+
+    pub fn load(path: &str) -> io::Result<Checkpoint> {
+        Checkpoint::read(&mut BufReader::new(File::open(path)?))
+    }
+}
+
+///Where a render saving its image to path keeps its checkpoint
+pub fn get_checkpoint_path(path: &str) -> String {
+    format!("{}.checkpoint", path)
+}
+
+fn describe_depth(depth: Option<u32>) -> String {
+    depth.map_or("none".to_string(), |depth| depth.to_string())
+}
+
+fn write_depth(writer: &mut dyn Write, depth: Option<u32>) -> io::Result<()> {
+    match depth {
+        Some(depth) => {
+            writer.write_all(&[1])?;
+            writer.write_all(&depth.to_le_bytes())
+        }
+        None => writer.write_all(&[0]),
+    }
+}
+
+fn read_depth(reader: &mut dyn Read) -> io::Result<Option<u32>> {
+    let mut flag = [0; 1];
+    reader.read_exact(&mut flag)?;
+    match flag[0] {
+        0 => Ok(None),
+        1 => Ok(Some(read_u32(reader)?)),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, "invalid depth")),
+    }
+}
+
+fn sampler_to_byte(sampler: SamplerKind) -> u8 {
+    match sampler {
+        SamplerKind::Independent => 0,
+        SamplerKind::Stratified => 1,
+        SamplerKind::Halton => 2,
+        SamplerKind::Sobol => 3,
+        SamplerKind::BlueNoise => 4,
+    }
+}
+
+fn sampler_from_byte(byte: u8) -> Option<SamplerKind> {
+    match byte {
+        0 => Some(SamplerKind::Independent),
+        1 => Some(SamplerKind::Stratified),
+        2 => Some(SamplerKind::Halton),
+        3 => Some(SamplerKind::Sobol),
+        4 => Some(SamplerKind::BlueNoise),
+        _ => None,
+    }
+}
+
+fn filter_to_byte(filter: FilterKind) -> u8 {
+    match filter {
+        FilterKind::Box => 0,
+        FilterKind::Tent => 1,
+        FilterKind::Gaussian => 2,
+        FilterKind::MitchellNetravali => 3,
+        FilterKind::Lanczos => 4,
+    }
+}
+
+fn filter_from_byte(byte: u8) -> Option<FilterKind> {
+    match byte {
+        0 => Some(FilterKind::Box),
+        1 => Some(FilterKind::Tent),
+        2 => Some(FilterKind::Gaussian),
+        3 => Some(FilterKind::MitchellNetravali),
+        4 => Some(FilterKind::Lanczos),
+        _ => None,
+    }
+}
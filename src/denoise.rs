@@ -0,0 +1,193 @@
+//! Denoising of finished renders with an edge-avoiding à-trous wavelet filter
+//!
+//! Each pass averages every pixel with 5x5 others spaced further apart than in the pass
+//! before, weighted by how alike their colors are and, from the AOVs, how alike their
+//! normals, albedos and depths are, so noise is smoothed out while the edges of objects
+//! and materials stay sharp. See Dammertz et al., "Edge-Avoiding À-Trous Wavelet
+//! Transform for fast Global Illumination Filtering".
+
+use crate::film::{gamma_correct, Film};
+use crate::graphics::{RGBColor, Vector3D};
+
+///B3 spline, the weights of the taps at -2..=2 steps
+const KERNEL: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+///Keeps pixels whose samples all agreed from refusing every neighbour
+const MIN_VARIANCE: f64 = 1e-6;
+
+///Settings of the filter, larger sigmas smooth more across differences
+#[derive(Debug, Clone)]
+pub struct Denoiser {
+    ///Passes, the filter reaches 2^(iterations+1)-2 pixels away
+    pub iterations: u32,
+    ///Differences of the gamma corrected colors are measured in standard errors of the
+    ///pixels, as estimated from their samples and carried through the passes
+    pub color_sigma: f64,
+    pub normal_sigma: f64,
+    pub albedo_sigma: f64,
+    ///Relative to the depth, per pixel of distance
+    pub depth_sigma: f64,
+}
+
+///What the filter knows about a pixel
+struct Guide {
+    ///None for pixels which hit nothing
+    normal: Option<Vector3D>,
+    depth: Option<f64>,
+    albedo: RGBColor,
+}
+
+impl Denoiser {
+    pub fn new() -> Self {
+        Denoiser {
+            iterations: 5,
+            color_sigma: 6.0,
+            normal_sigma: 0.3,
+            albedo_sigma: 0.1,
+            depth_sigma: 0.02,
+        }
+    }
+
+    ///Denoised copy of the film's colors, one sample per pixel
+    ///The film must record AOVs, see `SimpleTracer::with_aovs`
+    pub fn denoise(&self, film: &Film) -> Result<Film, String> {
+        let aovs = film
+            .get_aovs()
+            .ok_or("denoising needs a film which records AOVs")?;
+        let (width, height) = (film.get_width(), film.get_height());
+        let guides: Vec<Guide> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| Guide {
+                normal: aovs.get_normal(x, y),
+                depth: aovs.get_depth(x, y),
+                albedo: aovs.get_albedo(x, y),
+            })
+            .collect();
+        let mut colors = film.get_pixels();
+        //Pixels with fewer than two samples give no estimate and are smoothed freely
+        let mut variances: Vec<f64> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                film.get_error(x, y)
+                    .map_or(f64::INFINITY, |error| error * error)
+            })
+            .collect();
+        for iteration in 0..self.iterations {
+            let (filtered, filtered_variances) =
+                self.filter(&colors, &variances, &guides, width, height, iteration);
+            colors = filtered;
+            variances = filtered_variances;
+        }
+        Ok(Film::from_pixels(width, height, &colors))
+    }
+
+    ///One pass with taps 2^iteration pixels apart, returns the filtered colors and the
+    ///variances of their mean brightness
+    fn filter(
+        &self,
+        colors: &[RGBColor],
+        variances: &[f64],
+        guides: &[Guide],
+        width: u32,
+        height: u32,
+        iteration: u32,
+    ) -> (Vec<RGBColor>, Vec<f64>) {
+        let step = 1i64 << iteration;
+        let gamma_colors: Vec<RGBColor> =
+            colors.iter().map(|color| gamma_correct(*color)).collect();
+        let (width, height) = (i64::from(width), i64::from(height));
+        let mut filtered = Vec::with_capacity(colors.len());
+        let mut filtered_variances = Vec::with_capacity(colors.len());
+        for y in 0..height {
+            for x in 0..width {
+                let index = (y * width + x) as usize;
+                let (guide, gamma_color) = (&guides[index], gamma_colors[index]);
+                let mut sum = RGBColor::new(0.0, 0.0, 0.0);
+                let (mut total_weight, mut variance) = (0.0, 0.0);
+                for (ky, kernel_y) in KERNEL.iter().enumerate() {
+                    for (kx, kernel_x) in KERNEL.iter().enumerate() {
+                        let (dx, dy) = ((kx as i64 - 2) * step, (ky as i64 - 2) * step);
+                        let (other_x, other_y) = (x + dx, y + dy);
+                        if other_x < 0 || other_x >= width || other_y < 0 || other_y >= height {
+                            continue;
+                        }
+                        let other = (other_y * width + other_x) as usize;
+                        let distance = ((dx * dx + dy * dy) as f64).sqrt();
+                        let weight = kernel_x
+                            * kernel_y
+                            * self.get_color_weight(
+                                gamma_color,
+                                gamma_colors[other],
+                                variances[index] + variances[other],
+                            )
+                            * self.get_guide_weight(guide, &guides[other], distance);
+                        //An unknown variance times a weight of 0 would be NaN
+                        if weight == 0.0 {
+                            continue;
+                        }
+                        sum = sum + colors[other] * weight;
+                        total_weight += weight;
+                        variance = if variances[other].is_finite() {
+                            variance + weight * weight * variances[other]
+                        } else {
+                            f64::INFINITY
+                        };
+                    }
+                }
+                //The centre tap always has a weight of more than 0
+                filtered.push(sum * (1.0 / total_weight));
+                filtered_variances.push(variance / (total_weight * total_weight));
+            }
+        }
+        (filtered, filtered_variances)
+    }
+
+    ///variance-of the difference of the two colors' brightness, an unknown one doesn't
+    ///tell the colors apart at all
+    fn get_color_weight(&self, a: RGBColor, b: RGBColor, variance: f64) -> f64 {
+        if !variance.is_finite() {
+            return 1.0;
+        }
+        let difference = a - b;
+        //Mean of the channels, comparable with the variance of the brightness
+        let distance_sqr = (difference.r * difference.r
+            + difference.g * difference.g
+            + difference.b * difference.b)
+            / 3.0;
+        (-distance_sqr / (self.color_sigma * self.color_sigma * variance + MIN_VARIANCE)).exp()
+    }
+
+    ///Pixels which hit something are never mixed with ones which didn't
+    fn get_guide_weight(&self, guide: &Guide, other: &Guide, distance: f64) -> f64 {
+        let albedo = get_distance_weight(guide.albedo, other.albedo, self.albedo_sigma);
+        match (guide.normal, other.normal, guide.depth, other.depth) {
+            (Some(normal), Some(other_normal), Some(depth), Some(other_depth)) => {
+                let normal_difference = normal - other_normal;
+                let normal_weight = (-(normal_difference * normal_difference)
+                    / (self.normal_sigma * self.normal_sigma))
+                    .exp();
+                let depth_scale = self.depth_sigma * depth.abs() * distance;
+                let depth_weight = if depth_scale > 0.0 {
+                    (-(depth - other_depth).abs() / depth_scale).exp()
+                } else {
+                    1.0
+                };
+                albedo * normal_weight * depth_weight
+            }
+            (None, None, ..) => albedo,
+            _ => 0.0,
+        }
+    }
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Denoiser::new()
+    }
+}
+
+fn get_distance_weight(a: RGBColor, b: RGBColor, sigma: f64) -> f64 {
+    let difference = a - b;
+    let distance_sqr =
+        difference.r * difference.r + difference.g * difference.g + difference.b * difference.b;
+    (-distance_sqr / (sigma * sigma)).exp()
+}
@@ -0,0 +1,118 @@
+//! Minimal OpenEXR writer, the image crate can't encode EXR
+//!
+//! Writes single part scanline images without compression, every channel as 32 bit
+//! floats, which any EXR reader accepts.
+
+use std::io::{self, Write};
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+///Format version 2, no flags: scanlines, short names, single part
+const VERSION: [u8; 4] = [2, 0, 0, 0];
+const PIXEL_TYPE_FLOAT: i32 = 2;
+///Longer names need the long names flag
+const MAX_NAME_LENGTH: usize = 31;
+
+///A named channel with a value for every pixel row by row
+pub struct Channel {
+    pub name: String,
+    pub values: Vec<f32>,
+}
+
+impl Channel {
+    pub fn new(name: &str, values: Vec<f32>) -> Self {
+        Channel {
+            name: name.to_string(),
+            values,
+        }
+    }
+}
+
+///Channels can be in any order, readers group them into layers by the part of their
+///name before the last dot, e.g. "normal.X"
+pub fn write(
+    writer: &mut dyn Write,
+    width: u32,
+    height: u32,
+    channels: &[Channel],
+) -> io::Result<()> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+    if width == 0 || height == 0 || width > i32::MAX as u32 || height > i32::MAX as u32 {
+        return Err(invalid(format!("can't write a {}x{} EXR", width, height)));
+    }
+    let len = width as usize * height as usize;
+    for channel in channels {
+        if channel.name.is_empty() || channel.name.len() > MAX_NAME_LENGTH {
+            return Err(invalid(format!("bad EXR channel name {:?}", channel.name)));
+        }
+        if channel.values.len() != len {
+            return Err(invalid(format!(
+                "EXR channel {} has {} values for {} pixels",
+                channel.name,
+                channel.values.len(),
+                len
+            )));
+        }
+    }
+    //The channel list and the pixel data are both in the order of the names
+    let mut channels: Vec<&Channel> = channels.iter().collect();
+    channels.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut channel_list = Vec::new();
+    for channel in &channels {
+        channel_list.extend_from_slice(channel.name.as_bytes());
+        channel_list.push(0);
+        channel_list.extend_from_slice(&PIXEL_TYPE_FLOAT.to_le_bytes());
+        //pLinear and three reserved bytes
+        channel_list.extend_from_slice(&[0; 4]);
+        //x and y sampling
+        channel_list.extend_from_slice(&1i32.to_le_bytes());
+        channel_list.extend_from_slice(&1i32.to_le_bytes());
+    }
+    channel_list.push(0);
+    let mut window = Vec::new();
+    for value in [0, 0, width as i32 - 1, height as i32 - 1].iter() {
+        window.extend_from_slice(&value.to_le_bytes());
+    }
+
+    let mut header = Vec::new();
+    let mut attribute = |name: &str, kind: &str, value: &[u8]| {
+        header.extend_from_slice(name.as_bytes());
+        header.push(0);
+        header.extend_from_slice(kind.as_bytes());
+        header.push(0);
+        header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+        header.extend_from_slice(value);
+    };
+    attribute("channels", "chlist", &channel_list);
+    attribute("compression", "compression", &[0]);
+    attribute("dataWindow", "box2i", &window);
+    attribute("displayWindow", "box2i", &window);
+    attribute("lineOrder", "lineOrder", &[0]);
+    attribute("pixelAspectRatio", "float", &1f32.to_le_bytes());
+    attribute("screenWindowCenter", "v2f", &[0; 8]);
+    attribute("screenWindowWidth", "float", &1f32.to_le_bytes());
+    header.push(0);
+
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&VERSION)?;
+    writer.write_all(&header)?;
+    //One scanline per block, each the line number, the data size and the data
+    let line_size = width as usize * channels.len() * 4;
+    let first_line = (MAGIC.len() + VERSION.len() + header.len()) as u64 + 8 * u64::from(height);
+    for y in 0..u64::from(height) {
+        writer.write_all(&(first_line + y * (8 + line_size as u64)).to_le_bytes())?;
+    }
+    let mut line = Vec::with_capacity(line_size);
+    for y in 0..height as usize {
+        line.clear();
+        for channel in &channels {
+            for value in &channel.values[y * width as usize..(y + 1) * width as usize] {
+                line.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        writer.write_all(&(y as i32).to_le_bytes())?;
+        writer.write_all(&(line_size as i32).to_le_bytes())?;
+        writer.write_all(&line)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,438 @@
+use crate::aov::{AovFilm, AovSample};
+use crate::exr::{self, Channel};
+use crate::graphics::{Filter, RGBColor};
+use image::{DynamicImage, GenericImage, Pixel};
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+
+///Pixels whose weight sum is at most this fraction of the sum of the absolute weights are
+///left black, dividing by what's left of the weight would blow up their noise
+const MIN_WEIGHT_FRACTION: f64 = 1e-3;
+
+///Running sums of the samples reaching each pixel of an image or of a part of one,
+///weighted by the reconstruction filter
+///Colors are linear, gamma correction is only applied when converting to an image
+#[derive(Debug, Clone)]
+pub struct Film {
+    width: u32,
+    height: u32,
+    sum: Vec<RGBColor>,
+    ///Sum of the gamma corrected brightness of the samples and of its square weighted by
+    ///the absolute weights, for their variance
+    sum_brightness: Vec<f64>,
+    sum_sqr: Vec<f64>,
+    ///Sum of the weights, of their absolute values and of their squares, for the effective
+    ///number of samples, the negative lobes of some filters count like the positive ones
+    weights: Vec<f64>,
+    weights_abs: Vec<f64>,
+    weights_sqr: Vec<f64>,
+    ///Samples taken in each pixel, wherever their weight went
+    samples: Vec<u32>,
+    aovs: Option<AovFilm>,
+}
+
+impl Film {
+    pub fn new(width: u32, height: u32) -> Self {
+        let len = (width * height) as usize;
+        Film {
+            width,
+            height,
+            sum: vec![RGBColor::new(0.0, 0.0, 0.0); len],
+            sum_brightness: vec![0.0; len],
+            sum_sqr: vec![0.0; len],
+            weights: vec![0.0; len],
+            weights_abs: vec![0.0; len],
+            weights_sqr: vec![0.0; len],
+            samples: vec![0; len],
+            aovs: None,
+        }
+    }
+
+    ///A film with one sample of each color, row by row
+    pub fn from_pixels(width: u32, height: u32, pixels: &[RGBColor]) -> Self {
+        let mut film = Film::new(width, height);
+        for (index, color) in pixels.iter().enumerate() {
+            let index = index as u32;
+            film.add_sample(index % width, index / width, *color);
+        }
+        film
+    }
+
+    ///Also records the AOVs of the samples, see `add_aov_sample`
+    pub fn with_aovs(mut self) -> Self {
+        self.aovs = Some(AovFilm::new(self.width, self.height));
+        self
+    }
+
+    ///None unless the film was created with_aovs
+    pub fn get_aovs(&self) -> Option<&AovFilm> {
+        self.aovs.as_ref()
+    }
+
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        (y * self.width + x) as usize
+    }
+
+    ///Adds a sample to the pixel alone, like a box filter
+    pub fn add_sample(&mut self, x: u32, y: u32, color: RGBColor) {
+        let index = self.index(x, y);
+        self.add_weighted(index, color, 1.0);
+        self.samples[index] += 1;
+    }
+
+    ///Adds a sample taken in the pixel at (x,y) to the pixels around it within the filter's
+    ///radius, weighted by the filter
+    ///offset-where the sample was taken, from the top left corner of the pixel
+    ///Pixels outside the film are left out, so a film covering part of an image needs
+    ///`Filter::get_padding` more pixels on every side than the samples taken in it
+    pub fn add_filtered_sample(
+        &mut self,
+        x: u32,
+        y: u32,
+        offset: (f64, f64),
+        color: RGBColor,
+        filter: &Filter,
+    ) {
+        let (padding, radius) = (i64::from(filter.get_padding()), filter.get_radius());
+        for j in -padding..=padding {
+            for i in -padding..=padding {
+                let (dx, dy) = (i as f64 + 0.5 - offset.0, j as f64 + 0.5 - offset.1);
+                //A sample on the edge of its own pixel still counts there, neighbours
+                //just out of reach don't
+                if (i, j) != (0, 0) && (dx.abs() >= radius || dy.abs() >= radius) {
+                    continue;
+                }
+                let (other_x, other_y) = (i64::from(x) + i, i64::from(y) + j);
+                if other_x < 0
+                    || other_y < 0
+                    || other_x >= i64::from(self.width)
+                    || other_y >= i64::from(self.height)
+                {
+                    continue;
+                }
+                let weight = filter.evaluate(dx, dy);
+                if weight != 0.0 {
+                    let index = self.index(other_x as u32, other_y as u32);
+                    self.add_weighted(index, color, weight);
+                }
+            }
+        }
+        let index = self.index(x, y);
+        self.samples[index] += 1;
+    }
+
+    fn add_weighted(&mut self, index: usize, color: RGBColor, weight: f64) {
+        let brightness = get_brightness(color);
+        self.sum[index] = self.sum[index] + color * weight;
+        self.sum_brightness[index] += brightness * weight.abs();
+        self.sum_sqr[index] += brightness * brightness * weight.abs();
+        self.weights[index] += weight;
+        self.weights_abs[index] += weight.abs();
+        self.weights_sqr[index] += weight * weight;
+    }
+
+    ///Ignored unless the film records AOVs
+    pub fn add_aov_sample(&mut self, x: u32, y: u32, sample: &AovSample) {
+        if let Some(aovs) = self.aovs.as_mut() {
+            aovs.add_sample(x, y, sample);
+        }
+    }
+
+    ///Adds the samples of a film covering the pixels from (x,y) onwards, the parts of it
+    ///outside this film are left out
+    pub fn add_film(&mut self, x: i32, y: i32, other: &Film) {
+        let overlap = get_overlap(x, y, self.width, self.height, other.width, other.height);
+        for (other_x, other_y) in overlap {
+            let (index, other_index) = (
+                self.index((x + other_x as i32) as u32, (y + other_y as i32) as u32),
+                other.index(other_x, other_y),
+            );
+            self.sum[index] = self.sum[index] + other.sum[other_index];
+            self.sum_brightness[index] += other.sum_brightness[other_index];
+            self.sum_sqr[index] += other.sum_sqr[other_index];
+            self.weights[index] += other.weights[other_index];
+            self.weights_abs[index] += other.weights_abs[other_index];
+            self.weights_sqr[index] += other.weights_sqr[other_index];
+            self.samples[index] += other.samples[other_index];
+        }
+        if let (Some(aovs), Some(other_aovs)) = (self.aovs.as_mut(), other.aovs.as_ref()) {
+            aovs.add_film(x, y, other_aovs);
+        }
+    }
+
+    pub fn get_samples(&self, x: u32, y: u32) -> u32 {
+        self.samples[self.index(x, y)]
+    }
+
+    ///Sum of the filter's weights of the samples reaching the pixel, the number of samples
+    ///taken in it with a box filter
+    pub fn get_weight(&self, x: u32, y: u32) -> f64 {
+        self.weights[self.index(x, y)]
+    }
+
+    ///Linear weighted mean of the samples, black if none reach the pixel or if the negative
+    ///lobes of the filter cancel out nearly all of their weight
+    pub fn get_color(&self, x: u32, y: u32) -> RGBColor {
+        let index = self.index(x, y);
+        if !self.has_weight(index) {
+            return RGBColor::new(0.0, 0.0, 0.0);
+        }
+        self.sum[index] * (1.0 / self.weights[index])
+    }
+
+    ///Whether the weight sum is far enough above 0 to divide by
+    fn has_weight(&self, index: usize) -> bool {
+        self.weights[index] > MIN_WEIGHT_FRACTION * self.weights_abs[index]
+    }
+
+    ///Estimated standard error of the pixel's mean brightness, None with fewer than two
+    ///samples' worth of weight or a weight sum get_color treats as none
+    pub fn get_error(&self, x: u32, y: u32) -> Option<f64> {
+        let index = self.index(x, y);
+        let (weight, weight_sqr) = (self.weights_abs[index], self.weights_sqr[index]);
+        if weight_sqr <= 0.0 || !self.has_weight(index) {
+            return None;
+        }
+        //Kish's effective sample size, the number of samples with a box filter
+        let n = weight * weight / weight_sqr;
+        if n < 2.0 {
+            return None;
+        }
+        let mean = self.sum_brightness[index] / weight;
+        let variance = ((self.sum_sqr[index] / weight - mean * mean) * n / (n - 1.0)).max(0.0);
+        Some((variance / n).sqrt())
+    }
+
+    ///Largest standard error of the pixels in a region, infinite while some pixel has too few samples
+    pub fn get_max_error(&self, x: u32, y: u32, width: u32, height: u32) -> f64 {
+        let mut max: f64 = 0.0;
+        for y in y..y + height {
+            for x in x..x + width {
+                max = max.max(self.get_error(x, y).unwrap_or(f64::INFINITY));
+            }
+        }
+        max
+    }
+
+    ///Average standard error over all pixels, infinite while some pixel has too few samples
+    pub fn get_noise(&self) -> f64 {
+        let mut total = 0.0;
+        for y in 0..self.height {
+            for x in 0..self.width {
+                match self.get_error(x, y) {
+                    Some(error) => total += error,
+                    None => return f64::INFINITY,
+                }
+            }
+        }
+        total / f64::from(self.width * self.height)
+    }
+
+    ///Linear mean color of every pixel row by row, not clamped
+    pub fn get_pixels(&self) -> Vec<RGBColor> {
+        (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .map(|(x, y)| self.get_color(x, y))
+            .collect()
+    }
+
+    ///Gamma corrected 8 bit image
+    pub fn to_image(&self) -> DynamicImage {
+        let mut img = DynamicImage::new_rgb8(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let color = gamma_correct(self.get_color(x, y));
+                img.put_pixel(
+                    x,
+                    y,
+                    image::Rgba::from_channels(
+                        color.r_in_8_bit(),
+                        color.g_in_8_bit(),
+                        color.b_in_8_bit(),
+                        255,
+                    ),
+                );
+            }
+        }
+        img
+    }
+
+    ///Saves the gamma corrected image, or an EXR if the path ends in .exr
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("exr") => self.save_exr(path),
+            _ => self.to_image().save(path),
+        }
+    }
+
+    ///Linear 32 bit float EXR with the color as R, G and B and a layer for each AOV
+    ///if the film records them, see `AovFilm::get_channels`
+    pub fn save_exr(&self, path: &str) -> io::Result<()> {
+        let pixels = self.get_pixels();
+        let mut channels = vec![
+            Channel::new("R", pixels.iter().map(|color| color.r as f32).collect()),
+            Channel::new("G", pixels.iter().map(|color| color.g as f32).collect()),
+            Channel::new("B", pixels.iter().map(|color| color.b as f32).collect()),
+        ];
+        if let Some(aovs) = &self.aovs {
+            channels.extend(aovs.get_channels());
+        }
+        let mut writer = BufWriter::new(File::create(path)?);
+        exr::write(&mut writer, self.width, self.height, &channels)?;
+        writer.flush()
+    }
+
+    ///Writes the sums exactly, little endian: width and height as u32, then for every
+    ///pixel row by row the color sum, the brightness sums and the weight sums as f64 and
+    ///the sample count as u32, then 1 and the AOVs as in `AovFilm::write` if the film records them or 0
+    pub fn write(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(&self.width.to_le_bytes())?;
+        writer.write_all(&self.height.to_le_bytes())?;
+        for index in 0..self.samples.len() {
+            let sum = self.sum[index];
+            for value in [
+                sum.r,
+                sum.g,
+                sum.b,
+                self.sum_brightness[index],
+                self.sum_sqr[index],
+                self.weights[index],
+                self.weights_abs[index],
+                self.weights_sqr[index],
+            ]
+            .iter()
+            {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+            writer.write_all(&self.samples[index].to_le_bytes())?;
+        }
+        match &self.aovs {
+            Some(aovs) => {
+                writer.write_all(&[1])?;
+                aovs.write(writer)
+            }
+            None => writer.write_all(&[0]),
+        }
+    }
+
+    ///Reads a film written by write
+    pub fn read(reader: &mut dyn Read) -> io::Result<Film> {
+        let (width, height) = (read_u32(reader)?, read_u32(reader)?);
+        let len = u64::from(width) * u64::from(height);
+        if len > u64::from(u32::MAX) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "film is too large"));
+        }
+        let mut film = Film::new(width, height);
+        for index in 0..len as usize {
+            film.sum[index] = RGBColor::new(read_f64(reader)?, read_f64(reader)?, read_f64(reader)?);
+            film.sum_brightness[index] = read_f64(reader)?;
+            film.sum_sqr[index] = read_f64(reader)?;
+            film.weights[index] = read_f64(reader)?;
+            film.weights_abs[index] = read_f64(reader)?;
+            film.weights_sqr[index] = read_f64(reader)?;
+            film.samples[index] = read_u32(reader)?;
+        }
+        let mut has_aovs = [0; 1];
+        reader.read_exact(&mut has_aovs)?;
+        film.aovs = match has_aovs[0] {
+            0 => None,
+            1 => Some(AovFilm::read(reader, width, height)?),
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "bad AOV flag")),
+        };
+        Ok(film)
+    }
+
+    ///Samples spent on each pixel, from black for none through blue, red and yellow to
+    ///white for the most any pixel got
+    pub fn to_heat_map(&self) -> DynamicImage {
+        let stops = [
+            RGBColor::new(0.0, 0.0, 0.0),
+            RGBColor::new(0.0, 0.0, 1.0),
+            RGBColor::new(1.0, 0.0, 0.0),
+            RGBColor::new(1.0, 1.0, 0.0),
+            RGBColor::new(1.0, 1.0, 1.0),
+        ];
+        let max = self.samples.iter().cloned().max().unwrap_or(0).max(1);
+        let mut img = DynamicImage::new_rgb8(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let position =
+                    f64::from(self.get_samples(x, y)) / f64::from(max) * (stops.len() - 1) as f64;
+                let index = (position.floor() as usize).min(stops.len() - 2);
+                let u = position - index as f64;
+                let color = stops[index] * (1.0 - u) + stops[index + 1] * u;
+                let to_8_bit = |c: f64| (c * 255.0).round() as u8;
+                img.put_pixel(
+                    x,
+                    y,
+                    image::Rgba::from_channels(
+                        to_8_bit(color.r),
+                        to_8_bit(color.g),
+                        to_8_bit(color.b),
+                        255,
+                    ),
+                );
+            }
+        }
+        img
+    }
+}
+
+///Pixels of an other_width x other_height film placed at (x,y) which fall inside a
+///width x height one, row by row in the placed film's coordinates
+pub(crate) fn get_overlap(
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+    other_width: u32,
+    other_height: u32,
+) -> impl Iterator<Item = (u32, u32)> {
+    let range = |at: i32, size: u32, other_size: u32| {
+        let start = (-i64::from(at)).max(0);
+        let end = (i64::from(size) - i64::from(at)).min(i64::from(other_size));
+        start as u32..end.max(start) as u32
+    };
+    let (xs, ys) = (range(x, width, other_width), range(y, height, other_height));
+    ys.flat_map(move |y| xs.clone().map(move |x| (x, y)))
+}
+
+///Gamma of 2
+pub(crate) fn gamma_correct(color: RGBColor) -> RGBColor {
+    RGBColor::new(
+        color.r.max(0.0).sqrt(),
+        color.g.max(0.0).sqrt(),
+        color.b.max(0.0).sqrt(),
+    )
+}
+
+///Average of the gamma corrected channels, close to the perceived brightness
+fn get_brightness(color: RGBColor) -> f64 {
+    let color = gamma_correct(color);
+    (color.r + color.g + color.b) / 3.0
+}
+
+pub(crate) fn read_u32(reader: &mut dyn Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+pub(crate) fn read_u64(reader: &mut dyn Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+pub(crate) fn read_f64(reader: &mut dyn Read) -> io::Result<f64> {
+    Ok(f64::from_bits(read_u64(reader)?))
+}
@@ -0,0 +1,94 @@
+use crate::graphics::{Point3D, Ray};
+
+///Axis aligned box used to bound objects for the acceleration structure
+#[derive(Debug, Copy, Clone)]
+pub struct BoundingBox {
+    min: Point3D,
+    max: Point3D,
+}
+
+impl BoundingBox {
+    ///The corners can be given in any order
+    pub fn new(a: Point3D, b: Point3D) -> Self {
+        BoundingBox {
+            min: Point3D::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z())),
+            max: Point3D::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z())),
+        }
+    }
+
+    ///Smallest box containing all the points, None if there are no points
+    pub fn from_points(points: &[Point3D]) -> Option<Self> {
+        let (first, rest) = points.split_first()?;
+        Some(
+            rest.iter()
+                .fold(BoundingBox::new(*first, *first), |bbox, p| {
+                    bbox.union(&BoundingBox::new(*p, *p))
+                }),
+        )
+    }
+
+    pub fn get_min(&self) -> &Point3D {
+        &self.min
+    }
+
+    pub fn get_max(&self) -> &Point3D {
+        &self.max
+    }
+
+    pub fn union(&self, other: &BoundingBox) -> BoundingBox {
+        BoundingBox {
+            min: Point3D::new(
+                self.min.x().min(other.min.x()),
+                self.min.y().min(other.min.y()),
+                self.min.z().min(other.min.z()),
+            ),
+            max: Point3D::new(
+                self.max.x().max(other.max.x()),
+                self.max.y().max(other.max.y()),
+                self.max.z().max(other.max.z()),
+            ),
+        }
+    }
+
+    ///Grows the box by `delta` on every side, used to give flat objects some thickness
+    pub fn pad(&self, delta: f64) -> BoundingBox {
+        BoundingBox {
+            min: Point3D::new(self.min.x() - delta, self.min.y() - delta, self.min.z() - delta),
+            max: Point3D::new(self.max.x() + delta, self.max.y() + delta, self.max.z() + delta),
+        }
+    }
+
+    ///All eight corners of the box
+    pub fn get_corners(&self) -> [Point3D; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            Point3D::new(a.x(), a.y(), a.z()),
+            Point3D::new(b.x(), a.y(), a.z()),
+            Point3D::new(a.x(), b.y(), a.z()),
+            Point3D::new(b.x(), b.y(), a.z()),
+            Point3D::new(a.x(), a.y(), b.z()),
+            Point3D::new(b.x(), a.y(), b.z()),
+            Point3D::new(a.x(), b.y(), b.z()),
+            Point3D::new(b.x(), b.y(), b.z()),
+        ]
+    }
+
+    ///Slab test, returns the range of t for which the ray is inside the box
+    pub fn hit(&self, ray: &Ray) -> Option<(f64, f64)> {
+        let (mut t_near, mut t_far) = (f64::NEG_INFINITY, f64::INFINITY);
+        for axis in 0..3 {
+            let inverse = 1.0 / ray.d.axis(axis);
+            let mut t0 = (self.min.axis(axis) - ray.o.axis(axis)) * inverse;
+            let mut t1 = (self.max.axis(axis) - ray.o.axis(axis)) * inverse;
+            if inverse < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_near = t_near.max(t0);
+            t_far = t_far.min(t1);
+            if t_near > t_far {
+                return None;
+            }
+        }
+        Some((t_near, t_far))
+    }
+}
@@ -27,6 +27,15 @@ impl Point3D {
     pub fn z(&self) -> f64 {
         self.z
     }
+
+    ///Coordinate along axis 0, 1 or 2 (x, y, z)
+    pub fn axis(&self, axis: usize) -> f64 {
+        match axis {
+            0 => self.x,
+            1 => self.y,
+            _ => self.z,
+        }
+    }
 }
 
 impl Sub for Point3D {
@@ -198,6 +207,15 @@ impl Vector3D {
     pub fn z(&self) -> f64 {
         self.z
     }
+
+    ///Component along axis 0, 1 or 2 (x, y, z)
+    pub fn axis(&self, axis: usize) -> f64 {
+        match axis {
+            0 => self.x,
+            1 => self.y,
+            _ => self.z,
+        }
+    }
 }
 
 impl Add for Vector3D {
@@ -312,16 +330,17 @@ impl RGBColor {
         RGBColor { r, g, b }
     }
 
+    ///Channels below 0 or above 1 are clamped
     pub fn r_in_8_bit(&self) -> u8 {
-        (((self.r * 255.0) as u32)%256) as u8
+        (self.r * 255.0).clamp(0.0, 255.0) as u8
     }
 
     pub fn g_in_8_bit(&self) -> u8 {
-        (((self.g * 255.0) as u32)%256) as u8
+        (self.g * 255.0).clamp(0.0, 255.0) as u8
     }
 
     pub fn b_in_8_bit(&self) -> u8 {
-        (((self.b * 255.0) as u32)%256) as u8
+        (self.b * 255.0).clamp(0.0, 255.0) as u8
     }
 }
 
@@ -370,4 +389,15 @@ impl Add for RGBColor {
             b: self.b + other.b,
         }
     }
-}
\ No newline at end of file
+}
+impl Sub for RGBColor {
+    type Output = RGBColor;
+
+    fn sub(self, other: Self) -> Self {
+        RGBColor {
+            r: self.r - other.r,
+            g: self.g - other.g,
+            b: self.b - other.b,
+        }
+    }
+}
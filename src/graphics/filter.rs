@@ -0,0 +1,118 @@
+use std::f64::consts::PI;
+
+///Falloff of the Gaussian filter
+const GAUSSIAN_ALPHA: f64 = 2.0;
+///Mitchell and Netravali's recommended B and C
+const MITCHELL_B: f64 = 1.0 / 3.0;
+const MITCHELL_C: f64 = 1.0 / 3.0;
+
+///Shape of the reconstruction filter spreading each sample over the pixels around it
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FilterKind {
+    ///Every sample counts fully in the pixels within the radius, with the default radius
+    ///only in its own one
+    Box,
+    ///Linear falloff to 0 at the radius
+    Tent,
+    ///Gaussian shifted down to reach 0 at the radius
+    Gaussian,
+    ///Cubic with small negative lobes, sharper than the Gaussian
+    MitchellNetravali,
+    ///Windowed sinc with as many lobes as the radius, the sharpest and the most prone to ringing
+    Lanczos,
+}
+
+impl FilterKind {
+    ///Radius the filter is usually used with, in pixels
+    pub fn get_default_radius(self) -> f64 {
+        match self {
+            FilterKind::Box => 0.5,
+            FilterKind::Tent => 1.0,
+            FilterKind::Gaussian => 1.5,
+            FilterKind::MitchellNetravali => 2.0,
+            FilterKind::Lanczos => 3.0,
+        }
+    }
+}
+
+///Weights with which a sample counts in the pixels around it, the image is the weighted
+///mean of the samples reaching each pixel
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Filter {
+    kind: FilterKind,
+    radius: f64,
+}
+
+impl Filter {
+    ///radius-in pixels, at least half a pixel so every sample reaches its own pixel
+    pub fn new(kind: FilterKind, radius: f64) -> Self {
+        Filter {
+            kind,
+            radius: radius.max(0.5),
+        }
+    }
+
+    pub fn get_kind(&self) -> FilterKind {
+        self.kind
+    }
+
+    pub fn get_radius(&self) -> f64 {
+        self.radius
+    }
+
+    ///Pixels on each side of its own one which a sample may reach
+    pub fn get_padding(&self) -> u32 {
+        (self.radius - 0.5).ceil() as u32
+    }
+
+    ///Weight of a sample (dx,dy) pixels away from a pixel's centre, 0 beyond the radius
+    ///The box filter includes its edge so a sample on the border of its pixel counts there
+    pub fn evaluate(&self, dx: f64, dy: f64) -> f64 {
+        self.evaluate_1d(dx) * self.evaluate_1d(dy)
+    }
+
+    fn evaluate_1d(&self, d: f64) -> f64 {
+        let (x, radius) = (d.abs(), self.radius);
+        if x > radius {
+            return 0.0;
+        }
+        match self.kind {
+            FilterKind::Box => 1.0,
+            FilterKind::Tent => 1.0 - x / radius,
+            FilterKind::Gaussian => {
+                ((-GAUSSIAN_ALPHA * x * x).exp() - (-GAUSSIAN_ALPHA * radius * radius).exp()).max(0.0)
+            }
+            FilterKind::MitchellNetravali => mitchell(2.0 * x / radius),
+            FilterKind::Lanczos => sinc(x) * sinc(x / radius),
+        }
+    }
+}
+
+///A box over the sample's own pixel, the same as averaging the samples of each pixel
+impl Default for Filter {
+    fn default() -> Self {
+        Filter::new(FilterKind::Box, FilterKind::Box.get_default_radius())
+    }
+}
+
+///Mitchell-Netravali cubic for x from 0 to 2
+fn mitchell(x: f64) -> f64 {
+    let (b, c) = (MITCHELL_B, MITCHELL_C);
+    let weight = if x < 1.0 {
+        (12.0 - 9.0 * b - 6.0 * c) * x * x * x + (-18.0 + 12.0 * b + 6.0 * c) * x * x + (6.0 - 2.0 * b)
+    } else {
+        (-b - 6.0 * c) * x * x * x
+            + (6.0 * b + 30.0 * c) * x * x
+            + (-12.0 * b - 48.0 * c) * x
+            + (8.0 * b + 24.0 * c)
+    };
+    weight / 6.0
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-8 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
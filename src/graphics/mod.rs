@@ -1,22 +1,35 @@
+mod bounding_box;
 mod data_structures;
+mod filter;
+mod sampler;
+mod transform;
 mod world;
 
+pub use self::bounding_box::*;
 pub use self::data_structures::*;
+pub use self::filter::*;
+pub use self::sampler::*;
+pub use self::transform::*;
 pub use self::world::*;
-use rand::Rng;
-use std::rc::Rc;
 
 ///Infinite Ray represented by p=o+td
+#[derive(Debug, Clone)]
 pub struct Ray {
     ///Origin of ray
     pub o: Point3D,
     ///Direction in which it points
     pub d: Vector3D,
+    ///Instant at which the ray travels, within the shutter interval of the view plane
+    pub time: f64,
 }
 
 impl Ray {
     pub fn new(o: Point3D, d: Vector3D) -> Ray {
-        Ray { o, d }
+        Ray { o, d, time: 0.0 }
+    }
+
+    pub fn with_time(o: Point3D, d: Vector3D, time: f64) -> Ray {
+        Ray { o, d, time }
     }
 
     pub fn get_point_at(&self, t: f64) -> Point3D {
@@ -27,16 +40,36 @@ impl Ray {
 pub trait GeometricObject : /*Send+Sync*/{
     fn check_hit(&self, ray: &Ray) -> Option<HitInfo>;
 
-    fn get_material(&self) -> Rc<dyn Material>;
+    fn get_material(&self) -> &dyn Material;
+
+    ///Box enclosing the object, None for unbounded objects like planes
+    fn get_bounding_box(&self) -> Option<BoundingBox> {
+        None
+    }
+
+    ///Every part of the ray inside the object sorted by t, including parts behind the origin
+    ///None for objects which don't enclose a volume
+    fn get_intervals(&self, _ray: &Ray) -> Option<Vec<Interval>> {
+        None
+    }
+
+    ///Point on the surface for u uniform in the unit square, with the normal there and
+    ///the density of the point per unit area, for integrators which aim rays at lights
+    ///None for objects whose surface can't be sampled
+    fn sample_surface(&self, _u: (f64, f64)) -> Option<(Point3D, Normal3D, f64)> {
+        None
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct HitInfo {
     ///Lowest value of ray parameter t which intersects Hittable object
     tmin: f64,
     ///The normalised normal
     normal: Normal3D,
     hitpoint: Point3D,
+    ///Surface coordinates of the hitpoint, both in [0,1] for shapes that define them
+    uv: (f64, f64),
 }
 
 impl HitInfo {
@@ -45,9 +78,15 @@ impl HitInfo {
             tmin,
             normal,
             hitpoint,
+            uv: (0.0, 0.0),
         }
     }
 
+    pub fn with_uv(mut self, u: f64, v: f64) -> Self {
+        self.uv = (u, v);
+        self
+    }
+
     pub fn get_tmin(&self) -> f64 {
         self.tmin
     }
@@ -59,18 +98,75 @@ impl HitInfo {
     pub fn get_hitpoint(&self) -> &Point3D {
         &self.hitpoint
     }
+
+    pub fn get_uv(&self) -> (f64, f64) {
+        self.uv
+    }
+}
+
+///Part of a ray inside a closed object, normals of both ends point out of the object
+#[derive(Debug, Clone)]
+pub struct Interval {
+    enter: HitInfo,
+    exit: HitInfo,
+}
+
+impl Interval {
+    pub fn new(enter: HitInfo, exit: HitInfo) -> Self {
+        Interval { enter, exit }
+    }
+
+    pub fn get_enter(&self) -> &HitInfo {
+        &self.enter
+    }
+
+    pub fn get_exit(&self) -> &HitInfo {
+        &self.exit
+    }
+
+    ///First end of the interval in front of the ray origin
+    pub fn get_first_hit(&self, t_min: f64) -> Option<&HitInfo> {
+        if self.enter.tmin > t_min {
+            Some(&self.enter)
+        } else if self.exit.tmin > t_min {
+            Some(&self.exit)
+        } else {
+            None
+        }
+    }
 }
 
 pub trait Material /*: Send*/ {
     ///ray-normalised incoming ray
+    ///sampler-source of the random numbers for scattering
     ///returns outgoing normalized ray and attenuation
-    fn process(&self, ray_in: &Ray, hitinfo: &HitInfo) -> Option<(Ray, RGBColor)>{
+    fn process(&self, ray_in: &Ray, hitinfo: &HitInfo, _sampler: &mut dyn Sampler) -> Option<(Ray, RGBColor)>{
+        None
+    }
+
+    ///Density per unit solid angle with which process scatters ray_in towards direction
+    ///None for materials which only scatter into a few discrete directions
+    fn pdf(&self, _ray_in: &Ray, _hitinfo: &HitInfo, _direction: &Vector3D) -> Option<f64> {
         None
     }
 
+    ///Light arriving from direction which the surface scatters back along ray_in, per
+    ///unit solid angle and including the cosine, so process returns it divided by pdf
+    ///None with pdf, by default the albedo times pdf as process returns the albedo
+    fn bsdf(&self, ray_in: &Ray, hitinfo: &HitInfo, direction: &Vector3D) -> Option<RGBColor> {
+        self.pdf(ray_in, hitinfo, direction)
+            .map(|pdf| self.get_albedo() * pdf)
+    }
+
     fn get_emitted(&self) ->RGBColor{
         RGBColor::new(0.0,0.0,0.0)
     }
+
+    ///Fraction of the light the surface reflects, for the albedo AOV
+    ///Black for materials which only emit
+    fn get_albedo(&self) -> RGBColor {
+        RGBColor::new(0.0, 0.0, 0.0)
+    }
 }
 
 pub struct LambertianMaterial {
@@ -84,18 +180,28 @@ impl LambertianMaterial {
 }
 
 impl Material for LambertianMaterial {
-    fn process(&self, _ray_in: &Ray, hit_info: &HitInfo) -> Option<(Ray, RGBColor)> {
+    fn process(&self, ray_in: &Ray, hit_info: &HitInfo, sampler: &mut dyn Sampler) -> Option<(Ray, RGBColor)> {
         let hit_point = hit_info.get_hitpoint();
-        let mut rng = rand::thread_rng();
-        let random_unit_vec = Vector3D::new(
-            rng.gen_range(-1.0, 1.0),
-            rng.gen_range(-1.0, 1.0),
-            rng.gen_range(-1.0, 1.0),
-        )
-        .normalize();
-        let ray_out =
-            (Vector3D::from(hit_info.get_normal().normalize()) + random_unit_vec).normalize();
-        Some((Ray::new(hit_point.clone(), ray_out), self.albedo))
+        //A uniformly distributed point on the unit sphere around the tip of the normal
+        //gives a cosine weighted direction
+        let normal = Vector3D::from(hit_info.get_normal().normalize());
+        let direction = normal + uniform_sphere(sampler.get_2d());
+        let ray_out = if direction.magnitude_sqr() > 1e-12 {
+            direction.normalize()
+        } else {
+            normal
+        };
+        Some((Ray::with_time(*hit_point, ray_out, ray_in.time), self.albedo))
+    }
+
+    ///Cosine weighted over the hemisphere of the normal
+    fn pdf(&self, _ray_in: &Ray, hit_info: &HitInfo, direction: &Vector3D) -> Option<f64> {
+        let cosine = Vector3D::from(hit_info.get_normal().normalize()) * direction.normalize();
+        Some(cosine.max(0.0) / std::f64::consts::PI)
+    }
+
+    fn get_albedo(&self) -> RGBColor {
+        self.albedo
     }
 }
 
@@ -111,24 +217,45 @@ impl MetallicMaterial {
 }
 
 impl Material for MetallicMaterial {
-    fn process(&self, ray_in: &Ray, hit_info: &HitInfo) -> Option<(Ray, RGBColor)> {
+    fn process(&self, ray_in: &Ray, hit_info: &HitInfo, sampler: &mut dyn Sampler) -> Option<(Ray, RGBColor)> {
         let hit_point = hit_info.get_hitpoint();
-        let mut rng = rand::thread_rng();
-        let random_unit_vec = Vector3D::new(
-            rng.gen_range(-1.0, 1.0),
-            rng.gen_range(-1.0, 1.0),
-            rng.gen_range(-1.0, 1.0),
-        )
-        .normalize();
+        let random_unit_vec = uniform_sphere(sampler.get_2d());
 
         //Reflect
         let new_dir =reflect(&ray_in,hit_info.get_normal());
         let scattered = new_dir + random_unit_vec * self.fuzziness;
         Some( (
-            Ray::new(hit_point.clone(), scattered.normalize()),
+            Ray::with_time(*hit_point, scattered.normalize(), ray_in.time),
             self.albedo,
         ))
     }
+
+    ///Directions through a sphere of radius fuzziness around the tip of the mirrored
+    ///direction, weighted by how much of the sphere's surface they see
+    fn pdf(&self, ray_in: &Ray, hit_info: &HitInfo, direction: &Vector3D) -> Option<f64> {
+        if self.fuzziness == 0.0 {
+            return None;
+        }
+        let reflected = reflect(ray_in, hit_info.get_normal());
+        let b = direction.normalize() * reflected;
+        let discriminant = b * b - (1.0 - self.fuzziness * self.fuzziness);
+        if discriminant <= 0.0 {
+            return Some(0.0);
+        }
+        //t of the points where the direction enters and leaves the sphere, the density of
+        //each is t^2/(4pi*f^2*cos) where cos=sqrt(discriminant)/f
+        let root = discriminant.sqrt();
+        let t_sqr_sum: f64 = [b - root, b + root]
+            .iter()
+            .filter(|t| **t > 0.0)
+            .map(|t| t * t)
+            .sum();
+        Some(t_sqr_sum / (4.0 * std::f64::consts::PI * self.fuzziness * root))
+    }
+
+    fn get_albedo(&self) -> RGBColor {
+        self.albedo
+    }
 }
 
 fn reflect(ray_in:&Ray,normal:&Normal3D)->Vector3D{
@@ -162,7 +289,7 @@ pub fn refract2(ray_in:&Ray,normal:&Normal3D,refractive_index:f64)->Option<Vecto
     let n=Vector3D::from(normal);
     let cosi=(i*n)*-1.0;
     let sinsqr_t=refractive_index.powi(2)*(1.0-cosi.powi(2));
-    let discr=1.0-sinsqr_t.powi(2);
+    let discr=1.0-sinsqr_t;
     if discr<0.0{
         None
     }else{
@@ -187,7 +314,7 @@ impl Dielectric {
 }
 
 impl Material for Dielectric{
-    fn process(&self, ray_in: &Ray, hitinfo: &HitInfo) -> Option<(Ray, RGBColor)> {
+    fn process(&self, ray_in: &Ray, hitinfo: &HitInfo, sampler: &mut dyn Sampler) -> Option<(Ray, RGBColor)> {
         let ray_in_d=ray_in.d.normalize();
         let normal=hitinfo.get_normal();
         let (surface_normal,refractive_index,cosine)=
@@ -204,8 +331,7 @@ impl Material for Dielectric{
             match refract2(&ray_in,&surface_normal,refractive_index){
                 Some(result)=>{
                     let reflectivity=schlick_reflectivity(cosine,self.refractive_index);
-                    let mut rng = rand::thread_rng();
-                    if rng.gen_range(0.0,1.0)<reflectivity{
+                    if sampler.get_1d()<reflectivity{
                         reflect(&ray_in,&surface_normal)
                     }else {
                         result
@@ -213,7 +339,12 @@ impl Material for Dielectric{
                 },
                 None=>{reflect(&ray_in,&surface_normal)}
             };
-        Some((Ray::new(hitinfo.get_hitpoint().clone(),ray_out_d),attenuation))
+        Some((Ray::with_time(*hitinfo.get_hitpoint(),ray_out_d,ray_in.time),attenuation))
+    }
+
+    ///Clear glass loses no light
+    fn get_albedo(&self) -> RGBColor {
+        RGBColor::new(1.0, 1.0, 1.0)
     }
 }
 
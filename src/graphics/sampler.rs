@@ -0,0 +1,443 @@
+use super::Vector3D;
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+
+///Source of the random numbers of a path, one dimension after another
+///The camera takes the first two dimensions for the position in the pixel and the
+///next one for the time, materials take theirs as the path bounces around
+pub trait Sampler {
+    ///Starts a path, every sample of a pixel is its own path
+    fn start_pixel_sample(&mut self, pixel: (u32, u32), sample: u32);
+
+    ///Next dimension, in [0,1)
+    fn get_1d(&mut self) -> f64;
+
+    ///Next two dimensions, in [0,1)
+    fn get_2d(&mut self) -> (f64, f64);
+}
+
+///Which sampler a render uses
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SamplerKind {
+    ///Uncorrelated random numbers
+    Independent,
+    ///Latin hypercube, every dimension of the first samples_per_pixel samples
+    ///falls in a different stratum
+    Stratified,
+    ///Halton sequence with a random offset per pixel
+    Halton,
+    ///Sobol sequence with hash based Owen scrambling
+    Sobol,
+    ///Sobol sequence shifted by a blue noise mask, so the error of neighbouring
+    ///pixels is spread as fine noise at few samples per pixel
+    BlueNoise,
+}
+
+impl SamplerKind {
+    ///samples_per_pixel-samples the sampler distributes well, later samples are still valid
+    ///seed-picks the random numbers, the same seed gives the same numbers for each
+    ///sample of each pixel whatever order they're taken in
+    pub fn create(self, samples_per_pixel: u32, seed: u64) -> Box<dyn Sampler> {
+        match self {
+            SamplerKind::Independent => Box::new(IndependentSampler::new(seed)),
+            SamplerKind::Stratified => Box::new(StratifiedSampler::new(samples_per_pixel, seed)),
+            SamplerKind::Halton => Box::new(HaltonSampler::new(seed)),
+            SamplerKind::Sobol => Box::new(SobolSampler::new(seed)),
+            SamplerKind::BlueNoise => Box::new(BlueNoiseSampler::new(seed)),
+        }
+    }
+}
+
+pub struct IndependentSampler {
+    seed: u64,
+    ///Seeded anew for every sample of every pixel
+    rng: SmallRng,
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64) -> Self {
+        IndependentSampler {
+            seed,
+            rng: SmallRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn start_pixel_sample(&mut self, pixel: (u32, u32), sample: u32) {
+        self.rng = SmallRng::seed_from_u64(hash(&[
+            self.seed,
+            u64::from(pixel.0),
+            u64::from(pixel.1),
+            u64::from(sample),
+        ]));
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.rng.gen::<f64>()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.get_1d(), self.get_1d())
+    }
+}
+
+///State shared by the samplers which derive everything from the pixel, sample and dimension
+#[derive(Debug, Copy, Clone)]
+struct PathState {
+    seed: u64,
+    ///Hash of the seed and the pixel
+    pixel_hash: u64,
+    sample: u32,
+    dimension: u32,
+}
+
+impl PathState {
+    fn new(seed: u64) -> Self {
+        PathState {
+            seed,
+            pixel_hash: seed,
+            sample: 0,
+            dimension: 0,
+        }
+    }
+
+    fn start(&mut self, pixel: (u32, u32), sample: u32) {
+        self.pixel_hash = hash(&[self.seed, u64::from(pixel.0), u64::from(pixel.1)]);
+        self.sample = sample;
+        self.dimension = 0;
+    }
+
+    ///Hash for the current dimension, the same for every sample of the pixel
+    fn next_dimension(&mut self) -> u64 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        hash(&[self.pixel_hash, u64::from(dimension)])
+    }
+
+    ///Random number for the current sample, for the dimensions a sequence doesn't cover
+    fn get_random(&self, dimension_hash: u64) -> f64 {
+        to_unit(hash(&[dimension_hash, u64::from(self.sample)]) as u32)
+    }
+}
+
+pub struct StratifiedSampler {
+    samples_per_pixel: u32,
+    state: PathState,
+}
+
+impl StratifiedSampler {
+    pub fn new(samples_per_pixel: u32, seed: u64) -> Self {
+        StratifiedSampler {
+            samples_per_pixel: samples_per_pixel.max(1),
+            state: PathState::new(seed),
+        }
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel_sample(&mut self, pixel: (u32, u32), sample: u32) {
+        self.state.start(pixel, sample);
+    }
+
+    ///The samples of a pixel visit the strata of each dimension in a different random order
+    fn get_1d(&mut self) -> f64 {
+        let dimension_hash = self.state.next_dimension();
+        let jitter = self.state.get_random(dimension_hash);
+        let n = self.samples_per_pixel;
+        if self.state.sample >= n {
+            return jitter;
+        }
+        let stratum = permutation_element(self.state.sample, n, dimension_hash as u32);
+        (f64::from(stratum) + jitter) / f64::from(n)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.get_1d(), self.get_1d())
+    }
+}
+
+pub struct HaltonSampler {
+    primes: Vec<u32>,
+    state: PathState,
+}
+
+impl HaltonSampler {
+    ///Dimensions past the number of primes get random numbers, a path of 20 bounces needs
+    ///about 60
+    const DIMENSIONS: usize = 128;
+
+    pub fn new(seed: u64) -> Self {
+        let mut primes: Vec<u32> = Vec::with_capacity(HaltonSampler::DIMENSIONS);
+        let mut candidate = 2;
+        while primes.len() < HaltonSampler::DIMENSIONS {
+            if primes.iter().all(|p| candidate % p != 0) {
+                primes.push(candidate);
+            }
+            candidate += 1;
+        }
+        HaltonSampler {
+            primes,
+            state: PathState::new(seed),
+        }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel_sample(&mut self, pixel: (u32, u32), sample: u32) {
+        self.state.start(pixel, sample);
+    }
+
+    ///Each pixel's sequence is shifted by a random offset, wrapping around at 1
+    fn get_1d(&mut self) -> f64 {
+        let dimension = self.state.dimension as usize;
+        let dimension_hash = self.state.next_dimension();
+        match self.primes.get(dimension) {
+            Some(base) => {
+                let value = radical_inverse(self.state.sample, *base)
+                    + to_unit(hash(&[dimension_hash, 0]) as u32);
+                value.fract()
+            }
+            None => self.state.get_random(dimension_hash),
+        }
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.get_1d(), self.get_1d())
+    }
+}
+
+///After Burley, "Practical Hash-based Owen Scrambling", 2020
+///Only the first two Sobol dimensions are used, every request shuffles the sample
+///index differently so the dimensions aren't correlated
+pub struct SobolSampler {
+    state: PathState,
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64) -> Self {
+        SobolSampler {
+            state: PathState::new(seed),
+        }
+    }
+
+    fn get_index(&self, dimension_hash: u64) -> u32 {
+        nested_uniform_scramble(self.state.sample, dimension_hash as u32)
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel_sample(&mut self, pixel: (u32, u32), sample: u32) {
+        self.state.start(pixel, sample);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let dimension_hash = self.state.next_dimension();
+        let index = self.get_index(dimension_hash);
+        let x = nested_uniform_scramble(index.reverse_bits(), (dimension_hash >> 32) as u32);
+        to_unit(x)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let dimension_hash = self.state.next_dimension();
+        let index = self.get_index(dimension_hash);
+        let seeds = hash(&[dimension_hash, 1]);
+        let x = nested_uniform_scramble(index.reverse_bits(), seeds as u32);
+        let y = nested_uniform_scramble(sobol_second_dimension(index), (seeds >> 32) as u32);
+        (to_unit(x), to_unit(y))
+    }
+}
+
+///After Georgiev and Fajardo, "Blue-noise Dithered Sampling", 2016
+///Every pixel gets the same scrambled Sobol points, shifted by values of a tileable blue
+///noise mask which is moved around for each dimension
+pub struct BlueNoiseSampler {
+    ///Ranks of the mask's pixels, MASK_SIZE by MASK_SIZE
+    mask: Vec<u32>,
+    pixel: (u32, u32),
+    state: PathState,
+}
+
+impl BlueNoiseSampler {
+    const MASK_SIZE: u32 = 32;
+
+    pub fn new(seed: u64) -> Self {
+        BlueNoiseSampler {
+            mask: get_blue_noise_mask(BlueNoiseSampler::MASK_SIZE, seed),
+            pixel: (0, 0),
+            state: PathState::new(seed),
+        }
+    }
+
+    ///Value of the mask at the pixel, with the mask moved by an offset the salt picks
+    fn get_shift(&self, dimension_hash: u64, salt: u64) -> f64 {
+        let size = BlueNoiseSampler::MASK_SIZE;
+        let offset = hash(&[dimension_hash, salt]);
+        let x = (self.pixel.0 % size + offset as u32 % size) % size;
+        let y = (self.pixel.1 % size + (offset >> 32) as u32 % size) % size;
+        (f64::from(self.mask[(x + y * size) as usize]) + 0.5) / f64::from(size * size)
+    }
+}
+
+impl Sampler for BlueNoiseSampler {
+    fn start_pixel_sample(&mut self, pixel: (u32, u32), sample: u32) {
+        self.pixel = pixel;
+        //The dimensions are hashed the same way for every pixel, only the mask tells them apart
+        self.state.start((0, 0), sample);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let dimension_hash = self.state.next_dimension();
+        let index = nested_uniform_scramble(self.state.sample, dimension_hash as u32);
+        let x = nested_uniform_scramble(index.reverse_bits(), (dimension_hash >> 32) as u32);
+        (to_unit(x) + self.get_shift(dimension_hash, 0)).fract()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let dimension_hash = self.state.next_dimension();
+        let index = nested_uniform_scramble(self.state.sample, dimension_hash as u32);
+        let seeds = hash(&[dimension_hash, 1]);
+        let x = nested_uniform_scramble(index.reverse_bits(), seeds as u32);
+        let y = nested_uniform_scramble(sobol_second_dimension(index), (seeds >> 32) as u32);
+        (
+            (to_unit(x) + self.get_shift(dimension_hash, 2)).fract(),
+            (to_unit(y) + self.get_shift(dimension_hash, 3)).fract(),
+        )
+    }
+}
+
+///Ranks of the pixels of a tileable square mask, each rank going to the pixel farthest from
+///those already ranked as measured by a Gaussian energy, which leaves blue noise
+///After the void filling of Ulichney, "The void-and-cluster method for dither array
+///generation", 1993
+pub fn get_blue_noise_mask(size: u32, seed: u64) -> Vec<u32> {
+    let n = (size * size) as usize;
+    let sigma = 1.5;
+    //Energy a ranked pixel adds to the pixels at each offset from it, wrapping around
+    let kernel: Vec<f64> = (0..size * size)
+        .map(|i| {
+            let wrap = |d: u32| f64::from(d.min(size - d));
+            let (dx, dy) = (wrap(i % size), wrap(i / size));
+            (-(dx * dx + dy * dy) / (2.0 * sigma * sigma)).exp()
+        })
+        .collect();
+    let mut energy = vec![0.0; n];
+    let mut ranks: Vec<Option<u32>> = vec![None; n];
+    let mut next = (hash(&[seed]) % n as u64) as usize;
+    for rank in 0..size * size {
+        ranks[next] = Some(rank);
+        let (x, y) = (next as u32 % size, next as u32 / size);
+        for (i, e) in energy.iter_mut().enumerate() {
+            let dx = (i as u32 % size + size - x) % size;
+            let dy = (i as u32 / size + size - y) % size;
+            *e += kernel[(dx + dy * size) as usize];
+        }
+        next = (0..n)
+            .filter(|&i| ranks[i].is_none())
+            .min_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap_or(0);
+    }
+    ranks.into_iter().map(|rank| rank.unwrap_or(0)).collect()
+}
+
+///Direction on the unit sphere from two numbers in [0,1), all directions equally likely
+pub fn uniform_sphere(u: (f64, f64)) -> Vector3D {
+    let z = 1.0 - 2.0 * u.0;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * std::f64::consts::PI * u.1;
+    Vector3D::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+///[0,1) from all 32 bits
+fn to_unit(x: u32) -> f64 {
+    f64::from(x) / 4_294_967_296.0
+}
+
+///Finalizer of SplitMix64
+fn mix_bits(mut v: u64) -> u64 {
+    v ^= v >> 31;
+    v = v.wrapping_mul(0x7fb5_d329_728e_a185);
+    v ^= v >> 27;
+    v = v.wrapping_mul(0x81da_def4_bc2d_d44d);
+    v ^= v >> 33;
+    v
+}
+
+fn hash(values: &[u64]) -> u64 {
+    values.iter().fold(0x9e37_79b9_7f4a_7c15, |h, v| {
+        mix_bits(h ^ v.wrapping_add(0x9e37_79b9_7f4a_7c15))
+    })
+}
+
+///Element i of a random permutation of 0..l picked by p, after Kensler,
+///"Correlated Multi-Jittered Sampling", 2013
+fn permutation_element(mut i: u32, l: u32, p: u32) -> u32 {
+    let mut w = l - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < l {
+            return (i.wrapping_add(p)) % l;
+        }
+    }
+}
+
+///Digits of i in the base mirrored around the decimal point
+fn radical_inverse(mut i: u32, base: u32) -> f64 {
+    let inverse_base = 1.0 / f64::from(base);
+    let (mut value, mut factor) = (0.0, inverse_base);
+    while i > 0 {
+        value += f64::from(i % base) * factor;
+        i /= base;
+        factor *= inverse_base;
+    }
+    value.min(1.0 - f64::EPSILON)
+}
+
+///Second dimension of the Sobol sequence, the first is just the reversed bits of i
+fn sobol_second_dimension(mut i: u32) -> u32 {
+    let (mut result, mut v) = (0, 1 << 31);
+    while i != 0 {
+        if i & 1 != 0 {
+            result ^= v;
+        }
+        i >>= 1;
+        v ^= v >> 1;
+    }
+    result
+}
+
+///Owen scrambling of the bits of x, the higher bits decide how the lower ones are flipped
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x ^= x.wrapping_mul(0x3d20_adea);
+    x = x.wrapping_add(seed);
+    x = x.wrapping_mul((seed >> 16) | 1);
+    x ^= x.wrapping_mul(0x0552_6c56);
+    x ^= x.wrapping_mul(0x53a2_2864);
+    x
+}
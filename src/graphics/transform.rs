@@ -0,0 +1,617 @@
+use crate::graphics::{BoundingBox, Normal3D, Point3D, Ray, Vector3D};
+use std::ops::Mul;
+
+type Matrix4 = [[f64; 4]; 4];
+
+const IDENTITY: Matrix4 = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+///Affine transformation stored along with its inverse
+///Angles are in radians
+#[derive(Debug, Copy, Clone)]
+pub struct Transform {
+    m: Matrix4,
+    inverse: Matrix4,
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+        Transform {
+            m: IDENTITY,
+            inverse: IDENTITY,
+        }
+    }
+
+    ///None if the matrix is singular
+    pub fn from_matrix(m: [[f64; 4]; 4]) -> Option<Self> {
+        Some(Transform {
+            m,
+            inverse: invert(&m)?,
+        })
+    }
+
+    pub fn translate(offset: Vector3D) -> Self {
+        let (x, y, z) = (offset.x(), offset.y(), offset.z());
+        Transform {
+            m: [
+                [1.0, 0.0, 0.0, x],
+                [0.0, 1.0, 0.0, y],
+                [0.0, 0.0, 1.0, z],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+            inverse: [
+                [1.0, 0.0, 0.0, -x],
+                [0.0, 1.0, 0.0, -y],
+                [0.0, 0.0, 1.0, -z],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    ///Scale factors must be non zero
+    pub fn scale(x: f64, y: f64, z: f64) -> Self {
+        Transform {
+            m: [
+                [x, 0.0, 0.0, 0.0],
+                [0.0, y, 0.0, 0.0],
+                [0.0, 0.0, z, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+            inverse: [
+                [1.0 / x, 0.0, 0.0, 0.0],
+                [0.0, 1.0 / y, 0.0, 0.0],
+                [0.0, 0.0, 1.0 / z, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    pub fn rotate_x(angle: f64) -> Self {
+        Transform::rotate(Vector3D::new(1.0, 0.0, 0.0), angle)
+    }
+
+    pub fn rotate_y(angle: f64) -> Self {
+        Transform::rotate(Vector3D::new(0.0, 1.0, 0.0), angle)
+    }
+
+    pub fn rotate_z(angle: f64) -> Self {
+        Transform::rotate(Vector3D::new(0.0, 0.0, 1.0), angle)
+    }
+
+    ///Counter clockwise rotation about an axis through the origin, looking against the axis
+    pub fn rotate(axis: Vector3D, angle: f64) -> Self {
+        let a = axis.normalize();
+        let (x, y, z) = (a.x(), a.y(), a.z());
+        let (sin, cos) = angle.sin_cos();
+        let m = [
+            [
+                cos + x * x * (1.0 - cos),
+                x * y * (1.0 - cos) - z * sin,
+                x * z * (1.0 - cos) + y * sin,
+                0.0,
+            ],
+            [
+                y * x * (1.0 - cos) + z * sin,
+                cos + y * y * (1.0 - cos),
+                y * z * (1.0 - cos) - x * sin,
+                0.0,
+            ],
+            [
+                z * x * (1.0 - cos) - y * sin,
+                z * y * (1.0 - cos) + x * sin,
+                cos + z * z * (1.0 - cos),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        //Rotations are orthogonal so the inverse is the transpose
+        Transform {
+            m,
+            inverse: transpose(&m),
+        }
+    }
+
+    ///Places an object at eye with its local z axis pointing at target and
+    ///its local y axis as close to up as possible
+    pub fn look_at(eye: Point3D, target: Point3D, up: Vector3D) -> Self {
+        let z = (target - eye).normalize();
+        let x = (up ^ z).normalize();
+        let y = z ^ x;
+        let m = [
+            [x.x(), y.x(), z.x(), eye.x()],
+            [x.y(), y.y(), z.y(), eye.y()],
+            [x.z(), y.z(), z.z(), eye.z()],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        //Inverse of a rigid transform, transposed rotation followed by the negated translation
+        let e = eye - Point3D::origin();
+        let inverse = [
+            [x.x(), x.y(), x.z(), -(x * e)],
+            [y.x(), y.y(), y.z(), -(y * e)],
+            [z.x(), z.y(), z.z(), -(z * e)],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        Transform { m, inverse }
+    }
+
+    pub fn inverse(&self) -> Transform {
+        Transform {
+            m: self.inverse,
+            inverse: self.m,
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.m == IDENTITY
+    }
+
+    pub fn get_matrix(&self) -> &[[f64; 4]; 4] {
+        &self.m
+    }
+
+    ///Determinant of the linear part, the factor volumes grow by
+    pub fn get_determinant(&self) -> f64 {
+        let m = &self.m;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    ///Applies self and then other
+    pub fn then(&self, other: &Transform) -> Transform {
+        *other * *self
+    }
+
+    pub fn transform_point(&self, p: &Point3D) -> Point3D {
+        let m = &self.m;
+        let (x, y, z) = (p.x(), p.y(), p.z());
+        Point3D::new(
+            m[0][0] * x + m[0][1] * y + m[0][2] * z + m[0][3],
+            m[1][0] * x + m[1][1] * y + m[1][2] * z + m[1][3],
+            m[2][0] * x + m[2][1] * y + m[2][2] * z + m[2][3],
+        )
+    }
+
+    pub fn transform_vector(&self, v: &Vector3D) -> Vector3D {
+        let m = &self.m;
+        let (x, y, z) = (v.x(), v.y(), v.z());
+        Vector3D::new(
+            m[0][0] * x + m[0][1] * y + m[0][2] * z,
+            m[1][0] * x + m[1][1] * y + m[1][2] * z,
+            m[2][0] * x + m[2][1] * y + m[2][2] * z,
+        )
+    }
+
+    ///Normals transform by the inverse transpose to stay perpendicular to the surface
+    ///The result is not normalised
+    pub fn transform_normal(&self, n: &Normal3D) -> Normal3D {
+        let inv = &self.inverse;
+        let (x, y, z) = (n.x(), n.y(), n.z());
+        Normal3D::new(
+            inv[0][0] * x + inv[1][0] * y + inv[2][0] * z,
+            inv[0][1] * x + inv[1][1] * y + inv[2][1] * z,
+            inv[0][2] * x + inv[1][2] * y + inv[2][2] * z,
+        )
+    }
+
+    ///The direction is not normalised so t is the same along both rays
+    pub fn transform_ray(&self, ray: &Ray) -> Ray {
+        Ray::with_time(
+            self.transform_point(&ray.o),
+            self.transform_vector(&ray.d),
+            ray.time,
+        )
+    }
+
+    ///Same as inverse().transform_ray(ray) without building the inverse
+    pub fn inverse_transform_ray(&self, ray: &Ray) -> Ray {
+        let inv = &self.inverse;
+        let ((x, y, z), d) = ((ray.o.x(), ray.o.y(), ray.o.z()), &ray.d);
+        Ray::with_time(
+            Point3D::new(
+                inv[0][0] * x + inv[0][1] * y + inv[0][2] * z + inv[0][3],
+                inv[1][0] * x + inv[1][1] * y + inv[1][2] * z + inv[1][3],
+                inv[2][0] * x + inv[2][1] * y + inv[2][2] * z + inv[2][3],
+            ),
+            Vector3D::new(
+                inv[0][0] * d.x() + inv[0][1] * d.y() + inv[0][2] * d.z(),
+                inv[1][0] * d.x() + inv[1][1] * d.y() + inv[1][2] * d.z(),
+                inv[2][0] * d.x() + inv[2][1] * d.y() + inv[2][2] * d.z(),
+            ),
+            ray.time,
+        )
+    }
+
+    pub fn transform_bounding_box(&self, bbox: &BoundingBox) -> BoundingBox {
+        let corners: Vec<Point3D> = bbox
+            .get_corners()
+            .iter()
+            .map(|corner| self.transform_point(corner))
+            .collect();
+        BoundingBox::from_points(&corners).unwrap()
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::identity()
+    }
+}
+
+///Composition, (a*b) applies b first and then a
+impl Mul for Transform {
+    type Output = Transform;
+
+    fn mul(self, other: Transform) -> Transform {
+        Transform {
+            m: multiply(&self.m, &other.m),
+            inverse: multiply(&other.inverse, &self.inverse),
+        }
+    }
+}
+
+///Transform moving from start to end over a time interval, used for motion blur
+///Translation, rotation and scale are interpolated separately so that a rotating object
+///keeps its shape, reflections aren't supported
+#[derive(Debug, Copy, Clone)]
+pub struct AnimatedTransform {
+    start: Transform,
+    end: Transform,
+    start_time: f64,
+    end_time: f64,
+    ///Decompositions of start and end, None if the transform doesn't change
+    decomposed: Option<(Decomposed, Decomposed)>,
+}
+
+impl AnimatedTransform {
+    pub fn new(start: Transform, start_time: f64, end: Transform, end_time: f64) -> Self {
+        let decomposed = if start.m == end.m || start_time == end_time {
+            None
+        } else {
+            Some((Decomposed::new(&start.m), Decomposed::new(&end.m)))
+        };
+        AnimatedTransform {
+            start,
+            end,
+            start_time,
+            end_time,
+            decomposed,
+        }
+    }
+
+    ///Transform which doesn't change over time
+    pub fn fixed(transform: Transform) -> Self {
+        AnimatedTransform::new(transform, 0.0, transform, 0.0)
+    }
+
+    ///Times at which the motion starts and ends
+    pub fn get_interval(&self) -> (f64, f64) {
+        (self.start_time, self.end_time)
+    }
+
+    ///The transform at all times, None if it changes
+    pub fn get_fixed(&self) -> Option<&Transform> {
+        match self.decomposed {
+            None => Some(&self.start),
+            Some(_) => None,
+        }
+    }
+
+    pub fn is_animated(&self) -> bool {
+        self.decomposed.is_some()
+    }
+
+    ///Times outside the interval get the transform at the nearest end
+    pub fn at(&self, time: f64) -> Transform {
+        match &self.decomposed {
+            None => self.start,
+            Some(_) if time <= self.start_time => self.start,
+            Some(_) if time >= self.end_time => self.end,
+            Some((start, end)) => {
+                let u = (time - self.start_time) / (self.end_time - self.start_time);
+                let m = start.interpolate(end, u).to_matrix();
+                Transform::from_matrix(m).unwrap_or(self.start)
+            }
+        }
+    }
+
+    ///Box enclosing the transformed box at every instant of the interval
+    pub fn transform_bounding_box(&self, bbox: &BoundingBox) -> BoundingBox {
+        if !self.is_animated() {
+            return self.start.transform_bounding_box(bbox);
+        }
+        //Rotation can take corners outside the boxes at the ends, so sample in between
+        const STEPS: u32 = 32;
+        (1..=STEPS).fold(self.start.transform_bounding_box(bbox), |union, step| {
+            let time =
+                self.start_time + (self.end_time - self.start_time) * step as f64 / STEPS as f64;
+            union.union(&self.at(time).transform_bounding_box(bbox))
+        })
+    }
+}
+
+///Affine matrix split into translation*rotation*scale
+#[derive(Debug, Copy, Clone)]
+struct Decomposed {
+    translation: Vector3D,
+    rotation: Quaternion,
+    ///Remaining scale and shear
+    scale: [[f64; 3]; 3],
+}
+
+impl Decomposed {
+    ///Polar decomposition of the linear part, see Shoemake and Duff, "Matrix Animation and
+    ///Polar Decomposition"
+    fn new(m: &Matrix4) -> Self {
+        let translation = Vector3D::new(m[0][3], m[1][3], m[2][3]);
+        let mut linear = [[0.0; 3]; 3];
+        for (i, row) in linear.iter_mut().enumerate() {
+            row.copy_from_slice(&m[i][..3]);
+        }
+
+        //Averaging a matrix with its inverse transpose converges to the rotation
+        let mut rotation = linear;
+        for _ in 0..100 {
+            let inverse_transpose = match invert3(&rotation) {
+                Some(inverse) => transpose3(&inverse),
+                None => break,
+            };
+            let mut next = [[0.0; 3]; 3];
+            let mut change: f64 = 0.0;
+            for i in 0..3 {
+                for j in 0..3 {
+                    next[i][j] = 0.5 * (rotation[i][j] + inverse_transpose[i][j]);
+                    change = change.max((next[i][j] - rotation[i][j]).abs());
+                }
+            }
+            rotation = next;
+            if change < 1e-10 {
+                break;
+            }
+        }
+
+        Decomposed {
+            translation,
+            rotation: Quaternion::from_matrix(&rotation),
+            scale: multiply3(&transpose3(&rotation), &linear),
+        }
+    }
+
+    fn interpolate(&self, other: &Decomposed, u: f64) -> Decomposed {
+        let mut scale = [[0.0; 3]; 3];
+        for (i, row) in scale.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.scale[i][j] * (1.0 - u) + other.scale[i][j] * u;
+            }
+        }
+        Decomposed {
+            translation: self.translation * (1.0 - u) + other.translation * u,
+            rotation: self.rotation.slerp(&other.rotation, u),
+            scale,
+        }
+    }
+
+    fn to_matrix(self) -> Matrix4 {
+        let linear = multiply3(&self.rotation.to_matrix(), &self.scale);
+        let t = self.translation;
+        let mut m = IDENTITY;
+        for i in 0..3 {
+            m[i][..3].copy_from_slice(&linear[i]);
+        }
+        m[0][3] = t.x();
+        m[1][3] = t.y();
+        m[2][3] = t.z();
+        m
+    }
+}
+
+///Unit quaternion representing a rotation
+#[derive(Debug, Copy, Clone)]
+struct Quaternion {
+    w: f64,
+    x: f64,
+    y: f64,
+    z: f64,
+}
+
+impl Quaternion {
+    fn from_matrix(m: &[[f64; 3]; 3]) -> Self {
+        let trace = m[0][0] + m[1][1] + m[2][2];
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Quaternion {
+                w: 0.25 * s,
+                x: (m[2][1] - m[1][2]) / s,
+                y: (m[0][2] - m[2][0]) / s,
+                z: (m[1][0] - m[0][1]) / s,
+            }
+        } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+            let s = (1.0 + m[0][0] - m[1][1] - m[2][2]).sqrt() * 2.0;
+            Quaternion {
+                w: (m[2][1] - m[1][2]) / s,
+                x: 0.25 * s,
+                y: (m[0][1] + m[1][0]) / s,
+                z: (m[0][2] + m[2][0]) / s,
+            }
+        } else if m[1][1] > m[2][2] {
+            let s = (1.0 + m[1][1] - m[0][0] - m[2][2]).sqrt() * 2.0;
+            Quaternion {
+                w: (m[0][2] - m[2][0]) / s,
+                x: (m[0][1] + m[1][0]) / s,
+                y: 0.25 * s,
+                z: (m[1][2] + m[2][1]) / s,
+            }
+        } else {
+            let s = (1.0 + m[2][2] - m[0][0] - m[1][1]).sqrt() * 2.0;
+            Quaternion {
+                w: (m[1][0] - m[0][1]) / s,
+                x: (m[0][2] + m[2][0]) / s,
+                y: (m[1][2] + m[2][1]) / s,
+                z: 0.25 * s,
+            }
+        };
+        q.normalize()
+    }
+
+    fn to_matrix(self) -> [[f64; 3]; 3] {
+        let Quaternion { w, x, y, z } = self;
+        [
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+            ],
+        ]
+    }
+
+    fn dot(&self, other: &Quaternion) -> f64 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    fn scaled(&self, s: f64) -> Quaternion {
+        Quaternion {
+            w: self.w * s,
+            x: self.x * s,
+            y: self.y * s,
+            z: self.z * s,
+        }
+    }
+
+    fn add(&self, other: &Quaternion) -> Quaternion {
+        Quaternion {
+            w: self.w + other.w,
+            x: self.x + other.x,
+            y: self.y + other.y,
+            z: self.z + other.z,
+        }
+    }
+
+    fn normalize(&self) -> Quaternion {
+        self.scaled(1.0 / self.dot(self).sqrt())
+    }
+
+    ///Rotation at constant angular speed along the shorter arc
+    fn slerp(&self, other: &Quaternion, u: f64) -> Quaternion {
+        let mut cos = self.dot(other);
+        let mut other = *other;
+        if cos < 0.0 {
+            //q and -q are the same rotation, pick the one nearer to self
+            other = other.scaled(-1.0);
+            cos = -cos;
+        }
+        if cos > 0.9995 {
+            //Nearly parallel, linear interpolation is accurate and avoids dividing by sin(0)
+            return self.scaled(1.0 - u).add(&other.scaled(u)).normalize();
+        }
+        let theta = cos.acos();
+        let sin = theta.sin();
+        self.scaled(((1.0 - u) * theta).sin() / sin)
+            .add(&other.scaled((u * theta).sin() / sin))
+    }
+}
+
+fn multiply3(a: &[[f64; 3]; 3], b: &[[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    let mut result = [[0.0; 3]; 3];
+    for (i, row) in result.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    result
+}
+
+fn transpose3(m: &[[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    let mut result = [[0.0; 3]; 3];
+    for (i, row) in result.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = m[j][i];
+        }
+    }
+    result
+}
+
+///Inverse from the adjugate
+fn invert3(m: &[[f64; 3]; 3]) -> Option<[[f64; 3]; 3]> {
+    let cofactor = |i: usize, j: usize| {
+        let (r0, r1) = ((i + 1) % 3, (i + 2) % 3);
+        let (c0, c1) = ((j + 1) % 3, (j + 2) % 3);
+        m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]
+    };
+    let determinant = m[0][0] * cofactor(0, 0) + m[0][1] * cofactor(0, 1) + m[0][2] * cofactor(0, 2);
+    if determinant.abs() < 1e-12 {
+        return None;
+    }
+    let mut inverse = [[0.0; 3]; 3];
+    for (i, row) in inverse.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = cofactor(j, i) / determinant;
+        }
+    }
+    Some(inverse)
+}
+
+fn multiply(a: &Matrix4, b: &Matrix4) -> Matrix4 {
+    let mut result = [[0.0; 4]; 4];
+    for (i, row) in result.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..4).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    result
+}
+
+fn transpose(m: &Matrix4) -> Matrix4 {
+    let mut result = [[0.0; 4]; 4];
+    for (i, row) in result.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = m[j][i];
+        }
+    }
+    result
+}
+
+///Gauss-Jordan elimination with partial pivoting
+fn invert(m: &Matrix4) -> Option<Matrix4> {
+    let mut a = *m;
+    let mut inverse = IDENTITY;
+    for column in 0..4 {
+        let pivot = (column..4)
+            .max_by(|&i, &j| a[i][column].abs().partial_cmp(&a[j][column].abs()).unwrap())
+            .unwrap();
+        if a[pivot][column].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(column, pivot);
+        inverse.swap(column, pivot);
+
+        let scale = 1.0 / a[column][column];
+        for j in 0..4 {
+            a[column][j] *= scale;
+            inverse[column][j] *= scale;
+        }
+        for row in 0..4 {
+            if row != column {
+                let factor = a[row][column];
+                for j in 0..4 {
+                    a[row][j] -= factor * a[column][j];
+                    inverse[row][j] -= factor * inverse[column][j];
+                }
+            }
+        }
+    }
+    Some(inverse)
+}
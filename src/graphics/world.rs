@@ -1,5 +1,6 @@
-use super::RGBColor;
-use crate::graphics::GeometricObject;
+use super::{Filter, RGBColor, Ray, SamplerKind, Vector3D};
+use crate::graphics::{GeometricObject, HitInfo, Material};
+use std::cell::OnceCell;
 
 pub struct ViewPlane {
     hres: u32,
@@ -7,6 +8,13 @@ pub struct ViewPlane {
     ///Pixel size: Number of  in-world units corresponding to a pixel
     s: f64,
     samples: u32,
+    ///Times at which the shutter opens and closes, each ray gets a random time in between
+    shutter: (f64, f64),
+    sampler: SamplerKind,
+    ///Renders with the same seed are identical
+    seed: u64,
+    ///How samples are spread over the pixels around them
+    filter: Filter,
 }
 
 impl ViewPlane {
@@ -16,9 +24,33 @@ impl ViewPlane {
             vres,
             s,
             samples,
+            shutter: (0.0, 0.0),
+            sampler: SamplerKind::Sobol,
+            seed: 0,
+            filter: Filter::default(),
         }
     }
 
+    pub fn with_shutter(mut self, open: f64, close: f64) -> Self {
+        self.shutter = (open, close);
+        self
+    }
+
+    pub fn with_sampler(mut self, sampler: SamplerKind) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
     pub fn get_vres(&self) -> u32 {
         self.vres
     }
@@ -35,12 +67,32 @@ impl ViewPlane {
     pub fn get_samples(&self) -> u32 {
         self.samples
     }
+
+    pub fn get_shutter(&self) -> (f64, f64) {
+        self.shutter
+    }
+
+    pub fn get_sampler(&self) -> SamplerKind {
+        self.sampler
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn get_filter(&self) -> Filter {
+        self.filter
+    }
 }
 
 pub struct World {
     v_plane: ViewPlane,
     bg_color: RGBColor,
+    ///Rays which miss everything see a sky gradient, or bg_color without it
+    sky: bool,
     objects: Vec<Box<dyn GeometricObject >>,
+    ///Found on the first call of get_lights, forgotten when the objects may change
+    lights: OnceCell<Vec<usize>>,
 }
 
 impl World {
@@ -48,11 +100,19 @@ impl World {
         World {
             v_plane,
             bg_color,
+            sky: true,
             objects: Vec::new(),
+            lights: OnceCell::new(),
         }
     }
 
+    pub fn with_sky(mut self, sky: bool) -> Self {
+        self.sky = sky;
+        self
+    }
+
     pub fn get_objects_mut(&mut self) -> &mut Vec<Box<dyn GeometricObject>> {
+        self.lights = OnceCell::new();
         &mut self.objects
     }
 
@@ -60,6 +120,48 @@ impl World {
         &self.objects
     }
 
+    ///Materials of the objects without repeats, in the order the objects first use them
+    ///Objects share a material if they were given the same Rc
+    pub fn get_materials(&self) -> Vec<&dyn Material> {
+        let mut materials: Vec<&dyn Material> = Vec::new();
+        for object in &self.objects {
+            let material = object.get_material();
+            if !materials.iter().any(|other| std::ptr::addr_eq(*other, material)) {
+                materials.push(material);
+            }
+        }
+        materials
+    }
+
+    ///Indices of the objects which emit light and whose surfaces can be sampled, see
+    ///`GeometricObject::sample_surface`
+    ///They're only looked for once, not for every ray
+    pub fn get_lights(&self) -> &[usize] {
+        self.lights.get_or_init(|| {
+            (0..self.objects.len())
+                .filter(|index| {
+                    let object = &self.objects[*index];
+                    let emitted = object.get_material().get_emitted();
+                    emitted.r + emitted.g + emitted.b > 0.0
+                        && object.sample_surface((0.5, 0.5)).is_some()
+                })
+                .collect()
+        })
+    }
+
+    ///Index of the nearest object the ray hits and where
+    pub fn find_hit(&self, ray: &Ray) -> Option<(usize, HitInfo)> {
+        let mut nearest: Option<(usize, HitInfo)> = None;
+        for (index, object) in self.objects.iter().enumerate() {
+            if let Some(hit_info) = object.check_hit(ray) {
+                if nearest.as_ref().is_none_or(|(_, nearest)| hit_info.get_tmin() < nearest.get_tmin()) {
+                    nearest = Some((index, hit_info));
+                }
+            }
+        }
+        nearest
+    }
+
     pub fn get_view_plane(&self) -> &ViewPlane {
         &self.v_plane
     }
@@ -67,4 +169,15 @@ impl World {
     pub fn get_bg_color(&self) -> &RGBColor {
         &self.bg_color
     }
+
+    ///Color seen along a ray which misses everything
+    pub fn get_background(&self, ray: &Ray) -> RGBColor {
+        if !self.sky {
+            return self.bg_color;
+        }
+        let t = (ray.d.y() + 1.0) * 0.5;
+        let blue = Vector3D::new(0.4, 0.4, 1.0);
+        let white = Vector3D::new(1.0, 1.0, 1.0);
+        RGBColor::from(white * (1.0 - t) + blue * t)
+    }
 }
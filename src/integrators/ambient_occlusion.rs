@@ -0,0 +1,55 @@
+use super::{Integrator, Radiance};
+use crate::graphics::{uniform_sphere, HitInfo, RGBColor, Ray, Sampler, Vector3D, World};
+
+///Ambient occlusion: how much of the hemisphere above each surface is open, white where
+///nothing is within reach and darker the more other objects close it in
+///Ignores materials and lights, which makes it handy for checking geometry
+#[derive(Debug, Clone)]
+pub struct AmbientOcclusionIntegrator {
+    distance: f64,
+}
+
+impl AmbientOcclusionIntegrator {
+    ///distance-how far away objects still occlude a surface
+    pub fn new(distance: f64) -> Self {
+        AmbientOcclusionIntegrator { distance }
+    }
+}
+
+impl Integrator for AmbientOcclusionIntegrator {
+    fn get_name(&self) -> &'static str {
+        "ao"
+    }
+
+    ///Rays which miss everything see an open sky
+    fn trace(
+        &self,
+        ray: &Ray,
+        hit: Option<(usize, HitInfo)>,
+        world: &World,
+        sampler: &mut dyn Sampler,
+    ) -> Radiance {
+        let (white, black) = (RGBColor::new(1.0, 1.0, 1.0), RGBColor::new(0.0, 0.0, 0.0));
+        let hit_info = match hit {
+            Some((_, hit_info)) => hit_info,
+            None => return Radiance::from_direct(white),
+        };
+        //The side of the surface the ray came from
+        let mut normal = Vector3D::from(hit_info.get_normal().normalize());
+        if normal * ray.d > 0.0 {
+            normal = -normal;
+        }
+        //Cosine weighted like a Lambertian surface
+        let direction = normal + uniform_sphere(sampler.get_2d());
+        let direction = if direction.magnitude_sqr() > 1e-12 {
+            direction.normalize()
+        } else {
+            normal
+        };
+        let occlusion_ray = Ray::with_time(*hit_info.get_hitpoint(), direction, ray.time);
+        let occluded = world
+            .find_hit(&occlusion_ray)
+            .is_some_and(|(_, occluder)| occluder.get_tmin() < self.distance);
+        Radiance::from_direct(if occluded { black } else { white })
+    }
+}
@@ -0,0 +1,81 @@
+use super::{Integrator, Radiance};
+use crate::graphics::{HitInfo, RGBColor, Ray, Sampler, World};
+
+///Distance at which the depth view turns black, unless set with_far
+const FAR: f64 = 20.0;
+
+///What the debug integrator shows of the surfaces the rays hit first, rays which miss
+///everything are black
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DebugView {
+    ///Normals mapped from -1..1 to 0..1, x as red, y as green and z as blue
+    Normal,
+    ///Surface coordinates, u as red and v as green
+    Uv,
+    ///Distance from the view plane along the ray, white on the view plane fading
+    ///linearly to black at the far distance
+    Depth,
+}
+
+///Shows the normals, surface coordinates or depth of what the eye sees instead of
+///lighting it, without any noise
+#[derive(Debug, Clone)]
+pub struct DebugIntegrator {
+    view: DebugView,
+    far: f64,
+}
+
+impl DebugIntegrator {
+    pub fn new(view: DebugView) -> Self {
+        DebugIntegrator { view, far: FAR }
+    }
+
+    ///Distance at which the depth view turns black
+    pub fn with_far(mut self, far: f64) -> Self {
+        self.far = far;
+        self
+    }
+}
+
+impl Integrator for DebugIntegrator {
+    fn get_name(&self) -> &'static str {
+        match self.view {
+            DebugView::Normal => "normals",
+            DebugView::Uv => "uvs",
+            DebugView::Depth => "depth",
+        }
+    }
+
+    fn trace(
+        &self,
+        _ray: &Ray,
+        hit: Option<(usize, HitInfo)>,
+        _world: &World,
+        _sampler: &mut dyn Sampler,
+    ) -> Radiance {
+        let hit_info = match hit {
+            Some((_, hit_info)) => hit_info,
+            None => return Radiance::from_direct(RGBColor::new(0.0, 0.0, 0.0)),
+        };
+        let value = match self.view {
+            DebugView::Normal => {
+                let normal = hit_info.get_normal().normalize();
+                RGBColor::new(
+                    (normal.x() + 1.0) / 2.0,
+                    (normal.y() + 1.0) / 2.0,
+                    (normal.z() + 1.0) / 2.0,
+                )
+            }
+            DebugView::Uv => {
+                let (u, v) = hit_info.get_uv();
+                RGBColor::new(u, v, 0.0)
+            }
+            DebugView::Depth => {
+                let shade = (1.0 - hit_info.get_tmin() / self.far).max(0.0);
+                RGBColor::new(shade, shade, shade)
+            }
+        };
+        //Squared so the gamma corrected image shows the values themselves
+        Radiance::from_direct(value * value)
+    }
+}
@@ -0,0 +1,65 @@
+//! Rendering algorithms turning the rays from the eye into colors
+//!
+//! `SimpleTracer` generates the rays, finds what each one hits first and adds the colors
+//! to the film the same way whichever integrator it renders with.
+
+mod ambient_occlusion;
+mod debug;
+mod path;
+mod whitted;
+
+pub use self::ambient_occlusion::*;
+pub use self::debug::*;
+pub use self::path::*;
+pub use self::whitted::*;
+
+use crate::graphics::{HitInfo, RGBColor, Ray, Sampler, World};
+use std::fmt::Debug;
+
+///Light reaching the eye along a ray, colors are linear
+#[derive(Debug, Copy, Clone)]
+pub struct Radiance {
+    pub color: RGBColor,
+    ///Part of the color emitted by the first surface the ray hits or reaching that
+    ///surface straight from where it came from, for the direct and indirect AOVs
+    pub direct: RGBColor,
+}
+
+impl Radiance {
+    ///Radiance which is all direct
+    pub fn from_direct(color: RGBColor) -> Self {
+        Radiance {
+            color,
+            direct: color,
+        }
+    }
+}
+
+///Rendering algorithm, shared by the worker threads of a render
+pub trait Integrator: Debug + Send + Sync {
+    ///ray-from the eye
+    ///hit-index of the object the ray hits first and where, as found by `World::find_hit`
+    ///sampler-source of the random numbers, the camera has taken its dimensions already
+    fn trace(
+        &self,
+        ray: &Ray,
+        hit: Option<(usize, HitInfo)>,
+        world: &World,
+        sampler: &mut dyn Sampler,
+    ) -> Radiance;
+
+    ///Name of the algorithm as given to --integrator, a render only carries on from a
+    ///checkpoint of the same
+    fn get_name(&self) -> &'static str;
+
+    ///Bounces after which paths end, for integrators which follow rays through bounces
+    ///A render only carries on from a checkpoint with the same, see `Checkpoint::check`
+    fn get_max_depth(&self) -> Option<u32> {
+        None
+    }
+
+    ///Bounces after which Russian roulette may end a path, for integrators which use it
+    fn get_roulette_depth(&self) -> Option<u32> {
+        None
+    }
+}
@@ -0,0 +1,163 @@
+use super::{Integrator, Radiance};
+use crate::graphics::{HitInfo, RGBColor, Ray, Sampler, World};
+
+///Bounces after which a path ends in black, unless set with_max_depth
+const MAX_DEPTH: u32 = 20;
+///Bounces after which Russian roulette may end a path, unless set with_roulette_depth
+const ROULETTE_DEPTH: u32 = 3;
+
+///Path tracer following each ray as the materials scatter it until it escapes to the
+///background, is absorbed or ends, the default integrator
+#[derive(Debug, Clone)]
+pub struct PathIntegrator {
+    max_depth: u32,
+    roulette_depth: u32,
+}
+
+impl PathIntegrator {
+    pub fn new() -> Self {
+        PathIntegrator {
+            max_depth: MAX_DEPTH,
+            roulette_depth: ROULETTE_DEPTH,
+        }
+    }
+
+    ///Bounces after which a path ends, a path which hits a surface after that many adds
+    ///nothing more, as if the surface were black
+    ///Paths which escape sooner see the background, so cutting them short darkens the
+    ///image where light takes many bounces, e.g. through glass
+    pub fn with_max_depth(mut self, max_depth: u32) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    ///Bounces after which Russian roulette ends paths at random, the more likely the less
+    ///light they still carry, and brightens the ones which go on to make up for it
+    ///The image stays the same on average, paths through dim surroundings get cheaper
+    pub fn with_roulette_depth(mut self, roulette_depth: u32) -> Self {
+        self.roulette_depth = roulette_depth;
+        self
+    }
+
+    ///Light reaching the eye along a path which goes on with the ray after bounces
+    ///scatterings, and the part of it emitted by the first surface the ray hits, or the
+    ///background if it misses
+    ///throughput-fraction of the light arriving along the ray which reaches the eye
+    fn trace_path(
+        &self,
+        mut ray: Ray,
+        world: &World,
+        mut bounces: u32,
+        mut throughput: RGBColor,
+        sampler: &mut dyn Sampler,
+    ) -> (RGBColor, RGBColor) {
+        let black = RGBColor::new(0.0, 0.0, 0.0);
+        let mut color = black;
+        let mut first_emitted = None;
+        loop {
+            let (index, hit_info) = match world.find_hit(&ray) {
+                Some(hit) => hit,
+                None => {
+                    let background = throughput * world.get_background(&ray);
+                    return (color + background, first_emitted.unwrap_or(background));
+                }
+            };
+            if bounces >= self.max_depth {
+                return (color, first_emitted.unwrap_or(black));
+            }
+            let material = world.get_objects()[index].get_material();
+            let emitted = throughput * material.get_emitted();
+            color = color + emitted;
+            let first_emitted = *first_emitted.get_or_insert(emitted);
+            let (ray_out, attenuation) = match material.process(&ray, &hit_info, sampler) {
+                Some(scattered) => scattered,
+                None => return (color, first_emitted),
+            };
+            bounces += 1;
+            throughput = match self.survive(throughput * attenuation, bounces, sampler) {
+                Some(throughput) => throughput,
+                None => return (color, first_emitted),
+            };
+            ray = ray_out;
+        }
+    }
+
+    ///Russian roulette once a path has bounced more than roulette_depth times: None if
+    ///the path ends, else its throughput divided by the chance it had to go on, so the
+    ///paths which go on make up for the ones which ended
+    ///Paths carrying all the light they got always go on
+    fn survive(
+        &self,
+        throughput: RGBColor,
+        bounces: u32,
+        sampler: &mut dyn Sampler,
+    ) -> Option<RGBColor> {
+        if bounces <= self.roulette_depth {
+            return Some(throughput);
+        }
+        let survival = throughput.r.max(throughput.g).max(throughput.b);
+        if survival >= 1.0 {
+            Some(throughput)
+        } else if sampler.get_1d() < survival {
+            Some(throughput * (1.0 / survival))
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for PathIntegrator {
+    fn default() -> Self {
+        PathIntegrator::new()
+    }
+}
+
+impl Integrator for PathIntegrator {
+    fn get_name(&self) -> &'static str {
+        "path"
+    }
+
+    fn trace(
+        &self,
+        ray: &Ray,
+        hit: Option<(usize, HitInfo)>,
+        world: &World,
+        sampler: &mut dyn Sampler,
+    ) -> Radiance {
+        let (index, hit_info) = match hit {
+            Some(hit) => hit,
+            None => return Radiance::from_direct(world.get_background(ray)),
+        };
+        if self.max_depth == 0 {
+            return Radiance::from_direct(RGBColor::new(0.0, 0.0, 0.0));
+        }
+        let material = world.get_objects()[index].get_material();
+        let emitted = material.get_emitted();
+        let scattered =
+            material
+                .process(ray, &hit_info, sampler)
+                .and_then(|(ray_out, attenuation)| {
+                    self.survive(attenuation, 1, sampler)
+                        .map(|attenuation| (ray_out, attenuation))
+                });
+        match scattered {
+            Some((ray_out, attenuation)) => {
+                let (incoming, incoming_emitted) =
+                    self.trace_path(ray_out, world, 1, attenuation, sampler);
+                Radiance {
+                    color: emitted + incoming,
+                    direct: emitted + incoming_emitted,
+                }
+            }
+            None => Radiance::from_direct(emitted),
+        }
+    }
+
+    fn get_max_depth(&self) -> Option<u32> {
+        Some(self.max_depth)
+    }
+
+    fn get_roulette_depth(&self) -> Option<u32> {
+        Some(self.roulette_depth)
+    }
+}
@@ -0,0 +1,149 @@
+use super::{Integrator, Radiance};
+use crate::graphics::{HitInfo, Material, RGBColor, Ray, Sampler, Vector3D, World};
+
+///Mirror and glass bounces after which a ray ends in black, unless set with_max_depth
+const MAX_DEPTH: u32 = 8;
+
+///Whitted style ray tracer: follows rays through mirrors and glass, and lights every
+///other surface directly, by a shadow ray to a point on one of the lights and by the
+///background the one ray it scatters sees if it escapes
+///Much less noisy than the path tracer but leaves out the light bouncing between
+///diffuse surfaces, so shadows and the parts lights don't reach come out darker
+///Lights whose surface can't be sampled are only seen by the scattered ray, see
+///`World::get_lights`
+#[derive(Debug, Clone)]
+pub struct WhittedIntegrator {
+    max_depth: u32,
+}
+
+impl WhittedIntegrator {
+    pub fn new() -> Self {
+        WhittedIntegrator {
+            max_depth: MAX_DEPTH,
+        }
+    }
+
+    ///Mirror and glass bounces after which a ray which hits a surface adds nothing more
+    pub fn with_max_depth(mut self, max_depth: u32) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+}
+
+///Light reaching point from a point sampled on one of the lights, chosen at random,
+///scattered back along ray_in by the material
+fn sample_lights(
+    lights: &[usize],
+    world: &World,
+    ray_in: &Ray,
+    hit_info: &HitInfo,
+    material: &dyn Material,
+    sampler: &mut dyn Sampler,
+) -> RGBColor {
+    let black = RGBColor::new(0.0, 0.0, 0.0);
+    if lights.is_empty() {
+        return black;
+    }
+    let choice = ((sampler.get_1d() * lights.len() as f64) as usize).min(lights.len() - 1);
+    let light = lights[choice];
+    let object = &world.get_objects()[light];
+    let (point, normal, area_pdf) = match object.sample_surface(sampler.get_2d()) {
+        Some(sample) => sample,
+        None => return black,
+    };
+    let to_light = point - *hit_info.get_hitpoint();
+    let distance = to_light.magnitude_sqr().sqrt();
+    if distance == 0.0 {
+        return black;
+    }
+    let direction = to_light * (1.0 / distance);
+    //Lights emit from both sides
+    let cosine = (Vector3D::from(normal) * direction).abs();
+    let bsdf = match material.bsdf(ray_in, hit_info, &direction) {
+        Some(bsdf) if cosine > 0.0 => bsdf,
+        _ => return black,
+    };
+    //Anything nearer than the sampled point, including the light itself, casts a shadow
+    let shadow_ray = Ray::with_time(*hit_info.get_hitpoint(), direction, ray_in.time);
+    match world.find_hit(&shadow_ray) {
+        Some((index, shadow_hit))
+            if index == light && (shadow_hit.get_tmin() - distance).abs() <= 1e-6 * distance => {}
+        _ => return black,
+    }
+    //Density of the direction per unit solid angle, of picking the light included
+    let pdf = area_pdf * distance * distance / cosine / lights.len() as f64;
+    bsdf * object.get_material().get_emitted() * (1.0 / pdf)
+}
+
+impl Default for WhittedIntegrator {
+    fn default() -> Self {
+        WhittedIntegrator::new()
+    }
+}
+
+impl Integrator for WhittedIntegrator {
+    fn get_name(&self) -> &'static str {
+        "whitted"
+    }
+
+    fn trace(
+        &self,
+        ray: &Ray,
+        hit: Option<(usize, HitInfo)>,
+        world: &World,
+        sampler: &mut dyn Sampler,
+    ) -> Radiance {
+        let (mut ray, mut hit) = (ray.clone(), hit);
+        let (mut color, mut throughput) =
+            (RGBColor::new(0.0, 0.0, 0.0), RGBColor::new(1.0, 1.0, 1.0));
+        //Light gathered at the first surface, everything after a bounce is indirect
+        let mut direct = None;
+        let lights = world.get_lights();
+        for bounces in 0.. {
+            let (index, hit_info) = match hit {
+                Some(hit) => hit,
+                None => {
+                    color = color + throughput * world.get_background(&ray);
+                    break;
+                }
+            };
+            if bounces >= self.max_depth {
+                break;
+            }
+            let material = world.get_objects()[index].get_material();
+            color = color + throughput * material.get_emitted();
+            let (ray_out, attenuation) = match material.process(&ray, &hit_info, sampler) {
+                Some(scattered) => scattered,
+                None => break,
+            };
+            //Materials with a density scatter over a range of directions, mirrors and glass
+            //into a few
+            if material.pdf(&ray, &hit_info, &ray_out.d).is_some() {
+                let direct_light =
+                    sample_lights(lights, world, &ray, &hit_info, material, sampler);
+                //The lights sampled are left out of what the scattered ray sees, they'd count twice
+                let seen = match world.find_hit(&ray_out) {
+                    Some((index, _)) if lights.contains(&index) => RGBColor::new(0.0, 0.0, 0.0),
+                    Some((index, _)) => world.get_objects()[index].get_material().get_emitted(),
+                    None => world.get_background(&ray_out),
+                };
+                color = color + throughput * (direct_light + attenuation * seen);
+                break;
+            }
+            throughput = throughput * attenuation;
+            if bounces == 0 {
+                direct = Some(color);
+            }
+            hit = world.find_hit(&ray_out);
+            ray = ray_out;
+        }
+        Radiance {
+            color,
+            direct: direct.unwrap_or(color),
+        }
+    }
+
+    fn get_max_depth(&self) -> Option<u32> {
+        Some(self.max_depth)
+    }
+}
@@ -0,0 +1,55 @@
+//! Path tracer rendering scenes of spheres, boxes, quadrics, SDFs and more
+//!
+//! A scene is a `World` built by a function of the scene time, so animations and motion
+//! blur come from calling it at different times. `SimpleTracer` renders it to a `Film`,
+//! in memory, to an image file or to a whole sequence of frames, with a path tracer or
+//! another of the `integrators`.
+//!
+//! ```
+//! use ray_path::graphics::*;
+//! use ray_path::shapes::Sphere;
+//! use ray_path::{RenderBudget, SimpleTracer};
+//! use std::rc::Rc;
+//!
+//! fn get_world(_t: f64) -> World {
+//!     let mut world = World::new(ViewPlane::new(16, 12, 0.25, 4), RGBColor::new(0.0, 0.0, 0.0));
+//!     let red = Rc::new(LambertianMaterial::new(RGBColor::new(0.8, 0.2, 0.2)));
+//!     world
+//!         .get_objects_mut()
+//!         .push(Box::new(Sphere::new(Point3D::new(0.0, 0.0, 0.0), 1.0, red)));
+//!     world
+//! }
+//!
+//! let budget = RenderBudget::new().with_max_samples(4);
+//! let film = SimpleTracer::new().render_to_film(Box::new(get_world), 0.0, &budget, &mut |progress| {
+//!     println!("{} samples after {:?}", progress.samples, progress.elapsed);
+//! });
+//! let image = film.to_image();
+//! assert_eq!((film.get_width(), film.get_height()), (16, 12));
+//! ```
+
+pub mod animation;
+pub mod aov;
+pub mod checkpoint;
+pub mod denoise;
+mod exr;
+pub mod film;
+pub mod graphics;
+pub mod integrators;
+pub mod scene;
+pub mod sequence;
+pub mod shapes;
+#[cfg(test)]
+mod tests;
+pub mod tracer;
+
+pub use self::aov::Aov;
+pub use self::checkpoint::Checkpoint;
+pub use self::denoise::Denoiser;
+pub use self::film::Film;
+pub use self::integrators::{
+    AmbientOcclusionIntegrator, DebugIntegrator, DebugView, Integrator, PathIntegrator,
+    WhittedIntegrator,
+};
+pub use self::sequence::{render_sequence, FrameEvent, SequenceSettings, VideoOutput};
+pub use self::tracer::{CancelToken, Progress, RenderBudget, RenderEvent, SimpleTracer};
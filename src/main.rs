@@ -1,57 +1,249 @@
-mod graphics;
-mod shapes;
-mod tracer;
-
-use self::graphics::*;
-use self::shapes::*;
-use self::tracer::*;
+use ray_path::animation::Timeline;
+use ray_path::graphics::*;
+use ray_path::scene::SceneNode;
+use ray_path::shapes::*;
+use ray_path::{
+    render_sequence, AmbientOcclusionIntegrator, DebugIntegrator, DebugView, Denoiser, FrameEvent,
+    PathIntegrator, RenderBudget, RenderEvent, SequenceSettings, SimpleTracer, VideoOutput,
+    WhittedIntegrator,
+};
 use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+///Scene time between two frames unless --fps says otherwise
+const FRAME_DURATION: f64 = 0.2;
+///Fraction of the time between two frames for which the shutter stays open
+const SHUTTER_FRACTION: f64 = 0.5;
+///Distance within which objects occlude each other with --integrator ao
+const AO_DISTANCE: f64 = 2.0;
+///Animation of the default scene unless --timeline gives another
+const DEFAULT_TIMELINE: &str = include_str!("../scenes/default.timeline");
+
+const USAGE: &str = "Usage: ray_path [--start N] [--end N] [--fps F] [--output PATTERN] [--overwrite] [--ffmpeg VIDEO] [--gif GIF]
+                [--time SECONDS] [--samples N] [--noise ERROR] [--adaptive ERROR]
+                [--snapshot SECONDS] [--heatmap PATTERN] [--aovs PATTERN]
+                [--denoise] [--keep-raw] [--integrator NAME] [--max-depth N]
+                [--timeline FILE] [--help]
+  --start, --end  first and last frame to render, both 0 by default
+  --fps           frames per second, 1/FRAME_DURATION by default
+  --output        frame path, #s are replaced by the frame number
+  --overwrite     render frames again even if they're already on disk
+  --ffmpeg        also encode the frames to a video with ffmpeg
+  --gif           also encode the frames to an animated GIF
+  --time          render each frame progressively for at most this long
+  --samples       render each frame progressively up to this many samples per pixel
+  --noise         render each frame progressively until the average standard error of
+                  the pixels is below this, e.g. 0.01
+  --adaptive      render each frame progressively, sampling only the tiles with a pixel
+                  whose standard error is above this, until none is left
+  --snapshot      seconds between saves of a frame in progress and of a checkpoint, from which
+                  a frame that was interrupted carries on when rendered again, 10 by default
+  --heatmap       also save images of the samples spent on each pixel, #s are replaced
+                  by the frame number
+  --aovs          also save the depth, normal, albedo, position, object and material IDs
+                  and direct and indirect light of the first hits, #s are replaced by the
+                  frame number, a .exr path gives one EXR with a layer for each, any other
+                  an image for each
+  --denoise       save the frames denoised with a filter guided by the AOVs
+  --keep-raw      with --denoise, also save the frames as rendered, with .raw before
+                  the extension
+  --integrator    rendering algorithm: path, the default, whitted for mirrors, glass and
+                  direct light only, ao for ambient occlusion, or normals, uvs or depth
+                  to show those of the surfaces
+  --max-depth     with the path or whitted integrator, bounces after which a path ends in
+                  black, 20 and 8 by default
+  --timeline      keyframes animating the scene instead of scenes/default.timeline, see
+                  the docs of Timeline for the format
+With none of --time, --samples, --noise and --adaptive the view plane's sample count is used, unless
+--snapshot is given, in which case rendering goes on until stopped";
 
 fn main() {
+    if std::env::args().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", USAGE);
+        return;
+    }
+    let (settings, tracer, timeline) = match parse_args(std::env::args().skip(1)) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+    let tracer = tracer.with_aovs(settings.aov_pattern.is_some() || settings.denoiser.is_some());
+    let (timeline, frame_interval) = (Arc::new(timeline), settings.get_frame_interval());
+    let worldfunc = move |t| get_world(t, frame_interval, &timeline);
+    if let Err(e) = render_sequence(&tracer, worldfunc, &settings, &mut print_event) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<(SequenceSettings, SimpleTracer, Timeline), String> {
+    let mut settings = SequenceSettings::new(0, 0, 1.0 / FRAME_DURATION, "./render/img######.jpeg");
+    let (mut budget, mut progressive) = (RenderBudget::new(), false);
+    let (mut integrator, mut max_depth) = ("path".to_string(), None);
+    let mut timeline = None;
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--start" => settings.start_frame = value()?.parse().map_err(|_| "--start must be a frame number")?,
+            "--end" => settings.end_frame = value()?.parse().map_err(|_| "--end must be a frame number")?,
+            "--fps" => settings.fps = value()?.parse().map_err(|_| "--fps must be a number")?,
+            "--output" => settings.output_pattern = value()?,
+            "--overwrite" => settings.skip_existing = false,
+            "--ffmpeg" => settings.video = Some(VideoOutput::Ffmpeg(value()?)),
+            "--gif" => settings.video = Some(VideoOutput::Gif(value()?)),
+            "--time" => {
+                let seconds: f64 = value()?.parse().map_err(|_| "--time must be a number of seconds")?;
+                budget = budget.with_time_limit(Duration::from_secs_f64(seconds.max(0.0)));
+                progressive = true;
+            }
+            "--samples" => {
+                budget = budget.with_max_samples(value()?.parse().map_err(|_| "--samples must be a number")?);
+                progressive = true;
+            }
+            "--noise" => {
+                budget = budget.with_noise_threshold(value()?.parse().map_err(|_| "--noise must be a number")?);
+                progressive = true;
+            }
+            "--adaptive" => {
+                budget = budget.with_adaptive_threshold(value()?.parse().map_err(|_| "--adaptive must be a number")?);
+                progressive = true;
+            }
+            "--heatmap" => settings.heat_map_pattern = Some(value()?),
+            "--aovs" => settings.aov_pattern = Some(value()?),
+            "--denoise" => settings.denoiser = Some(Denoiser::new()),
+            "--keep-raw" => settings.keep_raw = true,
+            "--integrator" => integrator = value()?,
+            "--max-depth" => {
+                max_depth = Some(value()?.parse().map_err(|_| "--max-depth must be a number")?);
+            }
+            "--timeline" => timeline = Some(Timeline::load(&value()?)?),
+            "--snapshot" => {
+                let seconds: f64 = value()?.parse().map_err(|_| "--snapshot must be a number of seconds")?;
+                budget = budget.with_snapshot_interval(Duration::from_secs_f64(seconds.max(0.0)));
+                progressive = true;
+            }
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+    if progressive {
+        settings.budget = Some(budget);
+    }
+    if settings.keep_raw && settings.denoiser.is_none() {
+        return Err("--keep-raw needs --denoise".to_string());
+    }
+    if settings.end_frame < settings.start_frame {
+        return Err("--end is before --start".to_string());
+    }
+    if settings.fps.is_nan() || settings.fps <= 0.0 {
+        return Err("--fps must be positive".to_string());
+    }
+    let timeline = match timeline {
+        Some(timeline) => timeline,
+        None => Timeline::parse(DEFAULT_TIMELINE).map_err(|e| format!("default timeline: {}", e))?,
+    };
+    Ok((settings, get_tracer(&integrator, max_depth)?, timeline))
+}
+
+fn get_tracer(integrator: &str, max_depth: Option<u32>) -> Result<SimpleTracer, String> {
     let tracer = SimpleTracer::new();
-    for t in 0..1{
-        println!("Completed frames {}",t+1);
-        tracer.render(Box::new(get_world),&format!("./render/img{:06}.jpeg",t),t as f64/5.0);
+    if max_depth.is_some() && integrator != "path" && integrator != "whitted" {
+        return Err("--max-depth needs the path or whitted integrator".to_string());
     }
+    Ok(match integrator {
+        "path" => {
+            let path = PathIntegrator::new();
+            tracer.with_integrator(match max_depth {
+                Some(max_depth) => path.with_max_depth(max_depth),
+                None => path,
+            })
+        }
+        "whitted" => {
+            let whitted = WhittedIntegrator::new();
+            tracer.with_integrator(match max_depth {
+                Some(max_depth) => whitted.with_max_depth(max_depth),
+                None => whitted,
+            })
+        }
+        "ao" => tracer.with_integrator(AmbientOcclusionIntegrator::new(AO_DISTANCE)),
+        "normals" => tracer.with_integrator(DebugIntegrator::new(DebugView::Normal)),
+        "uvs" => tracer.with_integrator(DebugIntegrator::new(DebugView::Uv)),
+        "depth" => tracer.with_integrator(DebugIntegrator::new(DebugView::Depth)),
+        _ => return Err(format!("unknown integrator {}", integrator)),
+    })
 }
 
 
-fn get_world(t:f64)->World{
-    let (hres, vres, s,samples) = (1000, 800, 1.0 / (180.0+t/5.0),128);
-    let metallic1 = Rc::new(MetallicMaterial::new(RGBColor::new(0.5, 0.5, 0.5), 0.5*t.sin()+0.5));
+fn print_event(frame: u32, event: &FrameEvent) {
+    match event {
+        FrameEvent::Skipped { path } => println!("Skipping frame {}, {} exists", frame, path),
+        FrameEvent::Render(RenderEvent::Resumed { samples }) => {
+            println!("Resuming frame {} from {} samples", frame, samples)
+        }
+        FrameEvent::Render(RenderEvent::CheckpointIgnored { path, error }) => {
+            eprintln!("Ignoring {}: {}", path, error)
+        }
+        FrameEvent::Render(RenderEvent::Snapshot(progress)) => println!(
+            "{} samples, noise {:.4}, {:.1}s",
+            progress.samples,
+            progress.film.get_noise(),
+            progress.elapsed.as_secs_f64()
+        ),
+        FrameEvent::Render(RenderEvent::Progress(_)) => {}
+        FrameEvent::Completed => println!("Completed frame {}", frame),
+    }
+}
+
+///frame_interval-scene time until the next frame
+fn get_world(t: f64, frame_interval: f64, timeline: &Timeline) -> World {
+    let shutter_close = t + frame_interval * SHUTTER_FRACTION;
+    let (hres, vres, samples) = (1000, 800, 128);
+    let s = timeline.get_f64("camera.pixel_size", t, 1.0 / 180.0);
+    let fuzz = timeline.get_f64("disc.fuzz", t, 0.5);
+    let disc_normal = timeline
+        .get_vector("disc.normal", t)
+        .unwrap_or_else(|| Vector3D::new(-1.0, 0.0, 0.8));
+    let metallic1 = Rc::new(MetallicMaterial::new(RGBColor::new(0.5, 0.5, 0.5), fuzz));
     let metallic2 = Rc::new(MetallicMaterial::new(RGBColor::new(1.0, 1.0, 1.0), 0.0));
     let diffuse1 = Rc::new(LambertianMaterial::new(RGBColor::new(0.3, 0.2, 0.6)));
     let diffuse2 = Rc::new(LambertianMaterial::new(RGBColor::new(0.9, 0.5, 0.0)));
     let dielectric1 = Rc::new(Dielectric::new(1.3));
-    //let emit1 = Rc::new(DiffuseLight::new(RGBColor::new(1.0, 1.0, 1.0)));
-
-
-    let sphere1 = Sphere::new(Point3D::new(41.0*(t/5.0).cos(), -42.0+41.0*(t/5.0).sin(), 0.0), 1.0, metallic2.clone());
-    let sphere2 = Sphere::new(Point3D::new(2.0, -1.0*t.sin(), 0.0), 1.0, diffuse1.clone());
-    let sphere3 = Sphere::new(Point3D::new(-41.0*(t/5.0).cos(), -42.0+41.0*(t/5.0).sin(), t.sin()), 1.0, dielectric1);
-    let sphere4 = Sphere::new(Point3D::new(0.0, -42.0, 0.0), 40.0, diffuse2.clone());
-    let disc = ThinDisc::new(
-        Point3D::new(3.0, 0.0, -1.2),
-        2.0,
-        Normal3D::new(-1.0, -0.3*t.sin(), 0.8),
-        //Normal3D::new(0.0,0.0,1.0),
-        metallic1.clone()
-    );
-    /*let plane = Plane::new(
-        Point3D::new(0.0, 2.0, -5.0),
-        Normal3D::new(0.0, 1.0, 1.0),
-        metallic2.clone(),
-    );*/
+    let ball = |x: f64, material: Rc<dyn Material>| -> Rc<dyn GeometricObject> {
+        Rc::new(Sphere::new(Point3D::new(x, 0.0, 0.0), 1.0, material))
+    };
+
+    //The timeline moves the nodes, this is where they are without it
+    let mut scene = SceneNode::new("scene")
+        .with_child(
+            SceneNode::new("ground")
+                .with_transform(Transform::translate(Vector3D::new(0.0, -42.0, 0.0)))
+                .with_object(Rc::new(Sphere::new(Point3D::origin(), 40.0, diffuse2)))
+                .with_child(SceneNode::new("east").with_object(ball(41.0, metallic2)))
+                .with_child(SceneNode::new("west").with_object(ball(-41.0, dielectric1))),
+        )
+        .with_child(
+            SceneNode::new("bouncer")
+                .with_transform(Transform::translate(Vector3D::new(2.0, 0.0, 0.0)))
+                .with_object(ball(0.0, diffuse1)),
+        )
+        .with_child(
+            SceneNode::new("disc")
+                .with_transform(Transform::translate(Vector3D::new(3.0, 0.0, -1.2)))
+                .with_object(Rc::new(ThinDisc::new(
+                    Point3D::origin(),
+                    2.0,
+                    Normal3D::new(disc_normal.x(), disc_normal.y(), disc_normal.z()),
+                    metallic1,
+                ))),
+        );
+    scene.animate(timeline, t, shutter_close);
 
     let mut world = World::new(
-        ViewPlane::new(hres, vres, s, samples),
+        ViewPlane::new(hres, vres, s, samples).with_shutter(t, shutter_close),
         RGBColor::new(0.0, 0.0, 0.0),
     );
-    world.get_objects_mut().push(Box::new(sphere1));
-    //world.get_objects_mut().push(Box::new(plane));
-    world.get_objects_mut().push(Box::new(sphere2));
-    world.get_objects_mut().push(Box::new(sphere3));
-    world.get_objects_mut().push(Box::new(sphere4));
-    world.get_objects_mut().push(Box::new(disc));
+    scene.flatten_into(&mut world);
     world
 }
@@ -0,0 +1,206 @@
+use crate::animation::Timeline;
+use crate::graphics::*;
+use crate::shapes::Instance;
+use std::rc::Rc;
+
+///Named group in the scene graph, positioned relative to its parent
+///Nodes are addressed by the names along their path, e.g. "robot/arm/hand"
+pub struct SceneNode {
+    name: String,
+    ///Node space to parent space, moving over the shutter interval for motion blur
+    transform: AnimatedTransform,
+    ///Hidden nodes are left out of the world along with all their descendants
+    visible: bool,
+    ///Replaces the material of every object below this node unless a descendant overrides it again
+    material: Option<Rc<dyn Material>>,
+    objects: Vec<Rc<dyn GeometricObject>>,
+    children: Vec<SceneNode>,
+}
+
+impl SceneNode {
+    pub fn new(name: &str) -> Self {
+        SceneNode {
+            name: name.to_string(),
+            transform: AnimatedTransform::fixed(Transform::identity()),
+            visible: true,
+            material: None,
+            objects: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    pub fn with_transform(mut self, transform: Transform) -> Self {
+        self.transform = AnimatedTransform::fixed(transform);
+        self
+    }
+
+    pub fn with_animated_transform(mut self, transform: AnimatedTransform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    pub fn with_material(mut self, material: Rc<dyn Material>) -> Self {
+        self.material = Some(material);
+        self
+    }
+
+    pub fn with_object(mut self, object: Rc<dyn GeometricObject>) -> Self {
+        self.objects.push(object);
+        self
+    }
+
+    pub fn with_child(mut self, child: SceneNode) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_transform(&self) -> &AnimatedTransform {
+        &self.transform
+    }
+
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.transform = AnimatedTransform::fixed(transform);
+    }
+
+    pub fn set_animated_transform(&mut self, transform: AnimatedTransform) {
+        self.transform = transform;
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    pub fn set_material(&mut self, material: Option<Rc<dyn Material>>) {
+        self.material = material;
+    }
+
+    pub fn get_objects_mut(&mut self) -> &mut Vec<Rc<dyn GeometricObject>> {
+        &mut self.objects
+    }
+
+    pub fn get_children(&self) -> &Vec<SceneNode> {
+        &self.children
+    }
+
+    pub fn get_children_mut(&mut self) -> &mut Vec<SceneNode> {
+        &mut self.children
+    }
+
+    ///Descendant at a path relative to this node, e.g. "arm/hand"
+    ///The first child with a matching name is taken at every level
+    pub fn find(&self, path: &str) -> Option<&SceneNode> {
+        path.split('/')
+            .filter(|name| !name.is_empty())
+            .try_fold(self, |node, name| {
+                node.children.iter().find(|child| child.name == name)
+            })
+    }
+
+    pub fn find_mut(&mut self, path: &str) -> Option<&mut SceneNode> {
+        path.split('/')
+            .filter(|name| !name.is_empty())
+            .try_fold(self, |node, name| {
+                node.children.iter_mut().find(|child| child.name == name)
+            })
+    }
+
+    ///World transform at a time of the node at a path below this one, taking this node
+    ///as the root so its own transform is included, None if the path doesn't exist
+    pub fn get_world_transform(&self, path: &str, time: f64) -> Option<Transform> {
+        let mut node = self;
+        let mut transform = self.transform.at(time);
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            node = node.children.iter().find(|child| child.name == name)?;
+            transform = transform * node.transform.at(time);
+        }
+        Some(transform)
+    }
+
+    ///Moves the nodes below this one over an interval, usually the shutter interval,
+    ///along the timeline's `<path>.translate`, `<path>.rotate` and `<path>.scale` tracks,
+    ///e.g. "arm/hand.rotate"
+    ///Nodes without any of those tracks keep their transforms
+    pub fn animate(&mut self, timeline: &Timeline, start_time: f64, end_time: f64) {
+        for child in &mut self.children {
+            child.animate_with(timeline, "", start_time, end_time);
+        }
+    }
+
+    fn animate_with(
+        &mut self,
+        timeline: &Timeline,
+        parent_path: &str,
+        start_time: f64,
+        end_time: f64,
+    ) {
+        let path = format!("{}{}", parent_path, self.name);
+        if timeline.animates(&path) {
+            self.transform = timeline.get_animated_transform(&path, start_time, end_time);
+        }
+        let parent_path = format!("{}/", path);
+        for child in &mut self.children {
+            child.animate_with(timeline, &parent_path, start_time, end_time);
+        }
+    }
+
+    ///Adds every visible object below this node to the world as an instance carrying
+    ///the accumulated transform and material override
+    pub fn flatten_into(&self, world: &mut World) {
+        self.flatten_with(
+            world,
+            &AnimatedTransform::fixed(Transform::identity()),
+            None,
+        );
+    }
+
+    fn flatten_with(
+        &self,
+        world: &mut World,
+        parent_transform: &AnimatedTransform,
+        parent_material: Option<&Rc<dyn Material>>,
+    ) {
+        if !self.visible {
+            return;
+        }
+        let transform = compose(parent_transform, &self.transform);
+        let material = self.material.as_ref().or(parent_material);
+        for object in &self.objects {
+            let instance = Instance::animated(Rc::clone(object), transform);
+            let instance = match material {
+                Some(material) => instance.with_material(Rc::clone(material)),
+                None => instance,
+            };
+            world.get_objects_mut().push(Box::new(instance));
+        }
+        for child in &self.children {
+            child.flatten_with(world, &transform, material);
+        }
+    }
+}
+
+///parent*child, moving if either of them does
+///The product is taken at the ends of the motion and interpolated, which is exact for
+///translations and close for the small rotations of a shutter interval
+fn compose(parent: &AnimatedTransform, child: &AnimatedTransform) -> AnimatedTransform {
+    if let (Some(parent), Some(child)) = (parent.get_fixed(), child.get_fixed()) {
+        return AnimatedTransform::fixed(*parent * *child);
+    }
+    let (start_time, end_time) = match child.get_fixed() {
+        Some(_) => parent.get_interval(),
+        None => child.get_interval(),
+    };
+    AnimatedTransform::new(
+        parent.at(start_time) * child.at(start_time),
+        start_time,
+        parent.at(end_time) * child.at(end_time),
+        end_time,
+    )
+}
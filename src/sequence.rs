@@ -0,0 +1,294 @@
+use crate::aov::Aov;
+use crate::denoise::Denoiser;
+use crate::graphics::World;
+use crate::film::Film;
+use crate::tracer::{RenderBudget, RenderEvent, SimpleTracer};
+use image::gif::Encoder;
+use num_rational::Ratio;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+
+///Where the rendered frames are encoded to besides the frame images
+#[derive(Debug, Clone, PartialEq)]
+pub enum VideoOutput {
+    ///Video at this path encoded by piping the frames to the ffmpeg on the PATH
+    Ffmpeg(String),
+    ///Animated GIF at this path, APNG isn't supported by the png encoder
+    Gif(String),
+}
+
+///What render_sequence reports about a frame, for the caller to show
+pub enum FrameEvent<'a> {
+    ///The frame's image exists and skip_existing is set
+    Skipped { path: &'a str },
+    Render(&'a RenderEvent<'a>),
+    ///The frame's image is in place
+    Completed,
+}
+
+#[derive(Debug, Clone)]
+pub struct SequenceSettings {
+    pub start_frame: u32,
+    ///Last frame, inclusive
+    pub end_frame: u32,
+    ///Frames per second of scene time, also the playback rate of videos
+    pub fps: f64,
+    ///Path of each frame with a run of #s replaced by the zero padded frame number,
+    ///e.g. "./render/img######.jpeg"
+    pub output_pattern: String,
+    ///Frames which are already on disk aren't rendered again
+    pub skip_existing: bool,
+    pub video: Option<VideoOutput>,
+    ///Frames are rendered progressively within this budget instead of with the
+    ///view plane's sample count
+    pub budget: Option<RenderBudget>,
+    ///Pattern like output_pattern for images of the samples spent on each pixel
+    pub heat_map_pattern: Option<String>,
+    ///Pattern like output_pattern for the AOVs, which the tracer must record
+    ///A pattern ending in .exr gives one EXR per frame with the color and a layer per
+    ///AOV, any other an image per AOV with its name before the extension,
+    ///e.g. "img000001.depth.png"
+    pub aov_pattern: Option<String>,
+    ///Frames are saved denoised, which needs a tracer recording AOVs
+    pub denoiser: Option<Denoiser>,
+    ///With a denoiser, the frames as rendered are also saved with "raw" before the
+    ///extension, e.g. "img000001.raw.jpeg"
+    pub keep_raw: bool,
+}
+
+impl SequenceSettings {
+    pub fn new(start_frame: u32, end_frame: u32, fps: f64, output_pattern: &str) -> Self {
+        SequenceSettings {
+            start_frame,
+            end_frame,
+            fps,
+            output_pattern: output_pattern.to_string(),
+            skip_existing: true,
+            video: None,
+            budget: None,
+            heat_map_pattern: None,
+            aov_pattern: None,
+            denoiser: None,
+            keep_raw: false,
+        }
+    }
+
+    ///Scene time at which a frame is rendered
+    pub fn get_frame_time(&self, frame: u32) -> f64 {
+        f64::from(frame) / self.fps
+    }
+
+    ///Scene time between two frames, which a shutter can stay open for at most
+    pub fn get_frame_interval(&self) -> f64 {
+        1.0 / self.fps
+    }
+
+    pub fn get_frame_path(&self, frame: u32) -> String {
+        expand_pattern(&self.output_pattern, frame)
+    }
+}
+
+///Replaces a run of #s in the pattern with the zero padded frame number, a pattern
+///without #s gets the frame number before its extension
+pub(crate) fn expand_pattern(pattern: &str, frame: u32) -> String {
+    match pattern.find('#') {
+        Some(start) => {
+            let width = pattern[start..].chars().take_while(|c| *c == '#').count();
+            format!(
+                "{}{:0width$}{}",
+                &pattern[..start],
+                frame,
+                &pattern[start + width..],
+                width = width
+            )
+        }
+        None => {
+            let path = Path::new(pattern);
+            let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+            let name = match path.extension().and_then(|e| e.to_str()) {
+                Some(extension) => format!("{}{:06}.{}", stem, frame, extension),
+                None => format!("{}{:06}", stem, frame),
+            };
+            path.with_file_name(name).to_string_lossy().into_owned()
+        }
+    }
+}
+
+///Consumer of the finished frames of a sequence
+enum VideoEncoder {
+    Ffmpeg(Child),
+    Gif(Encoder<BufWriter<File>>, u16),
+}
+
+impl VideoEncoder {
+    fn new(video: &VideoOutput, fps: f64) -> Result<Self, String> {
+        match video {
+            VideoOutput::Ffmpeg(path) => Command::new("ffmpeg")
+                .args(["-y", "-loglevel", "error", "-f", "image2pipe"])
+                .args(["-framerate", &fps.to_string(), "-i", "-"])
+                .args(["-c:v", "libx264", "-pix_fmt", "yuv420p", path.as_str()])
+                .stdin(Stdio::piped())
+                .spawn()
+                .map(VideoEncoder::Ffmpeg)
+                .map_err(|e| format!("couldn't start ffmpeg: {}", e)),
+            VideoOutput::Gif(path) => {
+                let file = File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+                //GIF delays are in hundredths of a second
+                let delay = (100.0 / fps).round().max(1.0).min(f64::from(u16::MAX));
+                Ok(VideoEncoder::Gif(
+                    Encoder::new(BufWriter::new(file)),
+                    delay as u16,
+                ))
+            }
+        }
+    }
+
+    fn add_frame(&mut self, path: &str) -> Result<(), String> {
+        match self {
+            VideoEncoder::Ffmpeg(child) => {
+                let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+                let written = child.stdin.as_mut().unwrap().write_all(&bytes);
+                written.map_err(|e| {
+                    //ffmpeg closes its input when it fails, so wait for it to learn why
+                    //and to not leave it behind as a zombie
+                    drop(child.stdin.take());
+                    match child.wait() {
+                        Ok(status) => {
+                            format!("writing to ffmpeg: {}, ffmpeg exited with {}", e, status)
+                        }
+                        Err(_) => format!("writing to ffmpeg: {}", e),
+                    }
+                })
+            }
+            VideoEncoder::Gif(encoder, delay) => {
+                let image = image::open(path)
+                    .map_err(|e| format!("{}: {}", path, e))?
+                    .to_rgba();
+                let frame = image::Frame::from_parts(image, 0, 0, Ratio::from_integer(*delay));
+                encoder
+                    .encode_frames(vec![frame])
+                    .map_err(|e| format!("encoding gif: {}", e))
+            }
+        }
+    }
+
+    fn finish(self) -> Result<(), String> {
+        match self {
+            VideoEncoder::Ffmpeg(mut child) => {
+                //Closing stdin ends the input
+                drop(child.stdin.take());
+                let status = child.wait().map_err(|e| format!("ffmpeg: {}", e))?;
+                if status.success() {
+                    Ok(())
+                } else {
+                    Err(format!("ffmpeg exited with {}", status))
+                }
+            }
+            //The trailer is written when the encoder is dropped
+            VideoEncoder::Gif(..) => Ok(()),
+        }
+    }
+}
+
+///Renders every frame of the sequence, feeding each one to the video output if there is one
+///Each frame is rendered under a temporary name and renamed once complete, so an
+///interrupted render leaves no partial frame to be skipped when it's resumed
+///on_event gets the frame number along with what happened to it
+pub fn render_sequence<F>(
+    tracer: &SimpleTracer,
+    worldfunc: F,
+    settings: &SequenceSettings,
+    on_event: &mut dyn FnMut(u32, &FrameEvent),
+) -> Result<(), String>
+where
+    F: Fn(f64) -> World + Sync + Send + Clone + 'static,
+{
+    let mut encoder = match &settings.video {
+        Some(video) => Some(VideoEncoder::new(video, settings.fps)?),
+        None => None,
+    };
+    for frame in settings.start_frame..=settings.end_frame {
+        let path = settings.get_frame_path(frame);
+        if settings.skip_existing && Path::new(&path).exists() {
+            on_event(frame, &FrameEvent::Skipped { path: &path });
+        } else {
+            let partial_path = get_partial_path(&path);
+            let (worldfunc, time) = (Box::new(worldfunc.clone()), settings.get_frame_time(frame));
+            let on_render_event =
+                &mut |event: &RenderEvent| on_event(frame, &FrameEvent::Render(event));
+            let film = match &settings.budget {
+                Some(budget) => tracer.render_progressive(
+                    worldfunc,
+                    &partial_path,
+                    time,
+                    budget,
+                    on_render_event,
+                ),
+                None => tracer.render(worldfunc, &partial_path, time, on_render_event),
+            }
+            .map_err(|e| format!("{}: {}", partial_path, e))?;
+            if let Some(pattern) = &settings.heat_map_pattern {
+                let heat_map_path = expand_pattern(pattern, frame);
+                film.to_heat_map()
+                    .save(&heat_map_path)
+                    .map_err(|e| format!("{}: {}", heat_map_path, e))?;
+            }
+            if let Some(pattern) = &settings.aov_pattern {
+                save_aovs(&film, &expand_pattern(pattern, frame))?;
+            }
+            if let Some(denoiser) = &settings.denoiser {
+                if settings.keep_raw {
+                    let raw_path = insert_before_extension(&path, "raw");
+                    film.save(&raw_path).map_err(|e| format!("{}: {}", raw_path, e))?;
+                }
+                denoiser
+                    .denoise(&film)?
+                    .save(&partial_path)
+                    .map_err(|e| format!("{}: {}", partial_path, e))?;
+            }
+            std::fs::rename(&partial_path, &path).map_err(|e| format!("{}: {}", path, e))?;
+            on_event(frame, &FrameEvent::Completed);
+        }
+        if let Some(encoder) = encoder.as_mut() {
+            encoder.add_frame(&path)?;
+        }
+    }
+    match encoder {
+        Some(encoder) => encoder.finish(),
+        None => Ok(()),
+    }
+}
+
+///Saves the AOVs of a frame as described for `SequenceSettings::aov_pattern`
+fn save_aovs(film: &Film, path: &str) -> Result<(), String> {
+    let aovs = film
+        .get_aovs()
+        .ok_or("saving AOVs needs a tracer which records them")?;
+    if path.to_ascii_lowercase().ends_with(".exr") {
+        return film.save_exr(path).map_err(|e| format!("{}: {}", path, e));
+    }
+    for aov in Aov::ALL.iter() {
+        let aov_path = insert_before_extension(path, aov.get_name());
+        aovs.save(*aov, &aov_path)
+            .map_err(|e| format!("{}: {}", aov_path, e))?;
+    }
+    Ok(())
+}
+
+///"img0001.jpeg" becomes "img0001.partial.jpeg", keeping the extension the format is picked by
+fn get_partial_path(path: &str) -> String {
+    insert_before_extension(path, "partial")
+}
+
+///"img0001.jpeg" and "depth" give "img0001.depth.jpeg"
+fn insert_before_extension(path: &str, name: &str) -> String {
+    let path = Path::new(path);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    let name = match path.extension().and_then(|e| e.to_str()) {
+        Some(extension) => format!("{}.{}.{}", stem, name, extension),
+        None => format!("{}.{}", stem, name),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
@@ -0,0 +1,184 @@
+use super::{Frame, K_EPSILON};
+use crate::graphics::*;
+use std::rc::Rc;
+
+pub struct AxisAlignedBox {
+    ///Corner with the smallest coordinates
+    min: Point3D,
+    ///Corner with the largest coordinates
+    max: Point3D,
+    material: Rc<dyn Material>,
+}
+
+impl AxisAlignedBox {
+    ///The two opposite corners can be given in any order
+    pub fn new(a: Point3D, b: Point3D, material: Rc<dyn Material>) -> Self {
+        let bbox = BoundingBox::new(a, b);
+        AxisAlignedBox {
+            min: *bbox.get_min(),
+            max: *bbox.get_max(),
+            material,
+        }
+    }
+}
+
+impl GeometricObject for AxisAlignedBox {
+    fn check_hit(&self, ray: &Ray) -> Option<HitInfo> {
+        let (t, normal, (u, v)) = hit_slabs(&self.min, &self.max, ray)?;
+        Some(HitInfo::new(t, Normal3D::from(normal), ray.get_point_at(t)).with_uv(u, v))
+    }
+
+    fn get_material(&self) -> &dyn Material {
+        self.material.as_ref()
+    }
+
+    fn get_bounding_box(&self) -> Option<BoundingBox> {
+        Some(BoundingBox::new(self.min, self.max))
+    }
+
+    fn get_intervals(&self, ray: &Ray) -> Option<Vec<Interval>> {
+        Some(
+            slab_interval(&self.min, &self.max, ray)
+                .map(|interval| {
+                    to_interval(interval, |(t, normal, (u, v))| {
+                        HitInfo::new(t, Normal3D::from(normal), ray.get_point_at(t)).with_uv(u, v)
+                    })
+                })
+                .into_iter()
+                .collect(),
+        )
+    }
+}
+
+///Box rotated to an arbitrary orthonormal frame
+pub struct OrientedBox {
+    ///Frame centered on the box with the box's edges along its axes
+    frame: Frame,
+    ///Half of the box's size along each local axis
+    half_extents: Vector3D,
+    material: Rc<dyn Material>,
+}
+
+impl OrientedBox {
+    ///x_axis and y_axis need not be normalised, y_axis is made perpendicular to x_axis
+    pub fn new(
+        c: Point3D,
+        half_extents: Vector3D,
+        x_axis: Vector3D,
+        y_axis: Vector3D,
+        material: Rc<dyn Material>,
+    ) -> Self {
+        OrientedBox {
+            frame: Frame::from_axes(c, x_axis, y_axis),
+            half_extents,
+            material,
+        }
+    }
+
+    fn get_local_box(&self) -> BoundingBox {
+        let h = self.half_extents;
+        BoundingBox::new(
+            Point3D::new(-h.x(), -h.y(), -h.z()),
+            Point3D::new(h.x(), h.y(), h.z()),
+        )
+    }
+}
+
+impl GeometricObject for OrientedBox {
+    fn check_hit(&self, ray: &Ray) -> Option<HitInfo> {
+        let local = self.get_local_box();
+        let (t, normal, (u, v)) =
+            hit_slabs(local.get_min(), local.get_max(), &self.frame.to_local_ray(ray))?;
+        Some(
+            HitInfo::new(t, self.frame.to_world_normal(normal), ray.get_point_at(t))
+                .with_uv(u, v),
+        )
+    }
+
+    fn get_material(&self) -> &dyn Material {
+        self.material.as_ref()
+    }
+
+    fn get_bounding_box(&self) -> Option<BoundingBox> {
+        Some(self.frame.to_world_bounding_box(&self.get_local_box()))
+    }
+
+    fn get_intervals(&self, ray: &Ray) -> Option<Vec<Interval>> {
+        let local = self.get_local_box();
+        Some(
+            slab_interval(local.get_min(), local.get_max(), &self.frame.to_local_ray(ray))
+                .map(|interval| {
+                    to_interval(interval, |(t, normal, (u, v))| {
+                        HitInfo::new(t, self.frame.to_world_normal(normal), ray.get_point_at(t))
+                            .with_uv(u, v)
+                    })
+                })
+                .into_iter()
+                .collect(),
+        )
+    }
+}
+
+fn unit_vector(axis: usize) -> Vector3D {
+    match axis {
+        0 => Vector3D::new(1.0, 0.0, 0.0),
+        1 => Vector3D::new(0.0, 1.0, 0.0),
+        _ => Vector3D::new(0.0, 0.0, 1.0),
+    }
+}
+
+///Where a ray crosses the surface of a box: t, outward normal and uv on the face
+type SlabHit = (f64, Vector3D, (f64, f64));
+
+///Slab intersection of a ray with the box spanned by min and max
+///returns where the ray enters and exits the box, which may be behind the ray origin
+fn slab_interval(min: &Point3D, max: &Point3D, ray: &Ray) -> Option<(SlabHit, SlabHit)> {
+    let (mut t_near, mut near_axis) = (f64::NEG_INFINITY, 0);
+    let (mut t_far, mut far_axis) = (f64::INFINITY, 0);
+    for axis in 0..3 {
+        let inverse = 1.0 / ray.d.axis(axis);
+        let mut t0 = (min.axis(axis) - ray.o.axis(axis)) * inverse;
+        let mut t1 = (max.axis(axis) - ray.o.axis(axis)) * inverse;
+        if inverse < 0.0 {
+            std::mem::swap(&mut t0, &mut t1);
+        }
+        if t0 > t_near {
+            t_near = t0;
+            near_axis = axis;
+        }
+        if t1 < t_far {
+            t_far = t1;
+            far_axis = axis;
+        }
+    }
+    if t_near >= t_far {
+        return None;
+    }
+    //The ray enters through a face whose normal opposes it and leaves through one along it
+    let face_hit = |t: f64, axis: usize, side: f64| {
+        let normal = unit_vector(axis) * (side * ray.d.axis(axis).signum());
+        let hitpoint = ray.get_point_at(t);
+        let (u_axis, v_axis) = ((axis + 1) % 3, (axis + 2) % 3);
+        let u = (hitpoint.axis(u_axis) - min.axis(u_axis)) / (max.axis(u_axis) - min.axis(u_axis));
+        let v = (hitpoint.axis(v_axis) - min.axis(v_axis)) / (max.axis(v_axis) - min.axis(v_axis));
+        (t, normal, (u, v))
+    };
+    Some((face_hit(t_near, near_axis, -1.0), face_hit(t_far, far_axis, 1.0)))
+}
+
+///First crossing of the box's surface in front of the ray origin
+fn hit_slabs(min: &Point3D, max: &Point3D, ray: &Ray) -> Option<SlabHit> {
+    let (enter, exit) = slab_interval(min, max, ray)?;
+    if enter.0 > K_EPSILON {
+        Some(enter)
+    } else if exit.0 > K_EPSILON {
+        //Ray starts inside the box
+        Some(exit)
+    } else {
+        None
+    }
+}
+
+fn to_interval((enter, exit): (SlabHit, SlabHit), to_hitinfo: impl Fn(SlabHit) -> HitInfo) -> Interval {
+    Interval::new(to_hitinfo(enter), to_hitinfo(exit))
+}
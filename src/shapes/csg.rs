@@ -0,0 +1,147 @@
+use super::K_EPSILON;
+use crate::graphics::*;
+use std::rc::Rc;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    ///Left with right carved out of it
+    Difference,
+}
+
+impl CsgOperation {
+    fn is_inside(self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            CsgOperation::Union => in_left || in_right,
+            CsgOperation::Intersection => in_left && in_right,
+            CsgOperation::Difference => in_left && !in_right,
+        }
+    }
+}
+
+///Boolean combination of two closed objects, which can themselves be Csg nodes
+///Both children must report their intervals, objects which don't are treated as empty
+pub struct Csg {
+    operation: CsgOperation,
+    left: Box<dyn GeometricObject>,
+    right: Box<dyn GeometricObject>,
+    ///Material of the whole object, that of left unless set
+    material: Option<Rc<dyn Material>>,
+}
+
+impl Csg {
+    pub fn new(
+        operation: CsgOperation,
+        left: Box<dyn GeometricObject>,
+        right: Box<dyn GeometricObject>,
+    ) -> Self {
+        Csg {
+            operation,
+            left,
+            right,
+            material: None,
+        }
+    }
+
+    pub fn union(left: Box<dyn GeometricObject>, right: Box<dyn GeometricObject>) -> Self {
+        Csg::new(CsgOperation::Union, left, right)
+    }
+
+    pub fn intersection(left: Box<dyn GeometricObject>, right: Box<dyn GeometricObject>) -> Self {
+        Csg::new(CsgOperation::Intersection, left, right)
+    }
+
+    pub fn difference(left: Box<dyn GeometricObject>, right: Box<dyn GeometricObject>) -> Self {
+        Csg::new(CsgOperation::Difference, left, right)
+    }
+
+    pub fn with_material(mut self, material: Rc<dyn Material>) -> Self {
+        self.material = Some(material);
+        self
+    }
+}
+
+impl GeometricObject for Csg {
+    fn check_hit(&self, ray: &Ray) -> Option<HitInfo> {
+        self.get_intervals(ray)?
+            .iter()
+            .find_map(|interval| interval.get_first_hit(K_EPSILON))
+            .cloned()
+    }
+
+    fn get_material(&self) -> &dyn Material {
+        match &self.material {
+            Some(material) => material.as_ref(),
+            None => self.left.get_material(),
+        }
+    }
+
+    fn get_bounding_box(&self) -> Option<BoundingBox> {
+        let (left, right) = (self.left.get_bounding_box(), self.right.get_bounding_box());
+        match self.operation {
+            CsgOperation::Union => Some(left?.union(&right?)),
+            //The result can't be larger than either child, left is as good a bound as any
+            CsgOperation::Intersection => left.or(right),
+            CsgOperation::Difference => left,
+        }
+    }
+
+    fn get_intervals(&self, ray: &Ray) -> Option<Vec<Interval>> {
+        let left = self.left.get_intervals(ray).unwrap_or_default();
+        let right = self.right.get_intervals(ray).unwrap_or_default();
+
+        //Walk along the ray through every boundary of both children in order,
+        //the result starts and ends wherever the combined inside-ness changes
+        //Intervals with a NaN bound, from rays a child's maths can't handle, are left out
+        //whole so the enters and exits stay paired
+        let is_valid = |interval: &&Interval| {
+            !interval.get_enter().get_tmin().is_nan() && !interval.get_exit().get_tmin().is_nan()
+        };
+        let mut events: Vec<(&HitInfo, bool)> = Vec::with_capacity(2 * (left.len() + right.len()));
+        for interval in left.iter().filter(is_valid) {
+            events.push((interval.get_enter(), true));
+            events.push((interval.get_exit(), true));
+        }
+        for interval in right.iter().filter(is_valid) {
+            events.push((interval.get_enter(), false));
+            events.push((interval.get_exit(), false));
+        }
+        events.sort_by(|a, b| a.0.get_tmin().total_cmp(&b.0.get_tmin()));
+
+        let (mut in_left, mut in_right) = (false, false);
+        let mut enter: Option<HitInfo> = None;
+        let mut intervals = Vec::new();
+        for (hitinfo, from_left) in events {
+            let was_inside = self.operation.is_inside(in_left, in_right);
+            if from_left {
+                in_left = !in_left;
+            } else {
+                in_right = !in_right;
+            }
+            let is_inside = self.operation.is_inside(in_left, in_right);
+            if was_inside == is_inside {
+                continue;
+            }
+
+            //Surfaces of the carved out object face into the result
+            let hitinfo = if !from_left && self.operation == CsgOperation::Difference {
+                let (u, v) = hitinfo.get_uv();
+                HitInfo::new(
+                    hitinfo.get_tmin(),
+                    *hitinfo.get_normal() * -1.0,
+                    *hitinfo.get_hitpoint(),
+                )
+                .with_uv(u, v)
+            } else {
+                hitinfo.clone()
+            };
+            if is_inside {
+                enter = Some(hitinfo);
+            } else if let Some(enter) = enter.take() {
+                intervals.push(Interval::new(enter, hitinfo));
+            }
+        }
+        Some(intervals)
+    }
+}
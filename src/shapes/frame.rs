@@ -0,0 +1,75 @@
+use crate::graphics::*;
+
+///Orthonormal frame in which a shape is defined in its simplest form
+pub struct Frame {
+    origin: Point3D,
+    ///Normalised local x, y and z axes
+    axes: [Vector3D; 3],
+}
+
+impl Frame {
+    ///Frame with `axis` as the local y axis, x and z are picked arbitrarily
+    pub fn new(origin: Point3D, axis: Normal3D) -> Self {
+        let y = Vector3D::from(axis.normalize());
+        let helper = if y.x().abs() > 0.9 {
+            Vector3D::new(0.0, 0.0, 1.0)
+        } else {
+            Vector3D::new(1.0, 0.0, 0.0)
+        };
+        let x = (y ^ helper).normalize();
+        Frame {
+            origin,
+            axes: [x, y, x ^ y],
+        }
+    }
+
+    ///x_axis and y_axis need not be normalised, y_axis is made perpendicular to x_axis
+    pub fn from_axes(origin: Point3D, x_axis: Vector3D, y_axis: Vector3D) -> Self {
+        let x = x_axis.normalize();
+        let y = (y_axis - x * (y_axis * x)).normalize();
+        Frame {
+            origin,
+            axes: [x, y, x ^ y],
+        }
+    }
+
+    pub fn to_local_point(&self, p: Point3D) -> Point3D {
+        let v = p - self.origin;
+        Point3D::new(v * self.axes[0], v * self.axes[1], v * self.axes[2])
+    }
+
+    pub fn to_local_vector(&self, v: Vector3D) -> Vector3D {
+        Vector3D::new(v * self.axes[0], v * self.axes[1], v * self.axes[2])
+    }
+
+    ///The frame is orthonormal so t is the same for the local and the world ray
+    pub fn to_local_ray(&self, ray: &Ray) -> Ray {
+        Ray::with_time(
+            self.to_local_point(ray.o),
+            self.to_local_vector(ray.d),
+            ray.time,
+        )
+    }
+
+    pub fn to_world_vector(&self, v: Vector3D) -> Vector3D {
+        self.axes[0] * v.x() + self.axes[1] * v.y() + self.axes[2] * v.z()
+    }
+
+    pub fn to_world_normal(&self, n: Vector3D) -> Normal3D {
+        Normal3D::from(self.to_world_vector(n)).normalize()
+    }
+
+    pub fn to_world_point(&self, p: Point3D) -> Point3D {
+        self.origin + self.to_world_vector(p - Point3D::origin())
+    }
+
+    ///World space box enclosing a box given in local coordinates
+    pub fn to_world_bounding_box(&self, local: &BoundingBox) -> BoundingBox {
+        let corners: Vec<Point3D> = local
+            .get_corners()
+            .iter()
+            .map(|corner| self.to_world_point(*corner))
+            .collect();
+        BoundingBox::from_points(&corners).unwrap()
+    }
+}
@@ -0,0 +1,254 @@
+use super::K_EPSILON;
+use crate::graphics::*;
+use std::rc::Rc;
+
+///Terrain given by heights sampled on a regular grid in the xz plane
+///Each grid cell is split into two triangles, shaded with interpolated vertex normals
+pub struct Heightfield {
+    ///Heights in world units, row by row along z
+    heights: Vec<f64>,
+    ///Number of samples along x
+    nx: usize,
+    ///Number of samples along z
+    nz: usize,
+    ///Position of the first sample at height 0
+    corner: Point3D,
+    ///Distance between samples along x
+    cell_x: f64,
+    ///Distance between samples along z
+    cell_z: f64,
+    ///Smooth normals at each sample
+    normals: Vec<Vector3D>,
+    bounds: BoundingBox,
+    material: Rc<dyn Material>,
+}
+
+impl Heightfield {
+    ///heights-nx*nz samples from 0 to 1 row by row along z, scaled by size.y()
+    ///corner-where the first sample lies when its height is 0
+    ///size-extent of the grid along x and z, and the height of a sample of 1
+    pub fn new(
+        heights: &[f64],
+        nx: usize,
+        nz: usize,
+        corner: Point3D,
+        size: Vector3D,
+        material: Rc<dyn Material>,
+    ) -> Self {
+        assert!(nx >= 2 && nz >= 2 && heights.len() == nx * nz);
+        let heights: Vec<f64> = heights.iter().map(|h| h * size.y()).collect();
+        let (cell_x, cell_z) = (size.x() / (nx - 1) as f64, size.z() / (nz - 1) as f64);
+
+        //Central differences, one sided at the edges
+        let height_at = |i: usize, j: usize| heights[j * nx + i];
+        let mut normals = Vec::with_capacity(nx * nz);
+        for j in 0..nz {
+            for i in 0..nx {
+                let (i0, i1) = (i.saturating_sub(1), (i + 1).min(nx - 1));
+                let (j0, j1) = (j.saturating_sub(1), (j + 1).min(nz - 1));
+                let dh_dx = (height_at(i1, j) - height_at(i0, j)) / ((i1 - i0) as f64 * cell_x);
+                let dh_dz = (height_at(i, j1) - height_at(i, j0)) / ((j1 - j0) as f64 * cell_z);
+                normals.push(Vector3D::new(-dh_dx, 1.0, -dh_dz).normalize());
+            }
+        }
+
+        let (min_h, max_h) = heights
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), h| {
+                (min.min(*h), max.max(*h))
+            });
+        let bounds = BoundingBox::new(
+            corner + Vector3D::new(0.0, min_h, 0.0),
+            corner + Vector3D::new(size.x(), max_h, size.z()),
+        )
+        .pad(K_EPSILON);
+
+        Heightfield {
+            heights,
+            nx,
+            nz,
+            corner,
+            cell_x,
+            cell_z,
+            normals,
+            bounds,
+            material,
+        }
+    }
+
+    ///Heights from the brightness of a grayscale image, with white as size.y()
+    ///The image's x runs along x and its y along z
+    ///Images are read with 8 bits per channel, 16 bit heightmaps lose their low bits
+    ///and the terrain comes out in 256 steps
+    ///Images less than 2 pixels wide or tall give a DimensionError
+    pub fn from_image(
+        path: &str,
+        corner: Point3D,
+        size: Vector3D,
+        material: Rc<dyn Material>,
+    ) -> image::ImageResult<Self> {
+        let image = image::open(path)?.to_luma();
+        let (width, height) = image.dimensions();
+        if width < 2 || height < 2 {
+            return Err(image::ImageError::DimensionError);
+        }
+        let heights: Vec<f64> = image.pixels().map(|p| f64::from(p.data[0]) / 255.0).collect();
+        Ok(Heightfield::new(
+            &heights,
+            width as usize,
+            height as usize,
+            corner,
+            size,
+            material,
+        ))
+    }
+
+    fn get_vertex(&self, i: usize, j: usize) -> Point3D {
+        self.corner
+            + Vector3D::new(
+                i as f64 * self.cell_x,
+                self.heights[j * self.nx + i],
+                j as f64 * self.cell_z,
+            )
+    }
+
+    ///Closest hit with the two triangles of cell (i,j)
+    fn hit_cell(&self, ray: &Ray, i: usize, j: usize) -> Option<HitInfo> {
+        let corners = [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)];
+        let mut closest: Option<HitInfo> = None;
+        for triangle in &[[0, 1, 2], [0, 2, 3]] {
+            let [a, b, c] = [
+                corners[triangle[0]],
+                corners[triangle[1]],
+                corners[triangle[2]],
+            ];
+            let hit = hit_triangle(
+                ray,
+                self.get_vertex(a.0, a.1),
+                self.get_vertex(b.0, b.1),
+                self.get_vertex(c.0, c.1),
+            );
+            if let Some((t, beta, gamma)) = hit {
+                if closest.as_ref().is_some_and(|hit| hit.get_tmin() <= t) {
+                    continue;
+                }
+                let normal_at = |(i, j): (usize, usize)| self.normals[j * self.nx + i];
+                let normal =
+                    normal_at(a) * (1.0 - beta - gamma) + normal_at(b) * beta + normal_at(c) * gamma;
+                let hitpoint = ray.get_point_at(t);
+                let u = (hitpoint.x() - self.corner.x()) / (self.cell_x * (self.nx - 1) as f64);
+                let v = (hitpoint.z() - self.corner.z()) / (self.cell_z * (self.nz - 1) as f64);
+                closest = Some(
+                    HitInfo::new(t, Normal3D::from(normal).normalize(), hitpoint).with_uv(u, v),
+                );
+            }
+        }
+        closest
+    }
+}
+
+///Moller-Trumbore ray triangle intersection, returns t and the barycentric
+///coordinates of the hit relative to b and c
+fn hit_triangle(ray: &Ray, a: Point3D, b: Point3D, c: Point3D) -> Option<(f64, f64, f64)> {
+    let (edge1, edge2) = (b - a, c - a);
+    let p = ray.d ^ edge2;
+    let determinant = edge1 * p;
+    if determinant.abs() < 1e-12 {
+        //Ray is parallel to the triangle
+        return None;
+    }
+    let inverse = 1.0 / determinant;
+    let s = ray.o - a;
+    let beta = (s * p) * inverse;
+    if !(0.0..=1.0).contains(&beta) {
+        return None;
+    }
+    let q = s ^ edge1;
+    let gamma = (ray.d * q) * inverse;
+    if gamma < 0.0 || beta + gamma > 1.0 {
+        return None;
+    }
+    let t = (edge2 * q) * inverse;
+    if t > K_EPSILON {
+        Some((t, beta, gamma))
+    } else {
+        None
+    }
+}
+
+impl GeometricObject for Heightfield {
+    ///Walks the cells under the ray in order with a 2D DDA, so only the cells the
+    ///ray passes over are tested
+    fn check_hit(&self, ray: &Ray) -> Option<HitInfo> {
+        let (t_near, t_far) = self.bounds.hit(ray)?;
+        if t_far <= 0.0 {
+            return None;
+        }
+        let t_start = t_near.max(0.0);
+        let start = ray.get_point_at(t_start);
+        let to_cell = |offset: f64, cell: f64, max: usize| {
+            ((offset / cell).floor().max(0.0) as usize).min(max - 2)
+        };
+        let mut i = to_cell(start.x() - self.corner.x(), self.cell_x, self.nx);
+        let mut j = to_cell(start.z() - self.corner.z(), self.cell_z, self.nz);
+
+        //t at which the ray crosses the next cell boundary along an axis and the t between crossings
+        let crossing = |index: usize, d: f64, o: f64, min: f64, cell: f64| {
+            if d > 0.0 {
+                ((min + (index + 1) as f64 * cell - o) / d, cell / d)
+            } else if d < 0.0 {
+                ((min + index as f64 * cell - o) / d, -cell / d)
+            } else {
+                (f64::INFINITY, f64::INFINITY)
+            }
+        };
+        let (mut t_next_x, t_delta_x) =
+            crossing(i, ray.d.x(), ray.o.x(), self.corner.x(), self.cell_x);
+        let (mut t_next_z, t_delta_z) =
+            crossing(j, ray.d.z(), ray.o.z(), self.corner.z(), self.cell_z);
+
+        loop {
+            if let Some(hitinfo) = self.hit_cell(ray, i, j) {
+                return Some(hitinfo);
+            }
+            if t_next_x.min(t_next_z) > t_far {
+                return None;
+            }
+            if t_next_x < t_next_z {
+                if ray.d.x() > 0.0 {
+                    i += 1;
+                    if i > self.nx - 2 {
+                        return None;
+                    }
+                } else {
+                    if i == 0 {
+                        return None;
+                    }
+                    i -= 1;
+                }
+                t_next_x += t_delta_x;
+            } else {
+                if ray.d.z() > 0.0 {
+                    j += 1;
+                    if j > self.nz - 2 {
+                        return None;
+                    }
+                } else {
+                    if j == 0 {
+                        return None;
+                    }
+                    j -= 1;
+                }
+                t_next_z += t_delta_z;
+            }
+        }
+    }
+
+    fn get_material(&self) -> &dyn Material {
+        self.material.as_ref()
+    }
+
+    fn get_bounding_box(&self) -> Option<BoundingBox> {
+        Some(self.bounds)
+    }
+}
@@ -0,0 +1,132 @@
+use crate::graphics::*;
+use std::rc::Rc;
+
+///Places a shared object in the world with its own transform and optionally its own material
+pub struct Instance {
+    object: Rc<dyn GeometricObject>,
+    ///Object space to world space, moving over the shutter interval for motion blur
+    transform: AnimatedTransform,
+    ///Replaces the material of the object when present
+    material: Option<Rc<dyn Material>>,
+    ///World space box around the moving object, rays which miss it skip the costly
+    ///interpolation of the transform
+    bounds: Option<BoundingBox>,
+}
+
+impl Instance {
+    pub fn new(object: Rc<dyn GeometricObject>, transform: Transform) -> Self {
+        Instance::animated(object, AnimatedTransform::fixed(transform))
+    }
+
+    ///Instance whose transform depends on the time of the ray
+    pub fn animated(object: Rc<dyn GeometricObject>, transform: AnimatedTransform) -> Self {
+        let bounds = if transform.is_animated() {
+            object
+                .get_bounding_box()
+                .map(|bbox| transform.transform_bounding_box(&bbox))
+        } else {
+            None
+        };
+        Instance {
+            object,
+            transform,
+            material: None,
+            bounds,
+        }
+    }
+
+    pub fn with_material(mut self, material: Rc<dyn Material>) -> Self {
+        self.material = Some(material);
+        self
+    }
+
+    pub fn get_transform(&self) -> &AnimatedTransform {
+        &self.transform
+    }
+
+    ///Brings a hit found with the object space ray back to world space
+    fn to_world_hit(transform: &Transform, ray: &Ray, hitinfo: &HitInfo) -> HitInfo {
+        let t = hitinfo.get_tmin();
+        let (u, v) = hitinfo.get_uv();
+        HitInfo::new(
+            t,
+            transform.transform_normal(hitinfo.get_normal()).normalize(),
+            ray.get_point_at(t),
+        )
+        .with_uv(u, v)
+    }
+}
+
+impl GeometricObject for Instance {
+    fn check_hit(&self, ray: &Ray) -> Option<HitInfo> {
+        //The object space ray is not normalised so t carries over unchanged
+        match self.transform.get_fixed() {
+            Some(transform) if transform.is_identity() => self.object.check_hit(ray),
+            Some(transform) => {
+                let hitinfo = self
+                    .object
+                    .check_hit(&transform.inverse_transform_ray(ray))?;
+                Some(Instance::to_world_hit(transform, ray, &hitinfo))
+            }
+            None => {
+                if let Some(bounds) = &self.bounds {
+                    bounds.hit(ray).filter(|&(_, t_far)| t_far > 0.0)?;
+                }
+                let transform = self.transform.at(ray.time);
+                let hitinfo = self
+                    .object
+                    .check_hit(&transform.inverse_transform_ray(ray))?;
+                Some(Instance::to_world_hit(&transform, ray, &hitinfo))
+            }
+        }
+    }
+
+    fn get_material(&self) -> &dyn Material {
+        match &self.material {
+            Some(material) => material.as_ref(),
+            None => self.object.get_material(),
+        }
+    }
+
+    ///Only with a fixed transform, where a moving instance is depends on the time
+    fn sample_surface(&self, u: (f64, f64)) -> Option<(Point3D, Normal3D, f64)> {
+        let transform = self.transform.get_fixed()?;
+        let (point, normal, pdf) = self.object.sample_surface(u)?;
+        if transform.is_identity() {
+            return Some((point, normal, pdf));
+        }
+        //A patch of the surface grows by the determinant times the length of its
+        //transformed unit normal, spreading the density over the larger area
+        let normal = normal.normalize();
+        let world_normal = transform.transform_normal(&normal);
+        let area_scale = transform.get_determinant().abs() * world_normal.magnitude_sqr().sqrt();
+        Some((
+            transform.transform_point(&point),
+            world_normal.normalize(),
+            pdf / area_scale,
+        ))
+    }
+
+    fn get_bounding_box(&self) -> Option<BoundingBox> {
+        self.object
+            .get_bounding_box()
+            .map(|bbox| self.transform.transform_bounding_box(&bbox))
+    }
+
+    fn get_intervals(&self, ray: &Ray) -> Option<Vec<Interval>> {
+        let transform = self.transform.at(ray.time);
+        let object_ray = transform.inverse().transform_ray(ray);
+        let intervals = self.object.get_intervals(&object_ray)?;
+        Some(
+            intervals
+                .iter()
+                .map(|interval| {
+                    Interval::new(
+                        Instance::to_world_hit(&transform, ray, interval.get_enter()),
+                        Instance::to_world_hit(&transform, ray, interval.get_exit()),
+                    )
+                })
+                .collect(),
+        )
+    }
+}
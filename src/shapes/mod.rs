@@ -0,0 +1,291 @@
+mod boxes;
+mod csg;
+mod frame;
+mod heightfield;
+mod instance;
+mod polynomial;
+mod quadrics;
+mod rectangle;
+mod sdf;
+mod torus;
+
+pub use self::boxes::*;
+pub use self::csg::*;
+use self::frame::Frame;
+pub use self::heightfield::*;
+pub use self::instance::*;
+pub use self::quadrics::*;
+pub use self::rectangle::*;
+pub use self::sdf::*;
+pub use self::torus::*;
+use crate::graphics::*;
+use std::rc::Rc;
+const K_EPSILON: f64 = 0.00001;
+
+pub struct Plane {
+    ///A point on the plane
+    a: Point3D,
+    ///A normalised Normal to the plane
+    n: Normal3D,
+    material: Rc<dyn Material>,
+}
+
+impl Plane {
+    pub fn new(a: Point3D, n: Normal3D, material: Rc<dyn Material>) -> Self {
+        Plane {
+            a,
+            n: n.normalize(),
+            material,
+        }
+    }
+}
+
+///Hit of a ray with the plane through a with the normalised normal n
+fn hit_plane(a: Point3D, n: Normal3D, ray: &Ray) -> Option<HitInfo> {
+    let denominator = ray.d * n;
+    if denominator == 0.0 {
+        //Ray is parallel to plane
+        return Option::None;
+    }
+    let t = ((a - ray.o) * n) / denominator; //See https://en.wikipedia.org/wiki/Line%E2%80%93plane_intersection
+    if t > K_EPSILON {
+        Some(HitInfo::new(t, n, ray.get_point_at(t)))
+    } else {
+        Option::None
+    }
+}
+
+impl GeometricObject for Plane {
+    fn check_hit(&self, ray: &Ray) -> Option<HitInfo> {
+        hit_plane(self.a, self.n, ray)
+    }
+
+    fn get_material(&self) ->  &dyn Material{
+        self.material.as_ref()
+    }
+}
+
+pub struct Sphere {
+    ///Center of sphere
+    c: Point3D,
+    ///Radius of sphere
+    r: f64,
+    material: Rc<dyn Material>,
+}
+
+impl Sphere {
+    pub fn new(c: Point3D, r: f64, material: Rc<dyn Material>) -> Self {
+        Sphere { c, r, material }
+    }
+}
+
+impl GeometricObject for Sphere {
+    fn check_hit(&self, ray: &Ray) -> Option<HitInfo> {
+        hit_sphere(self.c, self.r, ray)
+    }
+
+    fn get_material(&self) ->  &dyn Material{
+        self.material.as_ref()
+    }
+
+    fn get_bounding_box(&self) -> Option<BoundingBox> {
+        Some(get_sphere_bounding_box(self.c, self.r))
+    }
+
+    fn get_intervals(&self, ray: &Ray) -> Option<Vec<Interval>> {
+        get_sphere_intervals(self.c, self.r, ray)
+    }
+
+    ///Uniform over the whole sphere
+    fn sample_surface(&self, u: (f64, f64)) -> Option<(Point3D, Normal3D, f64)> {
+        let direction = uniform_sphere(u);
+        let area = 4.0 * std::f64::consts::PI * self.r * self.r;
+        Some((
+            self.c + direction * self.r,
+            Normal3D::from(direction),
+            1.0 / area,
+        ))
+    }
+}
+
+///Nearest hit of a ray with the sphere of the given center and radius
+fn hit_sphere(center: Point3D, radius: f64, ray: &Ray) -> Option<HitInfo> {
+    let distance = ray.o - center;
+    let a = ray.d * ray.d;
+    let b = 2.0 * (distance * ray.d);
+    let c = distance * distance - radius * radius;
+
+    let discriminant = b * b - 4.0 * a * c;
+
+    if discriminant < 0.0 {
+        return None;
+    } else {
+        let t1 = (-b - discriminant.sqrt()) / (2.0 * a); //smaller
+
+        if t1 > K_EPSILON {
+            let hitpoint = ray.get_point_at(t1);
+            let normal = hitpoint - center;
+            //dbg!(normal);
+            return Some(HitInfo::new(
+                t1,
+                Normal3D::from(normal).normalize(),
+                hitpoint,
+            ));
+        }
+        let t2 = (-b + discriminant.sqrt()) / (2.0 * a); //larger
+
+        if t2 > K_EPSILON {
+            let hitpoint = ray.get_point_at(t2);
+            let normal = hitpoint - center;
+            return Some(HitInfo::new(
+                t2,
+                Normal3D::from(normal).normalize(),
+                hitpoint,
+            ));
+        } else {
+            //Both t1 and t2 are negative or 0
+            return None;
+        }
+    }
+}
+
+fn get_sphere_bounding_box(center: Point3D, radius: f64) -> BoundingBox {
+    let extent = Vector3D::new(radius, radius, radius);
+    BoundingBox::new(center + -extent, center + extent)
+}
+
+///Where a ray enters and leaves the sphere of the given center and radius
+fn get_sphere_intervals(center: Point3D, radius: f64, ray: &Ray) -> Option<Vec<Interval>> {
+    let distance = ray.o - center;
+    let a = ray.d * ray.d;
+    let b = 2.0 * (distance * ray.d);
+    let c = distance * distance - radius * radius;
+
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant <= 0.0 {
+        //A ray grazing the sphere doesn't pass through any volume
+        return Some(Vec::new());
+    }
+    let hit_at = |t: f64| {
+        let hitpoint = ray.get_point_at(t);
+        HitInfo::new(t, Normal3D::from(hitpoint - center).normalize(), hitpoint)
+    };
+    let t1 = (-b - discriminant.sqrt()) / (2.0 * a);
+    let t2 = (-b + discriminant.sqrt()) / (2.0 * a);
+    Some(vec![Interval::new(hit_at(t1), hit_at(t2))])
+}
+
+///Sphere whose center moves along a straight line, for motion blur
+pub struct MovingSphere {
+    ///Center at start_time
+    c0: Point3D,
+    ///Center at end_time
+    c1: Point3D,
+    start_time: f64,
+    end_time: f64,
+    ///Radius of sphere
+    r: f64,
+    material: Rc<dyn Material>,
+}
+
+impl MovingSphere {
+    pub fn new(
+        c0: Point3D,
+        start_time: f64,
+        c1: Point3D,
+        end_time: f64,
+        r: f64,
+        material: Rc<dyn Material>,
+    ) -> Self {
+        MovingSphere {
+            c0,
+            c1,
+            start_time,
+            end_time,
+            r,
+            material,
+        }
+    }
+
+    ///The sphere stops at either end of the time interval
+    fn get_center_at(&self, time: f64) -> Point3D {
+        let u = if self.end_time > self.start_time {
+            ((time - self.start_time) / (self.end_time - self.start_time)).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        self.c0 + (self.c1 - self.c0) * u
+    }
+}
+
+impl GeometricObject for MovingSphere {
+    fn check_hit(&self, ray: &Ray) -> Option<HitInfo> {
+        hit_sphere(self.get_center_at(ray.time), self.r, ray)
+    }
+
+    fn get_material(&self) -> &dyn Material {
+        self.material.as_ref()
+    }
+
+    fn get_bounding_box(&self) -> Option<BoundingBox> {
+        let start = get_sphere_bounding_box(self.get_center_at(self.start_time), self.r);
+        let end = get_sphere_bounding_box(self.get_center_at(self.end_time), self.r);
+        Some(start.union(&end))
+    }
+
+    fn get_intervals(&self, ray: &Ray) -> Option<Vec<Interval>> {
+        get_sphere_intervals(self.get_center_at(ray.time), self.r, ray)
+    }
+}
+
+pub struct ThinDisc {
+    ///Center of the disc
+    c: Point3D,
+    ///A normalised Normal to the disc
+    n: Normal3D,
+    ///Radius of disk
+    r: f64,
+    material: Rc<dyn Material>,
+}
+
+impl ThinDisc {
+    pub fn new(c: Point3D, r: f64, n: Normal3D, material: Rc<dyn Material>) -> Self {
+        ThinDisc {
+            c,
+            n: n.normalize(),
+            r,
+            material,
+        }
+    }
+}
+
+impl GeometricObject for ThinDisc {
+    fn check_hit(&self, ray: &Ray) -> Option<HitInfo> {
+        match hit_plane(self.c, self.n, ray) {
+            Some(hitinfo) => {
+                let hitpoint = *hitinfo.get_hitpoint();
+                let distance_from_center_sqr = (hitpoint - self.c) * (hitpoint - self.c);
+                if distance_from_center_sqr <= self.r * self.r {
+                    Some(hitinfo)
+                } else {
+                    None
+                }
+            }
+            None => None,
+        }
+    }
+
+    fn get_material(&self) ->  &dyn Material{
+        self.material.as_ref()
+    }
+
+    fn get_bounding_box(&self) -> Option<BoundingBox> {
+        //Extent of a disc along an axis is r*sin of the angle between the axis and the normal
+        let extent = Vector3D::new(
+            self.r * (1.0 - self.n.x() * self.n.x()).max(0.0).sqrt(),
+            self.r * (1.0 - self.n.y() * self.n.y()).max(0.0).sqrt(),
+            self.r * (1.0 - self.n.z() * self.n.z()).max(0.0).sqrt(),
+        );
+        Some(BoundingBox::new(self.c + -extent, self.c + extent).pad(K_EPSILON))
+    }
+}
@@ -0,0 +1,150 @@
+//Closed form polynomial solvers used for ray-surface intersections
+//Cubic and quartic solvers after Jochen Schwarze, "Cubic and Quartic Roots", Graphics Gems I
+
+const EQN_EPS: f64 = 1e-9;
+
+fn is_zero(x: f64) -> bool {
+    x.abs() < EQN_EPS
+}
+
+///Real roots of a*x^2+b*x+c=0 in ascending order
+///A single root is returned twice when a is 0 or the roots coincide
+pub fn solve_quadratic(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
+    if a == 0.0 {
+        if b == 0.0 {
+            return None;
+        }
+        let t = -c / b;
+        return Some((t, t));
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    //Avoids the cancellation in -b+sqrt(discriminant) when b*b is much larger than 4ac
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    let (t1, t2) = if q == 0.0 {
+        (0.0, 0.0)
+    } else {
+        (q / a, c / q)
+    };
+    if t1 < t2 {
+        Some((t1, t2))
+    } else {
+        Some((t2, t1))
+    }
+}
+
+///Real roots of c[3]*x^3+c[2]*x^2+c[1]*x+c[0]=0, unordered
+fn solve_cubic(c: [f64; 4]) -> Vec<f64> {
+    //Normal form x^3+Ax^2+Bx+C=0
+    let a = c[2] / c[3];
+    let b = c[1] / c[3];
+    let c = c[0] / c[3];
+
+    //Substitute x=y-A/3 to eliminate the quadratic term: y^3+3py+2q=0
+    let sq_a = a * a;
+    let p = (-sq_a / 3.0 + b) / 3.0;
+    let q = (2.0 / 27.0 * a * sq_a - a * b / 3.0 + c) / 2.0;
+    let cb_p = p * p * p;
+    let d = q * q + cb_p;
+
+    let mut roots = if is_zero(d) {
+        if is_zero(q) {
+            //One triple root
+            vec![0.0]
+        } else {
+            //One single and one double root
+            let u = (-q).cbrt();
+            vec![2.0 * u, -u]
+        }
+    } else if d < 0.0 {
+        //Three real roots
+        let phi = (-q / (-cb_p).sqrt()).acos() / 3.0;
+        let t = 2.0 * (-p).sqrt();
+        vec![
+            t * phi.cos(),
+            -t * (phi + std::f64::consts::PI / 3.0).cos(),
+            -t * (phi - std::f64::consts::PI / 3.0).cos(),
+        ]
+    } else {
+        //One real root
+        let sqrt_d = d.sqrt();
+        vec![(sqrt_d - q).cbrt() - (sqrt_d + q).cbrt()]
+    };
+
+    for root in roots.iter_mut() {
+        *root -= a / 3.0;
+    }
+    roots
+}
+
+///Real roots of c[4]*x^4+c[3]*x^3+c[2]*x^2+c[1]*x+c[0]=0 in ascending order
+pub fn solve_quartic(c: [f64; 5]) -> Vec<f64> {
+    //Normal form x^4+Ax^3+Bx^2+Cx+D=0
+    let a = c[3] / c[4];
+    let b = c[2] / c[4];
+    let c_ = c[1] / c[4];
+    let d = c[0] / c[4];
+
+    //Substitute x=y-A/4 to eliminate the cubic term: y^4+py^2+qy+r=0
+    let sq_a = a * a;
+    let p = -3.0 / 8.0 * sq_a + b;
+    let q = sq_a * a / 8.0 - a * b / 2.0 + c_;
+    let r = -3.0 / 256.0 * sq_a * sq_a + sq_a * b / 16.0 - a * c_ / 4.0 + d;
+
+    let mut roots = if is_zero(r) {
+        //No absolute term: y(y^3+py+q)=0
+        let mut roots = solve_cubic([q, p, 0.0, 1.0]);
+        roots.push(0.0);
+        roots
+    } else {
+        //Solve the resolvent cubic and use one of its roots to split into two quadratics
+        let z = solve_cubic([r * p / 2.0 - q * q / 8.0, -r, -p / 2.0, 1.0])[0];
+
+        let u = z * z - r;
+        let v = 2.0 * z - p;
+        let u = if is_zero(u) {
+            0.0
+        } else if u > 0.0 {
+            u.sqrt()
+        } else {
+            return Vec::new();
+        };
+        let v = if is_zero(v) {
+            0.0
+        } else if v > 0.0 {
+            v.sqrt()
+        } else {
+            return Vec::new();
+        };
+
+        let mut roots = Vec::with_capacity(4);
+        let v_signed = if q < 0.0 { -v } else { v };
+        if let Some((t1, t2)) = solve_quadratic(1.0, v_signed, z - u) {
+            roots.push(t1);
+            roots.push(t2);
+        }
+        if let Some((t1, t2)) = solve_quadratic(1.0, -v_signed, z + u) {
+            roots.push(t1);
+            roots.push(t2);
+        }
+        roots
+    };
+
+    for root in roots.iter_mut() {
+        *root -= a / 4.0;
+        //The closed form loses precision, polish with a couple of Newton steps
+        for _ in 0..2 {
+            let x = *root;
+            let f = (((c[4] * x + c[3]) * x + c[2]) * x + c[1]) * x + c[0];
+            let df = ((4.0 * c[4] * x + 3.0 * c[3]) * x + 2.0 * c[2]) * x + c[1];
+            if df != 0.0 {
+                *root = x - f / df;
+            }
+        }
+    }
+    roots.retain(|root| root.is_finite());
+    roots.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    roots
+}
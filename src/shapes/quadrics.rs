@@ -0,0 +1,404 @@
+use super::polynomial::solve_quadratic;
+use super::{Frame, K_EPSILON};
+use crate::graphics::*;
+use std::f64::consts::PI;
+use std::rc::Rc;
+
+//All shapes here are defined in a local frame with their axis along local y
+
+///Intersection in the local frame of a shape: t, unnormalised normal and uv
+struct LocalHit {
+    t: f64,
+    normal: Vector3D,
+    uv: (f64, f64),
+}
+
+impl LocalHit {
+    ///Keeps whichever of the two hits is closer
+    fn closest(current: Option<LocalHit>, candidate: LocalHit) -> Option<LocalHit> {
+        match current {
+            Some(hit) if hit.t <= candidate.t => Some(hit),
+            _ => Some(candidate),
+        }
+    }
+
+    fn to_hitinfo(&self, frame: &Frame, ray: &Ray) -> HitInfo {
+        HitInfo::new(self.t, frame.to_world_normal(self.normal), ray.get_point_at(self.t))
+            .with_uv(self.uv.0, self.uv.1)
+    }
+}
+
+///Angle around the local y axis mapped to [0,1]
+fn azimuth(p: &Point3D) -> f64 {
+    p.z().atan2(p.x()) / (2.0 * PI) + 0.5
+}
+
+///First hit with the lateral surface of a quadric whose coefficients are a, b and c
+///between heights 0 and h, normal_at gives the outward normal at a local point
+fn hit_lateral_surface(
+    local_ray: &Ray,
+    (a, b, c): (f64, f64, f64),
+    h: f64,
+    normal_at: impl Fn(&Point3D) -> Vector3D,
+) -> Option<LocalHit> {
+    let (t1, t2) = solve_quadratic(a, b, c)?;
+    for &t in &[t1, t2] {
+        if t > K_EPSILON {
+            let p = local_ray.get_point_at(t);
+            if p.y() >= 0.0 && p.y() <= h {
+                return Some(LocalHit {
+                    t,
+                    normal: normal_at(&p),
+                    uv: (azimuth(&p), p.y() / h),
+                });
+            }
+        }
+    }
+    None
+}
+
+///Hit with the disc of radius r at height y facing along normal_y (1 or -1)
+fn hit_cap(local_ray: &Ray, y: f64, r: f64, normal_y: f64) -> Option<LocalHit> {
+    if local_ray.d.y() == 0.0 {
+        return None;
+    }
+    let t = (y - local_ray.o.y()) / local_ray.d.y();
+    if t <= K_EPSILON {
+        return None;
+    }
+    let p = local_ray.get_point_at(t);
+    if p.x() * p.x() + p.z() * p.z() <= r * r {
+        Some(LocalHit {
+            t,
+            normal: Vector3D::new(0.0, normal_y, 0.0),
+            uv: ((p.x() / r + 1.0) * 0.5, (p.z() / r + 1.0) * 0.5),
+        })
+    } else {
+        None
+    }
+}
+
+pub struct Cylinder {
+    ///Frame with the center of the base as origin and the axis as y
+    frame: Frame,
+    ///Radius of cylinder
+    r: f64,
+    ///Height along the axis
+    h: f64,
+    ///Whether the ends are closed by discs
+    capped: bool,
+    material: Rc<dyn Material>,
+}
+
+impl Cylinder {
+    ///base-center of the bottom end, axis-direction from the bottom to the top end
+    pub fn new(
+        base: Point3D,
+        axis: Normal3D,
+        r: f64,
+        h: f64,
+        capped: bool,
+        material: Rc<dyn Material>,
+    ) -> Self {
+        Cylinder {
+            frame: Frame::new(base, axis),
+            r,
+            h,
+            capped,
+            material,
+        }
+    }
+}
+
+impl GeometricObject for Cylinder {
+    fn check_hit(&self, ray: &Ray) -> Option<HitInfo> {
+        let local_ray = self.frame.to_local_ray(ray);
+        let (o, d) = (local_ray.o, local_ray.d);
+        let a = d.x() * d.x() + d.z() * d.z();
+        let b = 2.0 * (o.x() * d.x() + o.z() * d.z());
+        let c = o.x() * o.x() + o.z() * o.z() - self.r * self.r;
+
+        let mut closest = hit_lateral_surface(&local_ray, (a, b, c), self.h, |p| {
+            Vector3D::new(p.x(), 0.0, p.z())
+        });
+        if self.capped {
+            for &(y, normal_y) in &[(0.0, -1.0), (self.h, 1.0)] {
+                if let Some(hit) = hit_cap(&local_ray, y, self.r, normal_y) {
+                    closest = LocalHit::closest(closest, hit);
+                }
+            }
+        }
+        closest.map(|hit| hit.to_hitinfo(&self.frame, ray))
+    }
+
+    fn get_material(&self) -> &dyn Material {
+        self.material.as_ref()
+    }
+
+    fn get_bounding_box(&self) -> Option<BoundingBox> {
+        let local = BoundingBox::new(
+            Point3D::new(-self.r, 0.0, -self.r),
+            Point3D::new(self.r, self.h, self.r),
+        );
+        Some(self.frame.to_world_bounding_box(&local))
+    }
+
+    ///Only capped cylinders enclose a volume
+    fn get_intervals(&self, ray: &Ray) -> Option<Vec<Interval>> {
+        if !self.capped {
+            return None;
+        }
+        let local_ray = self.frame.to_local_ray(ray);
+        let (o, d) = (local_ray.o, local_ray.d);
+        let a = d.x() * d.x() + d.z() * d.z();
+        let b = 2.0 * (o.x() * d.x() + o.z() * d.z());
+        let c = o.x() * o.x() + o.z() * o.z() - self.r * self.r;
+
+        let side_hit = |t: f64| {
+            let p = local_ray.get_point_at(t);
+            LocalHit {
+                t,
+                normal: Vector3D::new(p.x(), 0.0, p.z()),
+                uv: (azimuth(&p), p.y() / self.h),
+            }
+        };
+        let cap_hit = |t: f64, normal_y: f64| {
+            let p = local_ray.get_point_at(t);
+            LocalHit {
+                t,
+                normal: Vector3D::new(0.0, normal_y, 0.0),
+                uv: ((p.x() / self.r + 1.0) * 0.5, (p.z() / self.r + 1.0) * 0.5),
+            }
+        };
+
+        //Part of the ray inside the infinite cylinder
+        let (mut enter, mut exit) = if a == 0.0 {
+            //Parallel to the axis, the caps bound the interval below
+            if c > 0.0 {
+                return Some(Vec::new());
+            }
+            (side_hit(f64::NEG_INFINITY), side_hit(f64::INFINITY))
+        } else {
+            match solve_quadratic(a, b, c) {
+                Some((t1, t2)) if t1 < t2 => (side_hit(t1), side_hit(t2)),
+                _ => return Some(Vec::new()),
+            }
+        };
+
+        //Clip it to the slab between the caps
+        if d.y() == 0.0 {
+            if o.y() < 0.0 || o.y() > self.h {
+                return Some(Vec::new());
+            }
+        } else {
+            let t_bottom = -o.y() / d.y();
+            let t_top = (self.h - o.y()) / d.y();
+            let (cap_enter, cap_exit) = if d.y() > 0.0 {
+                (cap_hit(t_bottom, -1.0), cap_hit(t_top, 1.0))
+            } else {
+                (cap_hit(t_top, 1.0), cap_hit(t_bottom, -1.0))
+            };
+            if cap_enter.t > enter.t {
+                enter = cap_enter;
+            }
+            if cap_exit.t < exit.t {
+                exit = cap_exit;
+            }
+        }
+
+        if enter.t >= exit.t {
+            return Some(Vec::new());
+        }
+        Some(vec![Interval::new(
+            enter.to_hitinfo(&self.frame, ray),
+            exit.to_hitinfo(&self.frame, ray),
+        )])
+    }
+}
+
+pub struct Cone {
+    ///Frame with the center of the base as origin and the axis towards the apex as y
+    frame: Frame,
+    ///Radius of the base
+    r: f64,
+    ///Distance from the base to the apex
+    h: f64,
+    ///Whether the base is closed by a disc
+    capped: bool,
+    material: Rc<dyn Material>,
+}
+
+impl Cone {
+    ///base-center of the base, axis-direction from the base to the apex
+    pub fn new(
+        base: Point3D,
+        axis: Normal3D,
+        r: f64,
+        h: f64,
+        capped: bool,
+        material: Rc<dyn Material>,
+    ) -> Self {
+        Cone {
+            frame: Frame::new(base, axis),
+            r,
+            h,
+            capped,
+            material,
+        }
+    }
+}
+
+impl GeometricObject for Cone {
+    fn check_hit(&self, ray: &Ray) -> Option<HitInfo> {
+        //x^2+z^2=k^2(h-y)^2 where k is the slope of the side
+        let local_ray = self.frame.to_local_ray(ray);
+        let (o, d) = (local_ray.o, local_ray.d);
+        let k_sqr = (self.r / self.h).powi(2);
+        let h_o = self.h - o.y();
+        let a = d.x() * d.x() + d.z() * d.z() - k_sqr * d.y() * d.y();
+        let b = 2.0 * (o.x() * d.x() + o.z() * d.z() + k_sqr * h_o * d.y());
+        let c = o.x() * o.x() + o.z() * o.z() - k_sqr * h_o * h_o;
+
+        let h = self.h;
+        let mut closest = hit_lateral_surface(&local_ray, (a, b, c), h, |p| {
+            Vector3D::new(p.x(), k_sqr * (h - p.y()), p.z())
+        });
+        if self.capped {
+            if let Some(hit) = hit_cap(&local_ray, 0.0, self.r, -1.0) {
+                closest = LocalHit::closest(closest, hit);
+            }
+        }
+        closest.map(|hit| hit.to_hitinfo(&self.frame, ray))
+    }
+
+    fn get_material(&self) -> &dyn Material {
+        self.material.as_ref()
+    }
+
+    fn get_bounding_box(&self) -> Option<BoundingBox> {
+        let local = BoundingBox::new(
+            Point3D::new(-self.r, 0.0, -self.r),
+            Point3D::new(self.r, self.h, self.r),
+        );
+        Some(self.frame.to_world_bounding_box(&local))
+    }
+}
+
+///Open paraboloid bowl x^2+z^2=(r^2/h)y with its vertex at the origin
+pub struct Paraboloid {
+    ///Frame with the vertex as origin and the axis towards the open end as y
+    frame: Frame,
+    ///Radius of the open end
+    r: f64,
+    ///Distance from the vertex to the open end
+    h: f64,
+    material: Rc<dyn Material>,
+}
+
+impl Paraboloid {
+    ///vertex-lowest point of the bowl, axis-direction from the vertex to the open end
+    pub fn new(vertex: Point3D, axis: Normal3D, r: f64, h: f64, material: Rc<dyn Material>) -> Self {
+        Paraboloid {
+            frame: Frame::new(vertex, axis),
+            r,
+            h,
+            material,
+        }
+    }
+}
+
+impl GeometricObject for Paraboloid {
+    fn check_hit(&self, ray: &Ray) -> Option<HitInfo> {
+        let local_ray = self.frame.to_local_ray(ray);
+        let (o, d) = (local_ray.o, local_ray.d);
+        let k = self.r * self.r / self.h;
+        let a = d.x() * d.x() + d.z() * d.z();
+        let b = 2.0 * (o.x() * d.x() + o.z() * d.z()) - k * d.y();
+        let c = o.x() * o.x() + o.z() * o.z() - k * o.y();
+
+        hit_lateral_surface(&local_ray, (a, b, c), self.h, |p| {
+            Vector3D::new(2.0 * p.x(), -k, 2.0 * p.z())
+        })
+        .map(|hit| hit.to_hitinfo(&self.frame, ray))
+    }
+
+    fn get_material(&self) -> &dyn Material {
+        self.material.as_ref()
+    }
+
+    fn get_bounding_box(&self) -> Option<BoundingBox> {
+        let local = BoundingBox::new(
+            Point3D::new(-self.r, 0.0, -self.r),
+            Point3D::new(self.r, self.h, self.r),
+        );
+        Some(self.frame.to_world_bounding_box(&local))
+    }
+}
+
+///A ThinDisc with a hole in the middle
+pub struct Annulus {
+    ///Frame with the center as origin and the normal as y
+    frame: Frame,
+    ///Radius of the hole
+    inner_r: f64,
+    ///Radius of the outer edge
+    outer_r: f64,
+    material: Rc<dyn Material>,
+}
+
+impl Annulus {
+    pub fn new(
+        c: Point3D,
+        inner_r: f64,
+        outer_r: f64,
+        n: Normal3D,
+        material: Rc<dyn Material>,
+    ) -> Self {
+        Annulus {
+            frame: Frame::new(c, n),
+            inner_r,
+            outer_r,
+            material,
+        }
+    }
+}
+
+impl GeometricObject for Annulus {
+    fn check_hit(&self, ray: &Ray) -> Option<HitInfo> {
+        let local_ray = self.frame.to_local_ray(ray);
+        if local_ray.d.y() == 0.0 {
+            //Ray is parallel to the annulus
+            return None;
+        }
+        let t = -local_ray.o.y() / local_ray.d.y();
+        if t <= K_EPSILON {
+            return None;
+        }
+        let p = local_ray.get_point_at(t);
+        let distance_from_center_sqr = p.x() * p.x() + p.z() * p.z();
+        if distance_from_center_sqr < self.inner_r * self.inner_r
+            || distance_from_center_sqr > self.outer_r * self.outer_r
+        {
+            return None;
+        }
+        let v = (distance_from_center_sqr.sqrt() - self.inner_r) / (self.outer_r - self.inner_r);
+        Some(LocalHit {
+            t,
+            normal: Vector3D::new(0.0, 1.0, 0.0),
+            uv: (azimuth(&p), v),
+        }
+        .to_hitinfo(&self.frame, ray))
+    }
+
+    fn get_material(&self) -> &dyn Material {
+        self.material.as_ref()
+    }
+
+    fn get_bounding_box(&self) -> Option<BoundingBox> {
+        let local = BoundingBox::new(
+            Point3D::new(-self.outer_r, 0.0, -self.outer_r),
+            Point3D::new(self.outer_r, 0.0, self.outer_r),
+        );
+        Some(self.frame.to_world_bounding_box(&local).pad(K_EPSILON))
+    }
+}
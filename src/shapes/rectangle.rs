@@ -0,0 +1,79 @@
+use super::K_EPSILON;
+use crate::graphics::*;
+use std::rc::Rc;
+
+///Parallelogram spanned by two edges starting at a corner, a rectangle when the edges are perpendicular
+pub struct Rectangle {
+    ///Corner from which both edges start
+    corner: Point3D,
+    ///Edge along which u increases
+    a: Vector3D,
+    ///Edge along which v increases
+    b: Vector3D,
+    ///A normalised Normal along a x b
+    n: Normal3D,
+    ///(a x b)/|a x b|^2, projects a point on the plane onto the edges
+    w: Vector3D,
+    material: Rc<dyn Material>,
+}
+
+impl Rectangle {
+    pub fn new(corner: Point3D, a: Vector3D, b: Vector3D, material: Rc<dyn Material>) -> Self {
+        let cross = a ^ b;
+        Rectangle {
+            corner,
+            a,
+            b,
+            n: Normal3D::from(cross).normalize(),
+            w: cross * (1.0 / cross.magnitude_sqr()),
+            material,
+        }
+    }
+}
+
+impl GeometricObject for Rectangle {
+    fn check_hit(&self, ray: &Ray) -> Option<HitInfo> {
+        let denominator = ray.d * self.n;
+        if denominator == 0.0 {
+            //Ray is parallel to the rectangle
+            return None;
+        }
+        let t = ((self.corner - ray.o) * self.n) / denominator;
+        if t <= K_EPSILON {
+            return None;
+        }
+        let hitpoint = ray.get_point_at(t);
+        let q = hitpoint - self.corner;
+        let u = self.w * (q ^ self.b);
+        let v = self.w * (self.a ^ q);
+        if (0.0..=1.0).contains(&u) && (0.0..=1.0).contains(&v) {
+            Some(HitInfo::new(t, self.n, hitpoint).with_uv(u, v))
+        } else {
+            None
+        }
+    }
+
+    fn get_material(&self) -> &dyn Material {
+        self.material.as_ref()
+    }
+
+    fn get_bounding_box(&self) -> Option<BoundingBox> {
+        BoundingBox::from_points(&[
+            self.corner,
+            self.corner + self.a,
+            self.corner + self.b,
+            self.corner + self.a + self.b,
+        ])
+        .map(|bbox| bbox.pad(K_EPSILON))
+    }
+
+    ///Uniform over the parallelogram
+    fn sample_surface(&self, u: (f64, f64)) -> Option<(Point3D, Normal3D, f64)> {
+        let area = (self.a ^ self.b).magnitude_sqr().sqrt();
+        Some((
+            self.corner + self.a * u.0 + self.b * u.1,
+            self.n,
+            1.0 / area,
+        ))
+    }
+}
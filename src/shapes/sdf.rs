@@ -0,0 +1,279 @@
+use crate::graphics::*;
+use std::rc::Rc;
+
+///Signed distance expression, negative inside the shape
+///Primitives are centered on the origin with y as their axis, use translate to move them
+pub enum Sdf {
+    Sphere {
+        r: f64,
+    },
+    Cuboid {
+        half_extents: Vector3D,
+    },
+    Torus {
+        major_r: f64,
+        minor_r: f64,
+    },
+    ///Segment from a to b with radius r
+    Capsule {
+        a: Point3D,
+        b: Point3D,
+        r: f64,
+    },
+    ///Power 8 gives the classic bulb
+    Mandelbulb {
+        power: f64,
+        iterations: u32,
+    },
+    Translate(Vector3D, Box<Sdf>),
+    Union(Box<Sdf>, Box<Sdf>),
+    ///First with the second carved out of it
+    Subtraction(Box<Sdf>, Box<Sdf>),
+    ///Union blended over a distance of about k
+    SmoothUnion(f64, Box<Sdf>, Box<Sdf>),
+    ///Subtraction blended over a distance of about k
+    SmoothSubtraction(f64, Box<Sdf>, Box<Sdf>),
+    ///Infinite copies every period along each axis, 0 leaves the axis alone
+    Repeat(Vector3D, Box<Sdf>),
+    ///Rotation about y by rate radians per unit of height
+    Twist(f64, Box<Sdf>),
+}
+
+impl Sdf {
+    pub fn sphere(r: f64) -> Sdf {
+        Sdf::Sphere { r }
+    }
+
+    pub fn cuboid(half_extents: Vector3D) -> Sdf {
+        Sdf::Cuboid { half_extents }
+    }
+
+    pub fn torus(major_r: f64, minor_r: f64) -> Sdf {
+        Sdf::Torus { major_r, minor_r }
+    }
+
+    pub fn capsule(a: Point3D, b: Point3D, r: f64) -> Sdf {
+        Sdf::Capsule { a, b, r }
+    }
+
+    pub fn mandelbulb(power: f64, iterations: u32) -> Sdf {
+        Sdf::Mandelbulb { power, iterations }
+    }
+
+    pub fn translate(self, offset: Vector3D) -> Sdf {
+        Sdf::Translate(offset, Box::new(self))
+    }
+
+    pub fn union(self, other: Sdf) -> Sdf {
+        Sdf::Union(Box::new(self), Box::new(other))
+    }
+
+    pub fn subtract(self, other: Sdf) -> Sdf {
+        Sdf::Subtraction(Box::new(self), Box::new(other))
+    }
+
+    pub fn smooth_union(self, other: Sdf, k: f64) -> Sdf {
+        Sdf::SmoothUnion(k, Box::new(self), Box::new(other))
+    }
+
+    pub fn smooth_subtract(self, other: Sdf, k: f64) -> Sdf {
+        Sdf::SmoothSubtraction(k, Box::new(self), Box::new(other))
+    }
+
+    pub fn repeat(self, period: Vector3D) -> Sdf {
+        Sdf::Repeat(period, Box::new(self))
+    }
+
+    pub fn twist(self, rate: f64) -> Sdf {
+        Sdf::Twist(rate, Box::new(self))
+    }
+
+    //Distance functions after Inigo Quilez, https://iquilezles.org/articles/distfunctions/
+    pub fn distance(&self, p: Point3D) -> f64 {
+        match self {
+            Sdf::Sphere { r } => (p - Point3D::origin()).magnitude_sqr().sqrt() - r,
+            Sdf::Cuboid { half_extents } => {
+                let q = Vector3D::new(
+                    p.x().abs() - half_extents.x(),
+                    p.y().abs() - half_extents.y(),
+                    p.z().abs() - half_extents.z(),
+                );
+                let outside = Vector3D::new(q.x().max(0.0), q.y().max(0.0), q.z().max(0.0));
+                outside.magnitude_sqr().sqrt() + q.x().max(q.y()).max(q.z()).min(0.0)
+            }
+            Sdf::Torus { major_r, minor_r } => {
+                let distance_from_ring = (p.x() * p.x() + p.z() * p.z()).sqrt() - major_r;
+                (distance_from_ring * distance_from_ring + p.y() * p.y()).sqrt() - minor_r
+            }
+            Sdf::Capsule { a, b, r } => {
+                let (pa, ba) = (p - *a, *b - *a);
+                //A capsule with both ends at the same point is a sphere
+                let h = if ba * ba > 0.0 {
+                    ((pa * ba) / (ba * ba)).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                (pa - ba * h).magnitude_sqr().sqrt() - r
+            }
+            Sdf::Mandelbulb { power, iterations } => mandelbulb(p, *power, *iterations),
+            Sdf::Translate(offset, sdf) => sdf.distance(p + -*offset),
+            Sdf::Union(a, b) => a.distance(p).min(b.distance(p)),
+            Sdf::Subtraction(a, b) => a.distance(p).max(-b.distance(p)),
+            Sdf::SmoothUnion(k, a, b) => {
+                let (d1, d2) = (a.distance(p), b.distance(p));
+                let h = (0.5 + 0.5 * (d2 - d1) / k).clamp(0.0, 1.0);
+                d2 + (d1 - d2) * h - k * h * (1.0 - h)
+            }
+            Sdf::SmoothSubtraction(k, a, b) => {
+                let (d1, d2) = (a.distance(p), b.distance(p));
+                let h = (0.5 - 0.5 * (d1 + d2) / k).clamp(0.0, 1.0);
+                d1 + (-d2 - d1) * h + k * h * (1.0 - h)
+            }
+            Sdf::Repeat(period, sdf) => {
+                let wrap = |x: f64, period: f64| {
+                    if period == 0.0 {
+                        x
+                    } else {
+                        x - period * (x / period).round()
+                    }
+                };
+                sdf.distance(Point3D::new(
+                    wrap(p.x(), period.x()),
+                    wrap(p.y(), period.y()),
+                    wrap(p.z(), period.z()),
+                ))
+            }
+            Sdf::Twist(rate, sdf) => {
+                let (sin, cos) = (rate * p.y()).sin_cos();
+                sdf.distance(Point3D::new(
+                    cos * p.x() - sin * p.z(),
+                    p.y(),
+                    sin * p.x() + cos * p.z(),
+                ))
+            }
+        }
+    }
+}
+
+///Distance estimate for the Mandelbulb fractal from the running derivative of the iteration
+fn mandelbulb(p: Point3D, power: f64, iterations: u32) -> f64 {
+    let c = p - Point3D::origin();
+    let mut z = c;
+    let mut dr = 1.0;
+    let mut r = 0.0;
+    for _ in 0..iterations {
+        r = z.magnitude_sqr().sqrt();
+        if r > 2.0 || r == 0.0 {
+            break;
+        }
+        //Raise z to the power in spherical coordinates
+        let theta = (z.z() / r).acos() * power;
+        let phi = z.y().atan2(z.x()) * power;
+        dr = r.powf(power - 1.0) * power * dr + 1.0;
+        let zr = r.powf(power);
+        z = Vector3D::new(
+            theta.sin() * phi.cos(),
+            theta.sin() * phi.sin(),
+            theta.cos(),
+        ) * zr
+            + c;
+    }
+    if r == 0.0 {
+        return 0.0;
+    }
+    0.5 * r.ln() * r / dr
+}
+
+///Object rendered by marching along the ray in steps of the distance to the surface
+pub struct SdfObject {
+    sdf: Sdf,
+    ///Marching happens only inside this box, it must enclose the whole surface
+    bounds: BoundingBox,
+    ///Surface is hit when closer than this
+    epsilon: f64,
+    max_steps: u32,
+    ///Fraction of the distance to step, below 1 for expressions like twist which
+    ///overestimate the distance
+    step_scale: f64,
+    material: Rc<dyn Material>,
+}
+
+impl SdfObject {
+    pub fn new(sdf: Sdf, bounds: BoundingBox, material: Rc<dyn Material>) -> Self {
+        SdfObject {
+            sdf,
+            bounds,
+            epsilon: 1e-4,
+            max_steps: 512,
+            step_scale: 1.0,
+            material,
+        }
+    }
+
+    pub fn with_step_scale(mut self, step_scale: f64) -> Self {
+        self.step_scale = step_scale;
+        self
+    }
+
+    pub fn with_max_steps(mut self, max_steps: u32) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    ///Gradient of the distance by central differences on a tetrahedron
+    fn get_normal_at(&self, p: Point3D) -> Normal3D {
+        let h = self.epsilon;
+        let offsets = [
+            Vector3D::new(1.0, -1.0, -1.0),
+            Vector3D::new(-1.0, -1.0, 1.0),
+            Vector3D::new(-1.0, 1.0, -1.0),
+            Vector3D::new(1.0, 1.0, 1.0),
+        ];
+        let gradient = offsets
+            .iter()
+            .fold(Vector3D::null(), |sum, k| {
+                sum + *k * self.sdf.distance(p + *k * h)
+            });
+        Normal3D::from(gradient).normalize()
+    }
+}
+
+impl GeometricObject for SdfObject {
+    fn check_hit(&self, ray: &Ray) -> Option<HitInfo> {
+        let (t_near, t_far) = self.bounds.hit(ray)?;
+        if t_far <= 0.0 {
+            return None;
+        }
+        //Directions of rays in object space aren't always normalised
+        let speed = ray.d.magnitude_sqr().sqrt();
+        let mut t = t_near.max(0.0);
+        //Rays leaving the surface start within epsilon of it, they must get clear of
+        //the surface before a hit counts
+        let mut clear_of_surface = false;
+        for _ in 0..self.max_steps {
+            if t > t_far {
+                return None;
+            }
+            let hitpoint = ray.get_point_at(t);
+            let distance = self.sdf.distance(hitpoint).abs();
+            if distance < self.epsilon {
+                if clear_of_surface {
+                    return Some(HitInfo::new(t, self.get_normal_at(hitpoint), hitpoint));
+                }
+            } else {
+                clear_of_surface = true;
+            }
+            //abs lets refracted rays march inside the shape too
+            t += distance.max(self.epsilon) * self.step_scale / speed;
+        }
+        None
+    }
+
+    fn get_material(&self) -> &dyn Material {
+        self.material.as_ref()
+    }
+
+    fn get_bounding_box(&self) -> Option<BoundingBox> {
+        Some(self.bounds)
+    }
+}
@@ -0,0 +1,91 @@
+use super::polynomial::solve_quartic;
+use super::{Frame, K_EPSILON};
+use crate::graphics::*;
+use std::f64::consts::PI;
+use std::rc::Rc;
+
+pub struct Torus {
+    ///Frame with the center as origin and the axis of symmetry as y
+    frame: Frame,
+    ///Distance from the center to the middle of the tube
+    major_r: f64,
+    ///Radius of the tube
+    minor_r: f64,
+    material: Rc<dyn Material>,
+}
+
+impl Torus {
+    pub fn new(
+        c: Point3D,
+        axis: Normal3D,
+        major_r: f64,
+        minor_r: f64,
+        material: Rc<dyn Material>,
+    ) -> Self {
+        Torus {
+            frame: Frame::new(c, axis),
+            major_r,
+            minor_r,
+            material,
+        }
+    }
+
+    fn get_local_box(&self) -> BoundingBox {
+        let extent = self.major_r + self.minor_r;
+        BoundingBox::new(
+            Point3D::new(-extent, -self.minor_r, -extent),
+            Point3D::new(extent, self.minor_r, extent),
+        )
+    }
+}
+
+impl GeometricObject for Torus {
+    fn check_hit(&self, ray: &Ray) -> Option<HitInfo> {
+        let local_ray = self.frame.to_local_ray(ray);
+        let (t_near, _) = self.get_local_box().hit(&local_ray)?;
+        //The quartic is badly conditioned far from the torus, so solve from where the ray
+        //enters the bounding box and add that distance back
+        let t_start = t_near.max(0.0);
+        let o = local_ray.get_point_at(t_start);
+        let d = local_ray.d;
+
+        //(x^2+y^2+z^2+R^2-r^2)^2=4R^2(x^2+z^2)
+        let (r_sqr, minor_sqr) = (self.major_r * self.major_r, self.minor_r * self.minor_r);
+        let o_vec = o - Point3D::origin();
+        let sum_d_sqr = d * d;
+        let e = o_vec * o_vec - r_sqr - minor_sqr;
+        let f = o_vec * d;
+        let four_r_sqr = 4.0 * r_sqr;
+        let coefficients = [
+            e * e - four_r_sqr * (minor_sqr - o.y() * o.y()),
+            4.0 * f * e + 2.0 * four_r_sqr * o.y() * d.y(),
+            2.0 * sum_d_sqr * e + 4.0 * f * f + four_r_sqr * d.y() * d.y(),
+            4.0 * sum_d_sqr * f,
+            sum_d_sqr * sum_d_sqr,
+        ];
+
+        let t = solve_quartic(coefficients)
+            .into_iter()
+            .map(|t| t + t_start)
+            .find(|t| *t > K_EPSILON)?;
+
+        let p = local_ray.get_point_at(t);
+        let param = p.x() * p.x() + p.y() * p.y() + p.z() * p.z() - r_sqr - minor_sqr;
+        let normal = Vector3D::new(p.x() * param, p.y() * (param + 2.0 * r_sqr), p.z() * param);
+        let u = p.z().atan2(p.x()) / (2.0 * PI) + 0.5;
+        let distance_from_axis = (p.x() * p.x() + p.z() * p.z()).sqrt();
+        let v = p.y().atan2(distance_from_axis - self.major_r) / (2.0 * PI) + 0.5;
+        Some(
+            HitInfo::new(t, self.frame.to_world_normal(normal), ray.get_point_at(t))
+                .with_uv(u, v),
+        )
+    }
+
+    fn get_material(&self) -> &dyn Material {
+        self.material.as_ref()
+    }
+
+    fn get_bounding_box(&self) -> Option<BoundingBox> {
+        Some(self.frame.to_world_bounding_box(&self.get_local_box()))
+    }
+}
@@ -0,0 +1,233 @@
+//! Keyframe interpolation, easing and timeline files
+
+use crate::animation::*;
+use crate::graphics::*;
+use crate::scene::SceneNode;
+use crate::shapes::Sphere;
+use std::rc::Rc;
+
+fn assert_close(a: f64, b: f64) {
+    assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+}
+
+fn get_track(keys: Vec<Keyframe>) -> Track {
+    let mut track = Track::new();
+    for key in keys {
+        track.add_key(key).unwrap();
+    }
+    track
+}
+
+fn value_at(track: &Track, time: f64) -> f64 {
+    track.evaluate(time).unwrap()[0]
+}
+
+#[test]
+fn linear_keys() {
+    //Added out of order, with the key at 2 replaced
+    let track = get_track(vec![
+        Keyframe::new(2.0, &[9.0]),
+        Keyframe::new(0.0, &[0.0]),
+        Keyframe::new(4.0, &[2.0]),
+        Keyframe::new(2.0, &[4.0]),
+    ]);
+    let times: Vec<f64> = track.get_keys().iter().map(|key| key.get_time()).collect();
+    assert_eq!(times, vec![0.0, 2.0, 4.0]);
+    //At the keys, between them and held past both ends
+    assert_close(value_at(&track, 0.0), 0.0);
+    assert_close(value_at(&track, 2.0), 4.0);
+    assert_close(value_at(&track, 4.0), 2.0);
+    assert_close(value_at(&track, 1.0), 2.0);
+    assert_close(value_at(&track, 3.5), 2.5);
+    assert_close(value_at(&track, -1.0), 0.0);
+    assert_close(value_at(&track, 10.0), 2.0);
+    assert!(Track::new().evaluate(0.0).is_none());
+}
+
+#[test]
+fn keys_keep_their_track_arity() {
+    let mut track = get_track(vec![Keyframe::new(0.0, &[1.0, 2.0])]);
+    assert!(track.add_key(Keyframe::new(1.0, &[1.0])).is_err());
+    let handles = Interpolation::Bezier {
+        out_handle: vec![1.0],
+        in_handle: vec![1.0, 2.0],
+    };
+    assert!(track
+        .add_key(Keyframe::new(1.0, &[1.0, 2.0]).with_interpolation(handles))
+        .is_err());
+    assert_eq!(track.get_keys().len(), 1);
+    //A lone key can be replaced by one of another size
+    assert!(track.add_key(Keyframe::new(0.0, &[1.0])).is_ok());
+    assert_eq!(track.evaluate(0.5).unwrap(), vec![1.0]);
+}
+
+#[test]
+fn step_and_bezier_keys() {
+    let track = get_track(vec![
+        Keyframe::new(0.0, &[1.0, 2.0]).with_interpolation(Interpolation::Step),
+        Keyframe::new(1.0, &[5.0, 6.0]),
+    ]);
+    assert_eq!(track.evaluate(0.999).unwrap(), vec![1.0, 2.0]);
+    assert_eq!(track.evaluate(1.0).unwrap(), vec![5.0, 6.0]);
+
+    //Handles a third of the way along give a straight line
+    let straight = get_track(vec![
+        Keyframe::new(0.0, &[0.0]).with_interpolation(Interpolation::Bezier {
+            out_handle: vec![1.0],
+            in_handle: vec![2.0],
+        }),
+        Keyframe::new(1.0, &[3.0]),
+    ]);
+    for &u in &[0.0, 0.25, 0.5, 0.9, 1.0] {
+        assert_close(value_at(&straight, u), 3.0 * u);
+    }
+    //Both handles at the end value pull the curve ahead of the line
+    let curve = get_track(vec![
+        Keyframe::new(0.0, &[0.0]).with_interpolation(Interpolation::Bezier {
+            out_handle: vec![1.0],
+            in_handle: vec![1.0],
+        }),
+        Keyframe::new(2.0, &[1.0]),
+    ]);
+    assert_close(value_at(&curve, 1.0), 7.0 / 8.0);
+}
+
+#[test]
+fn easings() {
+    let easings = [
+        Easing::Linear,
+        Easing::EaseIn,
+        Easing::EaseOut,
+        Easing::EaseInOut,
+    ];
+    for &easing in &easings {
+        assert_close(easing.apply(0.0), 0.0);
+        assert_close(easing.apply(1.0), 1.0);
+        let mut previous = 0.0;
+        for step in 1..=100 {
+            let u = easing.apply(step as f64 / 100.0);
+            assert!(u >= previous, "{:?} goes back at {}", easing, step);
+            previous = u;
+        }
+    }
+    assert_close(Easing::Linear.apply(0.3), 0.3);
+    assert_close(Easing::EaseIn.apply(0.5), 0.125);
+    assert_close(Easing::EaseOut.apply(0.5), 0.875);
+    assert_close(Easing::EaseInOut.apply(0.5), 0.5);
+    assert_close(Easing::EaseInOut.apply(0.25), 0.0625);
+    assert_close(Easing::EaseInOut.apply(0.75), 0.9375);
+
+    //The easing of a key remaps the progress towards the next key
+    let track = get_track(vec![
+        Keyframe::new(0.0, &[0.0]).with_easing(Easing::EaseIn),
+        Keyframe::new(2.0, &[8.0]),
+    ]);
+    assert_close(value_at(&track, 1.0), 1.0);
+}
+
+#[test]
+fn parse_timeline() {
+    let timeline = Timeline::parse(
+        "# A comment line\n\
+         \n\
+         key ball.translate 0 0 0 0   # and a comment after a key\n\
+         key ball.translate 2 4 0 0 ease-in-out\n\
+         key camera.pixel_size 0 0.5 step\n\
+         key camera.pixel_size 1 0.25\n\
+         key fuzz 0 0 bezier 1 2\n\
+         key fuzz 1 3 linear\n",
+    )
+    .unwrap();
+    assert_eq!(
+        timeline
+            .get_track("ball.translate")
+            .unwrap()
+            .get_keys()
+            .len(),
+        2
+    );
+    let ball = timeline.get_point("ball.translate", 1.0).unwrap();
+    assert_close(ball.x(), 2.0);
+    assert_close(timeline.get_f64("camera.pixel_size", 0.5, 1.0), 0.5);
+    assert_close(timeline.get_f64("camera.missing", 0.5, 1.0), 1.0);
+    assert_close(timeline.get_f64("fuzz", 0.5, 0.0), 1.5);
+    assert!(timeline.animates("ball"));
+    assert!(!timeline.animates("camera"));
+
+    let errors = [
+        (
+            "frame ball 0 1",
+            "line 1: expected a line starting with key",
+        ),
+        ("\nkey", "line 2: missing track name"),
+        ("key ball", "line 1: missing time"),
+        ("key ball soon 1", "line 1: time is not a number"),
+        ("key ball 0", "line 1: missing value"),
+        (
+            "key ball 0 1 2 bezier 1 2 3",
+            "line 1: bezier needs 4 values for its handles",
+        ),
+        ("key ball 0 1 bouncy", "line 1: unknown keyword bouncy"),
+        (
+            "key ball 0 1 2 3\n# a comment\nkey ball 1 4 5",
+            "line 3: key has 2 values but the track's keys have 3",
+        ),
+    ];
+    for (text, error) in errors.iter() {
+        assert_eq!(Timeline::parse(text).unwrap_err(), *error);
+    }
+    assert!(Timeline::load("/nonexistent.timeline").is_err());
+}
+
+#[test]
+fn default_timeline_parses() {
+    let timeline = Timeline::parse(include_str!("../../scenes/default.timeline")).unwrap();
+    assert!(timeline.animates("ground/east"));
+    assert!(timeline.animates("bouncer"));
+}
+
+#[test]
+fn timeline_moves_scene_nodes() {
+    let mut timeline = Timeline::new();
+    timeline
+        .add_key("arm.translate", Keyframe::new(0.0, &[0.0, 0.0, 0.0]))
+        .unwrap();
+    timeline
+        .add_key("arm.translate", Keyframe::new(1.0, &[4.0, 0.0, 0.0]))
+        .unwrap();
+    timeline
+        .add_key("arm/hand.scale", Keyframe::new(0.0, &[2.0]))
+        .unwrap();
+    let material: Rc<dyn Material> = Rc::new(LambertianMaterial::new(RGBColor::new(0.5, 0.5, 0.5)));
+    let mut robot = SceneNode::new("robot").with_child(
+        SceneNode::new("arm").with_child(
+            SceneNode::new("hand")
+                .with_transform(Transform::translate(Vector3D::new(0.0, 9.0, 0.0)))
+                .with_object(Rc::new(Sphere::new(Point3D::origin(), 1.0, material))),
+        ),
+    );
+    robot.animate(&timeline, 0.0, 1.0);
+
+    //Animated nodes follow their tracks, replacing the hand's translation by its scale
+    let hand = robot.get_world_transform("arm/hand", 0.5).unwrap();
+    let p = hand.transform_point(&Point3D::new(1.0, 0.0, 0.0));
+    assert_close(p.x(), 4.0);
+    assert_close(p.y(), 0.0);
+    assert!(robot.find("arm").unwrap().get_transform().is_animated());
+
+    //Rays at different times see the sphere where it was then
+    let mut world = World::new(ViewPlane::new(1, 1, 1.0, 1), RGBColor::new(0.0, 0.0, 0.0));
+    robot.flatten_into(&mut world);
+    let ray_at = |x: f64, time: f64| {
+        Ray::with_time(
+            Point3D::new(x, 0.0, 10.0),
+            Vector3D::new(0.0, 0.0, -1.0),
+            time,
+        )
+    };
+    assert!(world.find_hit(&ray_at(0.0, 0.0)).is_some());
+    assert!(world.find_hit(&ray_at(4.0, 0.0)).is_none());
+    assert!(world.find_hit(&ray_at(4.0, 1.0)).is_some());
+    let (_, hit_info) = world.find_hit(&ray_at(2.0, 0.5)).unwrap();
+    assert_close(hit_info.get_tmin(), 8.0);
+}
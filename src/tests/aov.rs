@@ -0,0 +1,269 @@
+//! AOVs recorded at the first hits and written to EXR
+
+use crate::checkpoint::Checkpoint;
+use crate::film::Film;
+use crate::graphics::*;
+use crate::integrators::PathIntegrator;
+use crate::shapes::*;
+use crate::tracer::{RenderBudget, SimpleTracer};
+use std::convert::TryInto;
+use std::rc::Rc;
+
+const OUTPUT_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/target/aov");
+
+///A grey plane through the origin facing the eye, a red sphere in front of it in the
+///middle and a grey one at the top right, under a white background
+fn get_world(_t: f64) -> World {
+    let mut world = World::new(
+        ViewPlane::new(16, 16, 1.0 / 4.0, 4).with_seed(1),
+        RGBColor::new(1.0, 1.0, 1.0),
+    )
+    .with_sky(false);
+    let grey: Rc<dyn Material> = Rc::new(LambertianMaterial::new(RGBColor::new(0.5, 0.5, 0.5)));
+    let red = Rc::new(LambertianMaterial::new(RGBColor::new(0.8, 0.2, 0.2)));
+    let objects = world.get_objects_mut();
+    objects.push(Box::new(Plane::new(
+        Point3D::new(0.0, 0.0, 0.0),
+        Normal3D::new(0.0, 0.0, 1.0),
+        grey.clone(),
+    )));
+    objects.push(Box::new(Sphere::new(Point3D::new(0.0, 0.0, 0.0), 1.0, red)));
+    objects.push(Box::new(Sphere::new(
+        Point3D::new(2.5, 2.5, 0.0),
+        1.0,
+        grey,
+    )));
+    world
+}
+
+fn get_plane_world(_t: f64) -> World {
+    let mut world = World::new(
+        ViewPlane::new(8, 8, 1.0 / 4.0, 4),
+        RGBColor::new(1.0, 1.0, 1.0),
+    )
+    .with_sky(false);
+    world.get_objects_mut().push(Box::new(Plane::new(
+        Point3D::new(0.0, 0.0, 0.0),
+        Normal3D::new(0.0, 0.0, 1.0),
+        Rc::new(LambertianMaterial::new(RGBColor::new(0.5, 0.5, 0.5))),
+    )));
+    world
+}
+
+fn render(worldfunc: fn(f64) -> World, budget: &RenderBudget) -> Film {
+    SimpleTracer::new().with_aovs(true).render_to_film(
+        Box::new(worldfunc),
+        0.0,
+        budget,
+        &mut |_| {},
+    )
+}
+
+fn assert_close(a: f64, b: f64) {
+    assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+}
+
+#[test]
+fn records_first_hits() {
+    let film = render(get_world, &RenderBudget::new().with_max_samples(4));
+    let aovs = film.get_aovs().expect("no AOVs");
+
+    //Bottom left corner, only the plane
+    assert_close(aovs.get_depth(0, 15).unwrap(), 10.0);
+    let normal = aovs.get_normal(0, 15).unwrap();
+    assert_close(normal.z(), 1.0);
+    assert_close(aovs.get_position(0, 15).unwrap().z(), 0.0);
+    let albedo = aovs.get_albedo(0, 15);
+    assert_close(albedo.r, 0.5);
+    assert_eq!(aovs.get_object_id(0, 15), Some(0));
+    assert_eq!(aovs.get_material_id(0, 15), Some(0));
+
+    //The red sphere is nearer than the plane
+    assert!(aovs.get_depth(8, 8).unwrap() < 10.0);
+    assert_close(aovs.get_albedo(8, 8).g, 0.2);
+    assert_eq!(aovs.get_object_id(8, 8), Some(1));
+    assert_eq!(aovs.get_material_id(8, 8), Some(1));
+
+    //The grey sphere shares the plane's material
+    assert_eq!(aovs.get_object_id(12, 2), Some(2));
+    assert_eq!(aovs.get_material_id(12, 2), Some(0));
+}
+
+#[test]
+fn direct_and_indirect_add_up_to_the_color() {
+    let film = render(get_world, &RenderBudget::new().with_max_samples(4));
+    let aovs = film.get_aovs().unwrap();
+    for y in 0..film.get_height() {
+        for x in 0..film.get_width() {
+            let (color, direct, indirect) = (
+                film.get_color(x, y),
+                aovs.get_direct(x, y),
+                aovs.get_indirect(x, y),
+            );
+            assert_close(direct.r + indirect.r, color.r);
+            assert_close(direct.g + indirect.g, color.g);
+            assert_close(direct.b + indirect.b, color.b);
+        }
+    }
+}
+
+#[test]
+fn light_off_a_single_plane_is_all_direct() {
+    //Every ray bounces off the plane once straight into the background
+    let film = render(get_plane_world, &RenderBudget::new().with_max_samples(2));
+    let aovs = film.get_aovs().unwrap();
+    for y in 0..film.get_height() {
+        for x in 0..film.get_width() {
+            assert_close(aovs.get_direct(x, y).r, 0.5);
+            assert_eq!(aovs.get_indirect(x, y).r, 0.0);
+        }
+    }
+}
+
+#[test]
+fn recording_aovs_leaves_the_color_alone() {
+    let budget = RenderBudget::new().with_max_samples(3);
+    let with_aovs = render(get_world, &budget);
+    let without =
+        SimpleTracer::new().render_to_film(Box::new(get_world), 0.0, &budget, &mut |_| {});
+    assert!(without.get_aovs().is_none());
+    let bits = |film: &Film| -> Vec<u64> {
+        film.get_pixels()
+            .iter()
+            .flat_map(|color| vec![color.r.to_bits(), color.g.to_bits(), color.b.to_bits()])
+            .collect()
+    };
+    assert_eq!(bits(&with_aovs), bits(&without));
+}
+
+#[test]
+fn resume_keeps_aovs() {
+    let uninterrupted = render(get_world, &RenderBudget::new().with_max_samples(4));
+    let first_half = render(get_world, &RenderBudget::new().with_max_samples(2));
+    let mut bytes = Vec::new();
+    Checkpoint::new(
+        first_half,
+        2,
+        get_world(0.0).get_view_plane(),
+        &PathIntegrator::new(),
+    )
+    .write(&mut bytes)
+    .unwrap();
+    let checkpoint = Checkpoint::read(&mut bytes.as_slice()).unwrap();
+    assert!(checkpoint.film.get_aovs().is_some());
+    let resumed = SimpleTracer::new()
+        .with_aovs(true)
+        .resume_to_film(
+            Box::new(get_world),
+            0.0,
+            &RenderBudget::new().with_max_samples(4),
+            checkpoint,
+            &mut |_| {},
+        )
+        .unwrap();
+    let (mut bytes_a, mut bytes_b) = (Vec::new(), Vec::new());
+    uninterrupted.write(&mut bytes_a).unwrap();
+    resumed.write(&mut bytes_b).unwrap();
+    assert!(bytes_a == bytes_b, "films differ");
+}
+
+///Channel names and values of an uncompressed scanline EXR of 32 bit floats
+fn read_exr(bytes: &[u8]) -> (u32, u32, Vec<(String, Vec<f32>)>) {
+    let i32_at = |at: usize| i32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+    let string_at = |at: usize| {
+        let end = at + bytes[at..].iter().position(|&b| b == 0).unwrap();
+        (String::from_utf8(bytes[at..end].to_vec()).unwrap(), end + 1)
+    };
+    assert_eq!(&bytes[..8], &[0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]);
+    let (mut at, mut names, mut size) = (8, Vec::new(), (0, 0));
+    while bytes[at] != 0 {
+        let (name, next) = string_at(at);
+        let (kind, next) = string_at(next);
+        let length = i32_at(next) as usize;
+        let value = next + 4;
+        match name.as_str() {
+            "channels" => {
+                assert_eq!(kind, "chlist");
+                let mut channel = value;
+                while bytes[channel] != 0 {
+                    let (channel_name, next) = string_at(channel);
+                    assert_eq!(i32_at(next), 2, "not a float channel");
+                    names.push(channel_name);
+                    channel = next + 16;
+                }
+            }
+            "compression" => assert_eq!(bytes[value], 0),
+            "dataWindow" => size = (i32_at(value + 8) as u32 + 1, i32_at(value + 12) as u32 + 1),
+            _ => {}
+        }
+        at = value + length;
+    }
+    let (width, height) = size;
+    let offsets = at + 1;
+    let mut channels: Vec<(String, Vec<f32>)> =
+        names.into_iter().map(|name| (name, Vec::new())).collect();
+    for y in 0..height as usize {
+        let offset = u64::from_le_bytes(
+            bytes[offsets + 8 * y..offsets + 8 * y + 8]
+                .try_into()
+                .unwrap(),
+        ) as usize;
+        assert_eq!(i32_at(offset), y as i32);
+        assert_eq!(
+            i32_at(offset + 4) as usize,
+            width as usize * channels.len() * 4
+        );
+        let mut value = offset + 8;
+        for (_, values) in channels.iter_mut() {
+            for _ in 0..width {
+                values.push(f32::from_le_bytes(
+                    bytes[value..value + 4].try_into().unwrap(),
+                ));
+                value += 4;
+            }
+        }
+    }
+    (width, height, channels)
+}
+
+#[test]
+fn saves_exr_layers() {
+    std::fs::create_dir_all(OUTPUT_DIR).unwrap();
+    let path = format!("{}/layers.exr", OUTPUT_DIR);
+    let film = render(get_world, &RenderBudget::new().with_max_samples(2));
+    film.save(&path).unwrap();
+    let (width, height, channels) = read_exr(&std::fs::read(&path).unwrap());
+    assert_eq!((width, height), (16, 16));
+
+    let names: Vec<&str> = channels.iter().map(|(name, _)| name.as_str()).collect();
+    let mut sorted = names.clone();
+    sorted.sort();
+    assert_eq!(names, sorted);
+    for name in [
+        "R",
+        "G",
+        "B",
+        "depth.Z",
+        "normal.X",
+        "albedo.R",
+        "position.Z",
+        "object_id.id",
+        "material_id.id",
+        "direct.G",
+        "indirect.B",
+    ]
+    .iter()
+    {
+        assert!(names.contains(name), "no {} channel", name);
+    }
+
+    let channel = |name: &str| &channels.iter().find(|(other, _)| other == name).unwrap().1;
+    let aovs = film.get_aovs().unwrap();
+    let index = (15 * width) as usize;
+    assert_eq!(channel("R")[index], film.get_color(0, 15).r as f32);
+    assert_eq!(
+        channel("depth.Z")[index],
+        aovs.get_depth(0, 15).unwrap() as f32
+    );
+    assert_eq!(channel("object_id.id")[8 * 16 + 8], 1.0);
+}
@@ -0,0 +1,205 @@
+//! Renders resumed from a checkpoint must match renders which never stopped
+
+use crate::checkpoint::{get_checkpoint_path, Checkpoint};
+use crate::film::Film;
+use crate::graphics::*;
+use crate::integrators::{DebugIntegrator, DebugView, PathIntegrator};
+use crate::shapes::*;
+use crate::tracer::{RenderBudget, RenderEvent, SimpleTracer};
+use std::io::ErrorKind;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Duration;
+
+const OUTPUT_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/target/checkpoint");
+
+fn get_world(_t: f64) -> World {
+    let mut world = World::new(
+        ViewPlane::new(40, 30, 1.0 / 8.0, 4).with_seed(5),
+        RGBColor::new(0.0, 0.0, 0.0),
+    );
+    let objects = world.get_objects_mut();
+    objects.push(Box::new(Sphere::new(
+        Point3D::new(0.0, -41.0, 0.0),
+        40.0,
+        Rc::new(LambertianMaterial::new(RGBColor::new(0.5, 0.5, 0.5))),
+    )));
+    objects.push(Box::new(Sphere::new(
+        Point3D::new(0.0, 0.0, 0.0),
+        1.0,
+        Rc::new(MetallicMaterial::new(RGBColor::new(0.8, 0.6, 0.2), 0.3)),
+    )));
+    world
+}
+
+fn render(budget: &RenderBudget) -> Film {
+    SimpleTracer::new().render_to_film(Box::new(get_world), 0.0, budget, &mut |_| {})
+}
+
+///Bit for bit, sums included
+fn assert_same(a: &Film, b: &Film) {
+    let (mut bytes_a, mut bytes_b) = (Vec::new(), Vec::new());
+    a.write(&mut bytes_a).unwrap();
+    b.write(&mut bytes_b).unwrap();
+    assert!(bytes_a == bytes_b, "films differ");
+}
+
+fn resume(checkpoint: &Checkpoint, budget: &RenderBudget) -> Film {
+    //Through the file format, as a render which was killed would
+    let mut bytes = Vec::new();
+    checkpoint.write(&mut bytes).unwrap();
+    let checkpoint = Checkpoint::read(&mut bytes.as_slice()).unwrap();
+    SimpleTracer::new()
+        .resume_to_film(Box::new(get_world), 0.0, budget, checkpoint, &mut |_| {})
+        .unwrap()
+}
+
+#[test]
+fn resume_matches_uninterrupted_render() {
+    let uninterrupted = render(&RenderBudget::new().with_max_samples(6));
+    let first_half = render(&RenderBudget::new().with_max_samples(3));
+    let checkpoint = Checkpoint::new(
+        first_half,
+        3,
+        get_world(0.0).get_view_plane(),
+        &PathIntegrator::new(),
+    );
+    let resumed = resume(&checkpoint, &RenderBudget::new().with_max_samples(6));
+    assert_same(&uninterrupted, &resumed);
+}
+
+#[test]
+fn resume_matches_uninterrupted_adaptive_render() {
+    let budget = RenderBudget::new().with_adaptive_threshold(0.02);
+    let uninterrupted = render(&budget.clone().with_max_samples(40));
+    let first_part = render(&budget.clone().with_max_samples(12));
+    let checkpoint = Checkpoint::new(
+        first_part,
+        12,
+        get_world(0.0).get_view_plane(),
+        &PathIntegrator::new(),
+    );
+    let resumed = resume(&checkpoint, &budget.with_max_samples(40));
+    assert_same(&uninterrupted, &resumed);
+}
+
+#[test]
+fn render_progressive_resumes_from_checkpoint_file() {
+    std::fs::create_dir_all(OUTPUT_DIR).unwrap();
+    let path = format!("{}/resumed.png", OUTPUT_DIR);
+    let checkpoint_path = get_checkpoint_path(&path);
+    let budget = RenderBudget::new().with_max_samples(6);
+    let uninterrupted = render(&budget);
+
+    let first_half = render(&RenderBudget::new().with_max_samples(3));
+    Checkpoint::new(
+        first_half,
+        3,
+        get_world(0.0).get_view_plane(),
+        &PathIntegrator::new(),
+    )
+    .save(&checkpoint_path)
+    .unwrap();
+    let mut resumed_from = None;
+    let resumed = SimpleTracer::new()
+        .render_progressive(Box::new(get_world), &path, 0.0, &budget, &mut |event| {
+            if let RenderEvent::Resumed { samples } = event {
+                resumed_from = Some(*samples);
+            }
+        })
+        .unwrap();
+    assert_same(&uninterrupted, &resumed);
+    assert_eq!(resumed_from, Some(3));
+    assert!(
+        !Path::new(&checkpoint_path).exists(),
+        "checkpoint left behind"
+    );
+}
+
+#[test]
+fn rejects_other_view_plane_or_integrator() {
+    let checkpoint = Checkpoint::new(
+        render(&RenderBudget::new().with_max_samples(1)),
+        1,
+        get_world(0.0).get_view_plane(),
+        &PathIntegrator::new(),
+    );
+    let path = PathIntegrator::new();
+    let other_seed = ViewPlane::new(40, 30, 1.0 / 8.0, 4).with_seed(6);
+    assert!(checkpoint.check(&other_seed, &path).is_err());
+    let other_size = ViewPlane::new(30, 40, 1.0 / 8.0, 4).with_seed(5);
+    assert!(checkpoint.check(&other_size, &path).is_err());
+    let other_samples = ViewPlane::new(40, 30, 1.0 / 8.0, 16).with_seed(5);
+    assert!(checkpoint.check(&other_samples, &path).is_err());
+    let other_sampler = ViewPlane::new(40, 30, 1.0 / 8.0, 4)
+        .with_seed(5)
+        .with_sampler(SamplerKind::Halton);
+    assert!(checkpoint.check(&other_sampler, &path).is_err());
+    let other_filter = ViewPlane::new(40, 30, 1.0 / 8.0, 4)
+        .with_seed(5)
+        .with_filter(Filter::new(FilterKind::Tent, 1.0));
+    assert!(checkpoint.check(&other_filter, &path).is_err());
+
+    let world = get_world(0.0);
+    let view_plane = world.get_view_plane();
+    assert!(checkpoint.check(view_plane, &path).is_ok());
+    assert!(checkpoint
+        .check(view_plane, &PathIntegrator::new().with_max_depth(5))
+        .is_err());
+    assert!(checkpoint
+        .check(view_plane, &PathIntegrator::new().with_roulette_depth(8))
+        .is_err());
+
+    //Integrators which only differ in the algorithm, through the file format
+    let normals = Checkpoint::new(
+        Film::new(40, 30),
+        1,
+        view_plane,
+        &DebugIntegrator::new(DebugView::Normal),
+    );
+    let mut bytes = Vec::new();
+    normals.write(&mut bytes).unwrap();
+    let normals = Checkpoint::read(&mut bytes.as_slice()).unwrap();
+    assert_eq!(normals.integrator, "normals");
+    assert!(normals
+        .check(view_plane, &DebugIntegrator::new(DebugView::Normal))
+        .is_ok());
+    assert!(normals
+        .check(view_plane, &DebugIntegrator::new(DebugView::Uv))
+        .is_err());
+}
+
+#[test]
+fn rejects_damaged_files() {
+    let checkpoint = Checkpoint::new(
+        Film::new(4, 4),
+        0,
+        get_world(0.0).get_view_plane(),
+        &PathIntegrator::new(),
+    );
+    let mut bytes = Vec::new();
+    checkpoint.write(&mut bytes).unwrap();
+
+    let truncated = &bytes[..bytes.len() - 1];
+    let error = Checkpoint::read(&mut &truncated[..]).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+
+    let mut wrong_magic = bytes.clone();
+    wrong_magic[0] = b'X';
+    let error = Checkpoint::read(&mut wrong_magic.as_slice()).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+}
+
+#[test]
+fn render_progressive_reports_save_errors() {
+    let path = format!("{}/missing/dir/image.png", OUTPUT_DIR);
+    let budget = RenderBudget::new().with_max_samples(1);
+    assert!(SimpleTracer::new()
+        .render_progressive(Box::new(get_world), &path, 0.0, &budget, &mut |_| {})
+        .is_err());
+    //A snapshot which fails stops the render instead of going on without saving
+    let budget = RenderBudget::new().with_snapshot_interval(Duration::from_secs(0));
+    assert!(SimpleTracer::new()
+        .render_progressive(Box::new(get_world), &path, 0.0, &budget, &mut |_| {})
+        .is_err());
+}
@@ -0,0 +1,97 @@
+//! The denoiser against renders with many more samples
+
+use super::regression::get_cornell_box;
+use crate::aov::{AovSample, Surface};
+use crate::denoise::Denoiser;
+use crate::film::Film;
+use crate::graphics::*;
+use crate::tracer::{RenderBudget, SimpleTracer};
+
+fn render(samples: u32) -> Film {
+    SimpleTracer::new().with_aovs(true).render_to_film(
+        Box::new(get_cornell_box),
+        0.0,
+        &RenderBudget::new().with_max_samples(samples),
+        &mut |_| {},
+    )
+}
+
+///Root mean square difference of the 8 bit channels
+fn get_rmse(a: &Film, b: &Film) -> f64 {
+    let (a, b) = (a.to_image().to_rgb(), b.to_image().to_rgb());
+    let mut sum_sqr = 0.0;
+    for (pixel, other) in a.pixels().zip(b.pixels()) {
+        for channel in 0..3 {
+            let difference = f64::from(pixel.data[channel]) - f64::from(other.data[channel]);
+            sum_sqr += difference * difference;
+        }
+    }
+    //len counts the channels
+    (sum_sqr / a.len() as f64).sqrt()
+}
+
+#[test]
+fn denoising_brings_a_noisy_render_closer_to_the_reference() {
+    let reference = render(256);
+    let noisy = render(8);
+    let denoised = Denoiser::new().denoise(&noisy).unwrap();
+    let (raw_error, denoised_error) = (
+        get_rmse(&noisy, &reference),
+        get_rmse(&denoised, &reference),
+    );
+    assert!(
+        denoised_error < raw_error / 2.0,
+        "RMSE {:.2} raw, {:.2} denoised",
+        raw_error,
+        denoised_error
+    );
+}
+
+#[test]
+fn flat_image_stays_flat() {
+    let color = RGBColor::new(0.2, 0.4, 0.6);
+    let sample = AovSample {
+        surface: Some(Surface {
+            depth: 3.0,
+            normal: Normal3D::new(0.0, 0.0, 1.0),
+            position: Point3D::new(0.0, 0.0, 7.0),
+            object_id: 0,
+            material_id: 0,
+        }),
+        albedo: RGBColor::new(0.5, 0.5, 0.5),
+        direct: color,
+        indirect: RGBColor::new(0.0, 0.0, 0.0),
+    };
+    let mut film = Film::new(12, 10).with_aovs();
+    for y in 0..10 {
+        for x in 0..12 {
+            for _ in 0..4 {
+                film.add_sample(x, y, color);
+                film.add_aov_sample(x, y, &sample);
+            }
+        }
+    }
+    let denoised = Denoiser::new().denoise(&film).unwrap();
+    for pixel in denoised.get_pixels() {
+        assert!((pixel.r - color.r).abs() < 1e-12);
+        assert!((pixel.b - color.b).abs() < 1e-12);
+    }
+}
+
+#[test]
+fn needs_aovs() {
+    assert!(Denoiser::new().denoise(&Film::new(4, 4)).is_err());
+}
+
+#[test]
+fn single_sample_render_stays_finite() {
+    //With one sample the variance of every pixel is unknown
+    let denoised = Denoiser::new().denoise(&render(1)).unwrap();
+    for pixel in denoised.get_pixels() {
+        assert!(
+            pixel.r.is_finite() && pixel.g.is_finite() && pixel.b.is_finite(),
+            "{:?}",
+            pixel
+        );
+    }
+}
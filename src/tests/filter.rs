@@ -0,0 +1,191 @@
+//! Reconstruction filters and the splatting of samples across tile borders
+
+use crate::checkpoint::Checkpoint;
+use crate::film::Film;
+use crate::graphics::*;
+use crate::integrators::PathIntegrator;
+use crate::shapes::*;
+use crate::tracer::{RenderBudget, SimpleTracer};
+use std::rc::Rc;
+
+const KINDS: [FilterKind; 5] = [
+    FilterKind::Box,
+    FilterKind::Tent,
+    FilterKind::Gaussian,
+    FilterKind::MitchellNetravali,
+    FilterKind::Lanczos,
+];
+
+fn get_filter(kind: FilterKind) -> Filter {
+    Filter::new(kind, kind.get_default_radius())
+}
+
+///Two tiles by two of nothing but a grey background
+fn get_empty_world(filter: Filter) -> World {
+    World::new(
+        ViewPlane::new(64, 64, 1.0 / 16.0, 16).with_filter(filter),
+        RGBColor::new(0.25, 0.5, 0.75),
+    )
+    .with_sky(false)
+}
+
+fn get_sphere_world(_t: f64) -> World {
+    let mut world = World::new(
+        ViewPlane::new(40, 40, 1.0 / 8.0, 4)
+            .with_seed(2)
+            .with_filter(get_filter(FilterKind::Gaussian)),
+        RGBColor::new(1.0, 1.0, 1.0),
+    );
+    world.get_objects_mut().push(Box::new(Sphere::new(
+        Point3D::new(0.0, 0.0, 0.0),
+        1.5,
+        Rc::new(LambertianMaterial::new(RGBColor::new(0.7, 0.3, 0.3))),
+    )));
+    world
+}
+
+fn render_empty(filter: Filter, samples: u32) -> Film {
+    SimpleTracer::new().render_to_film(
+        Box::new(move |_| get_empty_world(filter)),
+        0.0,
+        &RenderBudget::new().with_max_samples(samples),
+        &mut |_| {},
+    )
+}
+
+#[test]
+fn filter_shapes() {
+    for kind in KINDS.iter() {
+        let filter = get_filter(*kind);
+        let radius = filter.get_radius();
+        let peak = filter.evaluate(0.0, 0.0);
+        assert!(peak > 0.0, "{:?}", kind);
+        for step in 1..=20 {
+            let d = radius * f64::from(step) / 20.0;
+            assert!(
+                filter.evaluate(d, 0.0) <= peak,
+                "{:?} peaks away from 0",
+                kind
+            );
+            assert_eq!(
+                filter.evaluate(d, 0.3),
+                filter.evaluate(-d, -0.3),
+                "{:?}",
+                kind
+            );
+        }
+        assert_eq!(filter.evaluate(radius * 1.01, 0.0), 0.0, "{:?}", kind);
+        if *kind != FilterKind::Box {
+            assert!(filter.evaluate(radius, 0.0).abs() < 1e-9, "{:?}", kind);
+        }
+    }
+    //Sharpening lobes
+    assert!(get_filter(FilterKind::MitchellNetravali).evaluate(1.5, 0.0) < 0.0);
+    assert!(get_filter(FilterKind::Lanczos).evaluate(1.5, 0.0) < 0.0);
+    //Never narrower than a pixel
+    assert_eq!(Filter::new(FilterKind::Tent, 0.1).get_radius(), 0.5);
+}
+
+#[test]
+fn box_weights_are_sample_counts() {
+    let film = render_empty(Filter::default(), 3);
+    for y in 0..film.get_height() {
+        for x in 0..film.get_width() {
+            assert_eq!(film.get_weight(x, y), 3.0);
+            assert_eq!(film.get_samples(x, y), 3);
+        }
+    }
+}
+
+#[test]
+fn samples_reach_across_tile_borders() {
+    for kind in KINDS[1..].iter() {
+        let film = render_empty(get_filter(*kind), 16);
+        //Away from the edges of the image every pixel gets about as much weight, whether
+        //or not its neighbours are in another tile
+        let interior = film.get_weight(16, 16);
+        for &(x, y) in [(31, 16), (32, 16), (16, 31), (16, 32), (31, 32), (32, 31)].iter() {
+            let weight = film.get_weight(x, y);
+            assert!(
+                (weight - interior).abs() < 0.1 * interior.abs(),
+                "{:?} weighs ({},{}) {} against {}",
+                kind,
+                x,
+                y,
+                weight,
+                interior
+            );
+        }
+        //A flat background stays flat, edges of the image included
+        for &(x, y) in [(0, 0), (31, 32), (63, 40)].iter() {
+            let color = film.get_color(x, y);
+            assert!(
+                (color.g - 0.5).abs() < 1e-9,
+                "{:?} at ({},{}): {}",
+                kind,
+                x,
+                y,
+                color.g
+            );
+            assert_eq!(film.get_samples(x, y), 16);
+        }
+    }
+}
+
+#[test]
+fn filtered_resume_matches_uninterrupted_render() {
+    let render = |samples: u32| {
+        SimpleTracer::new().render_to_film(
+            Box::new(get_sphere_world),
+            0.0,
+            &RenderBudget::new().with_max_samples(samples),
+            &mut |_| {},
+        )
+    };
+    let uninterrupted = render(4);
+    let checkpoint = Checkpoint::new(
+        render(2),
+        2,
+        get_sphere_world(0.0).get_view_plane(),
+        &PathIntegrator::new(),
+    );
+    let mut bytes = Vec::new();
+    checkpoint.write(&mut bytes).unwrap();
+    let checkpoint = Checkpoint::read(&mut bytes.as_slice()).unwrap();
+    assert_eq!(checkpoint.filter, get_filter(FilterKind::Gaussian));
+    let resumed = SimpleTracer::new()
+        .resume_to_film(
+            Box::new(get_sphere_world),
+            0.0,
+            &RenderBudget::new().with_max_samples(4),
+            checkpoint,
+            &mut |_| {},
+        )
+        .unwrap();
+    let (mut bytes_a, mut bytes_b) = (Vec::new(), Vec::new());
+    uninterrupted.write(&mut bytes_a).unwrap();
+    resumed.write(&mut bytes_b).unwrap();
+    assert!(bytes_a == bytes_b, "films differ");
+}
+
+#[test]
+fn negative_lobes_dont_blow_up_pixels() {
+    let filter = Filter::new(FilterKind::Lanczos, 2.0);
+    let (white, black) = (RGBColor::new(1.0, 1.0, 1.0), RGBColor::new(0.0, 0.0, 0.0));
+    let mut film = Film::new(3, 1);
+    //1.5 pixels away, in the negative lobe of the pixel to the right
+    film.add_filtered_sample(0, 0, (0.0, 0.5), white, &filter);
+    assert!(film.get_weight(1, 0) < 0.0);
+    let color = film.get_color(1, 0);
+    assert_eq!((color.r, color.g, color.b), (0.0, 0.0, 0.0));
+    assert_eq!(film.get_error(1, 0), None);
+
+    //The pixel's own samples outweigh it, and the effective sample count stays below the
+    //number of samples reaching the pixel even though the weights partly cancel
+    for _ in 0..3 {
+        film.add_filtered_sample(1, 0, (0.5, 0.5), black, &filter);
+    }
+    assert!(film.get_color(1, 0).g < 0.0);
+    let error = film.get_error(1, 0).unwrap();
+    assert!(error.is_finite() && error > 0.0, "{}", error);
+}
@@ -0,0 +1,125 @@
+//! White furnace tests: a sphere lit evenly from every direction by a white background
+//!
+//! A material which reflects everything it receives disappears against the background,
+//! so every pixel must come out exactly white. One which gains energy comes out brighter
+//! and one which loses it comes out darker, noise can't hide either.
+
+use crate::graphics::*;
+use crate::shapes::*;
+use crate::tracer::SimpleTracer;
+use std::rc::Rc;
+
+const OUTPUT_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/target/furnace");
+const TOLERANCE: f64 = 1e-9;
+
+///Renders the sphere filling most of a small image and checks that every pixel is
+///the given linear color
+fn check_furnace(name: &str, worldfunc: fn(f64) -> World, expected: f64) {
+    std::fs::create_dir_all(OUTPUT_DIR).unwrap();
+    let path = format!("{}/{}.png", OUTPUT_DIR, name);
+    let film = SimpleTracer::new().render(Box::new(worldfunc), &path, 0.0, &mut |_| {}).unwrap();
+    for y in 0..film.get_height() {
+        for x in 0..film.get_width() {
+            let color = film.get_color(x, y);
+            for channel in [color.r, color.g, color.b].iter() {
+                assert!(
+                    (channel - expected).abs() < TOLERANCE,
+                    "{}: pixel ({},{}) is {:?}, expected {}",
+                    name,
+                    x,
+                    y,
+                    color,
+                    expected
+                );
+            }
+        }
+    }
+}
+
+fn get_furnace(material: Rc<dyn Material>) -> World {
+    let mut world = World::new(
+        ViewPlane::new(16, 16, 1.0 / 16.0, 16),
+        RGBColor::new(1.0, 1.0, 1.0),
+    )
+    .with_sky(false);
+    world.get_objects_mut().push(Box::new(Sphere::new(
+        Point3D::new(0.0, 0.0, 0.0),
+        3.0,
+        material,
+    )));
+    world
+}
+
+#[test]
+fn lambertian() {
+    check_furnace(
+        "lambertian",
+        |_| {
+            get_furnace(Rc::new(LambertianMaterial::new(RGBColor::new(
+                1.0, 1.0, 1.0,
+            ))))
+        },
+        1.0,
+    );
+}
+
+#[test]
+fn lambertian_absorbs() {
+    //Half of the light is absorbed at the first bounce
+    check_furnace(
+        "lambertian_absorbs",
+        |_| {
+            get_furnace(Rc::new(LambertianMaterial::new(RGBColor::new(
+                0.5, 0.5, 0.5,
+            ))))
+        },
+        0.5,
+    );
+}
+
+#[test]
+fn mirror() {
+    check_furnace(
+        "mirror",
+        |_| {
+            get_furnace(Rc::new(MetallicMaterial::new(
+                RGBColor::new(1.0, 1.0, 1.0),
+                0.0,
+            )))
+        },
+        1.0,
+    );
+}
+
+#[test]
+fn fuzzy_metal() {
+    check_furnace(
+        "fuzzy_metal",
+        |_| {
+            get_furnace(Rc::new(MetallicMaterial::new(
+                RGBColor::new(1.0, 1.0, 1.0),
+                0.5,
+            )))
+        },
+        1.0,
+    );
+}
+
+#[test]
+fn dielectric() {
+    check_furnace(
+        "dielectric",
+        |_| get_furnace(Rc::new(Dielectric::new(1.5))),
+        1.0,
+    );
+}
+
+#[test]
+fn diffuse_light() {
+    //Emits its color and reflects nothing
+    check_furnace(
+        "diffuse_light",
+        |_| get_furnace(Rc::new(DiffuseLight::new(RGBColor::new(0.25, 0.25, 0.25)))),
+        0.25,
+    );
+}
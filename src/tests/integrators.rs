@@ -0,0 +1,347 @@
+//! The integrators other than the path tracer, on scenes whose images are known
+
+use crate::film::Film;
+use crate::graphics::*;
+use crate::integrators::*;
+use crate::scene::SceneNode;
+use crate::shapes::*;
+use crate::tracer::{RenderBudget, SimpleTracer};
+use std::rc::Rc;
+
+fn render(
+    integrator: impl Integrator + 'static,
+    worldfunc: fn(f64) -> World,
+    samples: u32,
+) -> Film {
+    SimpleTracer::new()
+        .with_integrator(integrator)
+        .render_to_film(
+            Box::new(worldfunc),
+            0.0,
+            &RenderBudget::new().with_max_samples(samples),
+            &mut |_| {},
+        )
+}
+
+fn assert_close(a: f64, b: f64) {
+    assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+}
+
+///A sphere filling most of the image in front of a white background
+fn get_sphere_world(material: Rc<dyn Material>) -> World {
+    let mut world = World::new(
+        ViewPlane::new(8, 8, 1.0 / 8.0, 4),
+        RGBColor::new(1.0, 1.0, 1.0),
+    )
+    .with_sky(false);
+    world.get_objects_mut().push(Box::new(Sphere::new(
+        Point3D::new(0.0, 0.0, 0.0),
+        3.0,
+        material,
+    )));
+    world
+}
+
+///A grey floor facing the eye with a ball of radius 1 resting on it in the middle
+fn get_ball_on_floor(_t: f64) -> World {
+    let mut world = World::new(
+        ViewPlane::new(16, 16, 1.0 / 4.0, 16),
+        RGBColor::new(1.0, 1.0, 1.0),
+    )
+    .with_sky(false);
+    let grey: Rc<dyn Material> = Rc::new(LambertianMaterial::new(RGBColor::new(0.5, 0.5, 0.5)));
+    let objects = world.get_objects_mut();
+    objects.push(Box::new(Plane::new(
+        Point3D::new(0.0, 0.0, 0.0),
+        Normal3D::new(0.0, 0.0, 1.0),
+        grey.clone(),
+    )));
+    objects.push(Box::new(Sphere::new(
+        Point3D::new(0.0, 0.0, 1.0),
+        1.0,
+        grey,
+    )));
+    world
+}
+
+#[test]
+fn whitted_lights_diffuse_surfaces_directly() {
+    //Every ray scattered off the sphere sees the background, without noise
+    let film = render(
+        WhittedIntegrator::new(),
+        |_| {
+            get_sphere_world(Rc::new(LambertianMaterial::new(RGBColor::new(
+                0.5, 0.5, 0.5,
+            ))))
+        },
+        2,
+    );
+    for y in 0..8 {
+        for x in 0..8 {
+            assert_close(film.get_color(x, y).g, 0.5);
+        }
+    }
+    //The ball lights the floor around it only by what it emits, which is nothing
+    let film = render(WhittedIntegrator::new(), get_ball_on_floor, 16);
+    let path = render(PathIntegrator::new(), get_ball_on_floor, 16);
+    let (x, y) = (10, 7);
+    assert!(film.get_color(x, y).g < path.get_color(x, y).g);
+}
+
+///A grey floor lit only by a 2 by 2 light facing it 3 above and 5 to the side, out of
+///view, with a sphere between the light and the middle of the floor if shadowed
+fn get_lit_floor(shadowed: bool) -> World {
+    let mut world = World::new(
+        ViewPlane::new(8, 8, 1.0 / 8.0, 64),
+        RGBColor::new(0.0, 0.0, 0.0),
+    )
+    .with_sky(false);
+    let objects = world.get_objects_mut();
+    objects.push(Box::new(Plane::new(
+        Point3D::new(0.0, 0.0, 0.0),
+        Normal3D::new(0.0, 0.0, 1.0),
+        Rc::new(LambertianMaterial::new(RGBColor::new(0.5, 0.5, 0.5))),
+    )));
+    objects.push(Box::new(Rectangle::new(
+        Point3D::new(4.0, -1.0, 3.0),
+        Vector3D::new(0.0, 2.0, 0.0),
+        Vector3D::new(2.0, 0.0, 0.0),
+        Rc::new(DiffuseLight::new(RGBColor::new(4.0, 4.0, 4.0))),
+    )));
+    if shadowed {
+        objects.push(Box::new(Sphere::new(
+            Point3D::new(2.5, 0.0, 1.5),
+            1.0,
+            Rc::new(LambertianMaterial::new(RGBColor::new(0.5, 0.5, 0.5))),
+        )));
+    }
+    world
+}
+
+#[test]
+fn whitted_samples_lights() {
+    let film = render(WhittedIntegrator::new(), |_| get_lit_floor(false), 64);
+    //Radiance of the floor at the middle of a pixel, the light integrated on a grid
+    let expected = |x: u32, y: u32| {
+        let floor = Point3D::new(
+            (f64::from(x) + 0.5) / 4.0 - 1.0,
+            1.0 - (f64::from(y) + 0.5) / 4.0,
+            0.0,
+        );
+        let cells = 100;
+        let cell_area = 4.0 / f64::from(cells * cells);
+        let mut irradiance = 0.0;
+        for i in 0..cells {
+            for j in 0..cells {
+                let light = Point3D::new(
+                    4.0 + 2.0 * (f64::from(i) + 0.5) / f64::from(cells),
+                    -1.0 + 2.0 * (f64::from(j) + 0.5) / f64::from(cells),
+                    3.0,
+                );
+                let d = light - floor;
+                let distance_sqr = d.magnitude_sqr();
+                //Both cosines are the height over the distance
+                irradiance += 4.0 * 3.0 * 3.0 / (distance_sqr * distance_sqr) * cell_area;
+            }
+        }
+        0.5 / std::f64::consts::PI * irradiance
+    };
+    for &(x, y) in &[(0, 0), (4, 4), (7, 2), (2, 7)] {
+        let (color, expected) = (film.get_color(x, y).g, expected(x, y));
+        assert!(
+            (color - expected).abs() < 0.03 * expected,
+            "pixel ({}, {}): {} against {}",
+            x,
+            y,
+            color,
+            expected
+        );
+    }
+
+    //The sphere hides the whole light from the middle of the floor
+    let film = render(WhittedIntegrator::new(), |_| get_lit_floor(true), 16);
+    assert_eq!(film.get_color(4, 4).g, 0.0);
+    assert!(film.get_color(0, 7).g > 0.0);
+}
+
+///get_lit_floor with the light a unit square in a scene node which scales and moves it
+///into place, squashing the normal by the scale along z
+fn get_lit_floor_in_node(_t: f64) -> World {
+    let mut world = get_lit_floor(false);
+    world.get_objects_mut().truncate(1);
+    let light: Rc<dyn GeometricObject> = Rc::new(Rectangle::new(
+        Point3D::new(0.0, 0.0, 0.0),
+        Vector3D::new(0.0, 1.0, 0.0),
+        Vector3D::new(1.0, 0.0, 0.0),
+        Rc::new(DiffuseLight::new(RGBColor::new(4.0, 4.0, 4.0))),
+    ));
+    SceneNode::new("light")
+        .with_transform(
+            Transform::scale(2.0, 2.0, 5.0)
+                .then(&Transform::translate(Vector3D::new(4.0, -1.0, 3.0))),
+        )
+        .with_object(light)
+        .flatten_into(&mut world);
+    world
+}
+
+#[test]
+fn whitted_samples_lights_in_scene_nodes() {
+    assert_eq!(get_lit_floor_in_node(0.0).get_lights(), &[1]);
+    let expected = render(WhittedIntegrator::new(), |_| get_lit_floor(false), 4);
+    let film = render(WhittedIntegrator::new(), get_lit_floor_in_node, 4);
+    for &(x, y) in &[(0, 0), (4, 4), (7, 2), (2, 7)] {
+        let (color, expected) = (film.get_color(x, y).g, expected.get_color(x, y).g);
+        assert!(
+            (color - expected).abs() < 1e-6 * expected,
+            "pixel ({}, {}): {} against {}",
+            x,
+            y,
+            color,
+            expected
+        );
+    }
+}
+
+#[test]
+fn whitted_follows_mirrors() {
+    let film = render(
+        WhittedIntegrator::new(),
+        |_| {
+            get_sphere_world(Rc::new(MetallicMaterial::new(
+                RGBColor::new(0.8, 0.6, 0.4),
+                0.0,
+            )))
+        },
+        2,
+    );
+    let aovs = SimpleTracer::new()
+        .with_integrator(WhittedIntegrator::new())
+        .with_aovs(true)
+        .render_to_film(
+            Box::new(|_| {
+                get_sphere_world(Rc::new(MetallicMaterial::new(
+                    RGBColor::new(0.8, 0.6, 0.4),
+                    0.0,
+                )))
+            }),
+            0.0,
+            &RenderBudget::new().with_max_samples(2),
+            &mut |_| {},
+        );
+    let aovs = aovs.get_aovs().unwrap();
+    for y in 0..8 {
+        for x in 0..8 {
+            let color = film.get_color(x, y);
+            assert_close(color.r, 0.8);
+            assert_close(color.b, 0.4);
+            //The background is seen after a bounce
+            assert_eq!(aovs.get_direct(x, y).r, 0.0);
+            assert_close(aovs.get_indirect(x, y).r, 0.8);
+        }
+    }
+    let film = render(
+        WhittedIntegrator::new().with_max_depth(0),
+        |_| {
+            get_sphere_world(Rc::new(MetallicMaterial::new(
+                RGBColor::new(0.8, 0.6, 0.4),
+                0.0,
+            )))
+        },
+        1,
+    );
+    assert_eq!(film.get_color(4, 4).r, 0.0);
+}
+
+#[test]
+fn ambient_occlusion_darkens_corners() {
+    let film = render(AmbientOcclusionIntegrator::new(1.0), get_ball_on_floor, 16);
+    //Far from the ball the floor is open, next to it the ball hides part of the sky
+    assert_eq!(film.get_color(0, 0).g, 1.0);
+    assert_eq!(film.get_color(15, 15).g, 1.0);
+    let next_to_ball = film.get_color(10, 7).g;
+    assert!(next_to_ball < 0.95, "{}", next_to_ball);
+    assert!(next_to_ball > 0.0);
+    //Occluders further away than the distance don't count
+    let film = render(AmbientOcclusionIntegrator::new(1e-3), get_ball_on_floor, 4);
+    assert_eq!(film.get_color(10, 7).g, 1.0);
+}
+
+#[test]
+fn debug_views() {
+    //The floor's normal is +z, mapped to (0.5,0.5,1) and squared for the gamma
+    let film = render(
+        DebugIntegrator::new(DebugView::Normal),
+        get_ball_on_floor,
+        1,
+    );
+    let color = film.get_color(0, 0);
+    assert_close(color.r, 0.25);
+    assert_close(color.g, 0.25);
+    assert_close(color.b, 1.0);
+
+    //The floor is about 5 from the view plane
+    let film = render(
+        DebugIntegrator::new(DebugView::Depth).with_far(10.0),
+        get_ball_on_floor,
+        1,
+    );
+    let floor = film.get_color(0, 0).g.sqrt();
+    assert!(floor < 0.5 && floor > 0.3, "{}", floor);
+    let ball = film.get_color(8, 8).g.sqrt();
+    assert!(
+        ball > floor,
+        "the ball is nearer: {} against {}",
+        ball,
+        floor
+    );
+
+    //A rectangle across the whole view, u grows to the right and v upwards
+    let film = render(
+        DebugIntegrator::new(DebugView::Uv),
+        |_| {
+            let mut world = World::new(
+                ViewPlane::new(8, 8, 1.0 / 8.0, 1),
+                RGBColor::new(0.0, 0.0, 0.0),
+            );
+            world.get_objects_mut().push(Box::new(Rectangle::new(
+                Point3D::new(-2.0, -2.0, 0.0),
+                Vector3D::new(4.0, 0.0, 0.0),
+                Vector3D::new(0.0, 4.0, 0.0),
+                Rc::new(LambertianMaterial::new(RGBColor::new(0.5, 0.5, 0.5))),
+            )));
+            world
+        },
+        1,
+    );
+    assert!(film.get_color(0, 4).r < film.get_color(7, 4).r);
+    assert!(film.get_color(4, 7).g < film.get_color(4, 0).g);
+    assert_eq!(film.get_color(4, 4).b, 0.0);
+}
+
+#[test]
+fn every_integrator_splits_direct_and_indirect() {
+    let integrators: Vec<Box<dyn Fn(SimpleTracer) -> SimpleTracer>> = vec![
+        Box::new(|tracer| tracer.with_integrator(PathIntegrator::new())),
+        Box::new(|tracer| tracer.with_integrator(WhittedIntegrator::new())),
+        Box::new(|tracer| tracer.with_integrator(AmbientOcclusionIntegrator::new(1.0))),
+        Box::new(|tracer| tracer.with_integrator(DebugIntegrator::new(DebugView::Normal))),
+    ];
+    for with_integrator in integrators.iter() {
+        let film = with_integrator(SimpleTracer::new().with_aovs(true)).render_to_film(
+            Box::new(get_ball_on_floor),
+            0.0,
+            &RenderBudget::new().with_max_samples(2),
+            &mut |_| {},
+        );
+        let aovs = film.get_aovs().unwrap();
+        assert_eq!(aovs.get_object_id(8, 8), Some(1));
+        for y in 0..16 {
+            for x in 0..16 {
+                let color = film.get_color(x, y);
+                let sum = aovs.get_direct(x, y) + aovs.get_indirect(x, y);
+                assert_close(sum.g, color.g);
+            }
+        }
+    }
+}
@@ -0,0 +1,534 @@
+//! Rays with intersections worked out by hand for each shape
+
+use crate::graphics::*;
+use crate::shapes::*;
+use std::rc::Rc;
+
+const TOLERANCE: f64 = 1e-6;
+
+fn get_material() -> Rc<dyn Material> {
+    Rc::new(LambertianMaterial::new(RGBColor::new(0.5, 0.5, 0.5)))
+}
+
+fn get_ray(o: (f64, f64, f64), d: (f64, f64, f64)) -> Ray {
+    Ray::new(Point3D::new(o.0, o.1, o.2), Vector3D::new(d.0, d.1, d.2))
+}
+
+fn assert_hit_within(
+    object: &dyn GeometricObject,
+    ray: &Ray,
+    t: f64,
+    normal: (f64, f64, f64),
+    tolerance: f64,
+) {
+    let hit_info = object
+        .check_hit(ray)
+        .unwrap_or_else(|| panic!("{:?} misses, expected t={}", ray.o, t));
+    let expected = Vector3D::new(normal.0, normal.1, normal.2).normalize();
+    let n = Vector3D::from(hit_info.get_normal());
+    assert!(
+        (hit_info.get_tmin() - t).abs() < tolerance,
+        "{:?}: t={}, expected {}",
+        ray.o,
+        hit_info.get_tmin(),
+        t
+    );
+    assert!(
+        (n - expected).magnitude_sqr().sqrt() < tolerance,
+        "{:?}: normal {:?}, expected {:?}",
+        ray.o,
+        n,
+        expected
+    );
+    let hit_point = hit_info.get_hitpoint();
+    let on_ray = ray.get_point_at(t);
+    assert!((*hit_point - on_ray).magnitude_sqr().sqrt() < tolerance);
+}
+
+fn assert_hit(object: &dyn GeometricObject, ray: &Ray, t: f64, normal: (f64, f64, f64)) {
+    assert_hit_within(object, ray, t, normal, TOLERANCE);
+}
+
+fn assert_miss(object: &dyn GeometricObject, ray: &Ray) {
+    if let Some(hit_info) = object.check_hit(ray) {
+        panic!("{:?} hits at t={}", ray.o, hit_info.get_tmin());
+    }
+}
+
+#[test]
+fn plane() {
+    let plane = Plane::new(
+        Point3D::new(0.0, -1.0, 0.0),
+        Normal3D::new(0.0, 1.0, 0.0),
+        get_material(),
+    );
+    assert_hit(
+        &plane,
+        &get_ray((0.0, 5.0, 0.0), (0.0, -1.0, 0.0)),
+        6.0,
+        (0.0, 1.0, 0.0),
+    );
+    assert_hit(
+        &plane,
+        &get_ray((3.0, 1.0, 0.0), (-1.0, -1.0, 0.0)),
+        2.0,
+        (0.0, 1.0, 0.0),
+    );
+    assert_miss(&plane, &get_ray((0.0, 5.0, 0.0), (1.0, 0.0, 0.0)));
+    assert_miss(&plane, &get_ray((0.0, 5.0, 0.0), (0.0, 1.0, 0.0)));
+}
+
+#[test]
+fn sphere() {
+    let sphere = Sphere::new(Point3D::new(0.0, 0.0, 0.0), 2.0, get_material());
+    assert_hit(
+        &sphere,
+        &get_ray((0.0, 0.0, 10.0), (0.0, 0.0, -1.0)),
+        8.0,
+        (0.0, 0.0, 1.0),
+    );
+    //t is in units of the direction, which needn't be normalised
+    assert_hit(
+        &sphere,
+        &get_ray((0.0, 0.0, 10.0), (0.0, 0.0, -2.0)),
+        4.0,
+        (0.0, 0.0, 1.0),
+    );
+    //From the inside the far side is hit, with the outward normal
+    assert_hit(
+        &sphere,
+        &get_ray((0.0, 0.0, 0.0), (1.0, 0.0, 0.0)),
+        2.0,
+        (1.0, 0.0, 0.0),
+    );
+    let y = 3.0f64.sqrt();
+    assert_hit(
+        &sphere,
+        &get_ray((1.0, y, 5.0), (0.0, 0.0, -1.0)),
+        5.0,
+        (1.0, y, 0.0),
+    );
+    assert_miss(&sphere, &get_ray((3.0, 0.0, 10.0), (0.0, 0.0, -1.0)));
+    assert_miss(&sphere, &get_ray((0.0, 0.0, 10.0), (0.0, 0.0, 1.0)));
+}
+
+#[test]
+fn moving_sphere() {
+    let sphere = MovingSphere::new(
+        Point3D::new(0.0, 0.0, 0.0),
+        0.0,
+        Point3D::new(2.0, 0.0, 0.0),
+        1.0,
+        1.0,
+        get_material(),
+    );
+    let at_time = |x: f64, time: f64| {
+        Ray::with_time(
+            Point3D::new(x, 0.0, 10.0),
+            Vector3D::new(0.0, 0.0, -1.0),
+            time,
+        )
+    };
+    assert_hit(&sphere, &at_time(0.0, 0.0), 9.0, (0.0, 0.0, 1.0));
+    assert_hit(&sphere, &at_time(1.0, 0.5), 9.0, (0.0, 0.0, 1.0));
+    assert_hit(&sphere, &at_time(2.0, 1.0), 9.0, (0.0, 0.0, 1.0));
+    assert_miss(&sphere, &at_time(2.0, 0.0));
+}
+
+#[test]
+fn thin_disc() {
+    let disc = ThinDisc::new(
+        Point3D::new(0.0, 0.0, 0.0),
+        1.0,
+        Normal3D::new(0.0, 0.0, 1.0),
+        get_material(),
+    );
+    assert_hit(
+        &disc,
+        &get_ray((0.5, 0.0, 5.0), (0.0, 0.0, -1.0)),
+        5.0,
+        (0.0, 0.0, 1.0),
+    );
+    assert_miss(&disc, &get_ray((1.5, 0.0, 5.0), (0.0, 0.0, -1.0)));
+}
+
+#[test]
+fn rectangle() {
+    let rectangle = Rectangle::new(
+        Point3D::new(0.0, 0.0, 0.0),
+        Vector3D::new(2.0, 0.0, 0.0),
+        Vector3D::new(0.0, 1.0, 0.0),
+        get_material(),
+    );
+    let ray = get_ray((1.0, 0.5, 3.0), (0.0, 0.0, -1.0));
+    assert_hit(&rectangle, &ray, 3.0, (0.0, 0.0, 1.0));
+    let (u, v) = rectangle.check_hit(&ray).unwrap().get_uv();
+    assert!((u - 0.5).abs() < TOLERANCE && (v - 0.5).abs() < TOLERANCE);
+    assert_miss(&rectangle, &get_ray((2.5, 0.5, 3.0), (0.0, 0.0, -1.0)));
+    assert_miss(&rectangle, &get_ray((1.0, -0.5, 3.0), (0.0, 0.0, -1.0)));
+}
+
+#[test]
+fn axis_aligned_box() {
+    let aabb = AxisAlignedBox::new(
+        Point3D::new(-1.0, -1.0, -1.0),
+        Point3D::new(1.0, 1.0, 1.0),
+        get_material(),
+    );
+    assert_hit(
+        &aabb,
+        &get_ray((0.0, 0.0, 5.0), (0.0, 0.0, -1.0)),
+        4.0,
+        (0.0, 0.0, 1.0),
+    );
+    assert_hit(
+        &aabb,
+        &get_ray((0.5, 5.0, 0.2), (0.0, -1.0, 0.0)),
+        4.0,
+        (0.0, 1.0, 0.0),
+    );
+    assert_hit(
+        &aabb,
+        &get_ray((-4.0, 0.0, 0.0), (1.0, 0.0, 0.0)),
+        3.0,
+        (-1.0, 0.0, 0.0),
+    );
+    assert_miss(&aabb, &get_ray((1.5, 0.0, 5.0), (0.0, 0.0, -1.0)));
+
+    let intervals = aabb
+        .get_intervals(&get_ray((0.0, 0.0, 5.0), (0.0, 0.0, -1.0)))
+        .unwrap();
+    assert_eq!(intervals.len(), 1);
+    assert!((intervals[0].get_enter().get_tmin() - 4.0).abs() < TOLERANCE);
+    assert!((intervals[0].get_exit().get_tmin() - 6.0).abs() < TOLERANCE);
+}
+
+#[test]
+fn oriented_box() {
+    //Turned 45 degrees around z
+    let obb = OrientedBox::new(
+        Point3D::new(0.0, 0.0, 0.0),
+        Vector3D::new(1.0, 2.0, 3.0),
+        Vector3D::new(1.0, 1.0, 0.0),
+        Vector3D::new(-1.0, 1.0, 0.0),
+        get_material(),
+    );
+    let diagonal = 2.0f64.sqrt();
+    assert_hit(
+        &obb,
+        &get_ray((5.0, 0.0, 0.0), (-1.0, 0.0, 0.0)),
+        5.0 - diagonal,
+        (1.0, 1.0, 0.0),
+    );
+    assert_hit(
+        &obb,
+        &get_ray((0.0, 0.0, 5.0), (0.0, 0.0, -1.0)),
+        2.0,
+        (0.0, 0.0, 1.0),
+    );
+    //Inside the axis aligned box around it but outside the oriented box
+    assert_miss(&obb, &get_ray((1.5, 1.5, 5.0), (0.0, 0.0, -1.0)));
+}
+
+#[test]
+fn cylinder() {
+    let axis = Normal3D::new(0.0, 1.0, 0.0);
+    let capped = Cylinder::new(
+        Point3D::new(0.0, 0.0, 0.0),
+        axis,
+        1.0,
+        2.0,
+        true,
+        get_material(),
+    );
+    let open = Cylinder::new(
+        Point3D::new(0.0, 0.0, 0.0),
+        axis,
+        1.0,
+        2.0,
+        false,
+        get_material(),
+    );
+    let side = get_ray((0.0, 1.0, 5.0), (0.0, 0.0, -1.0));
+    assert_hit(&capped, &side, 4.0, (0.0, 0.0, 1.0));
+    assert_hit(&open, &side, 4.0, (0.0, 0.0, 1.0));
+    let down_axis = get_ray((0.0, 5.0, 0.0), (0.0, -1.0, 0.0));
+    assert_hit(&capped, &down_axis, 3.0, (0.0, 1.0, 0.0));
+    assert_miss(&open, &down_axis);
+    assert_miss(&capped, &get_ray((0.0, 2.5, 5.0), (0.0, 0.0, -1.0)));
+}
+
+#[test]
+fn cone() {
+    let cone = Cone::new(
+        Point3D::new(0.0, 0.0, 0.0),
+        Normal3D::new(0.0, 1.0, 0.0),
+        1.0,
+        2.0,
+        true,
+        get_material(),
+    );
+    //Halfway up the radius is 0.5, the side's normal leans towards the apex by atan(r/h)
+    assert_hit(
+        &cone,
+        &get_ray((0.0, 1.0, 5.0), (0.0, 0.0, -1.0)),
+        4.5,
+        (0.0, 0.5, 1.0),
+    );
+    assert_hit(
+        &cone,
+        &get_ray((0.5, -5.0, 0.0), (0.0, 1.0, 0.0)),
+        5.0,
+        (0.0, -1.0, 0.0),
+    );
+    assert_miss(&cone, &get_ray((0.0, 1.6, 5.0), (1.0, 0.0, 0.0)));
+    assert_miss(&cone, &get_ray((0.6, 1.0, 5.0), (0.0, 0.0, -1.0)));
+}
+
+#[test]
+fn paraboloid() {
+    //y=x^2+z^2 up to y=1
+    let paraboloid = Paraboloid::new(
+        Point3D::new(0.0, 0.0, 0.0),
+        Normal3D::new(0.0, 1.0, 0.0),
+        1.0,
+        1.0,
+        get_material(),
+    );
+    assert_hit(
+        &paraboloid,
+        &get_ray((0.0, 0.25, 5.0), (0.0, 0.0, -1.0)),
+        4.5,
+        (0.0, -1.0, 1.0),
+    );
+    assert_hit(
+        &paraboloid,
+        &get_ray((0.0, -5.0, 0.0), (0.0, 1.0, 0.0)),
+        5.0,
+        (0.0, -1.0, 0.0),
+    );
+    assert_miss(&paraboloid, &get_ray((0.0, 1.5, 5.0), (0.0, 0.0, -1.0)));
+}
+
+#[test]
+fn annulus() {
+    let annulus = Annulus::new(
+        Point3D::new(0.0, 0.0, 0.0),
+        0.5,
+        1.0,
+        Normal3D::new(0.0, 1.0, 0.0),
+        get_material(),
+    );
+    assert_hit(
+        &annulus,
+        &get_ray((0.75, 5.0, 0.0), (0.0, -1.0, 0.0)),
+        5.0,
+        (0.0, 1.0, 0.0),
+    );
+    assert_miss(&annulus, &get_ray((0.25, 5.0, 0.0), (0.0, -1.0, 0.0)));
+    assert_miss(&annulus, &get_ray((1.5, 5.0, 0.0), (0.0, -1.0, 0.0)));
+}
+
+#[test]
+fn torus() {
+    let torus = Torus::new(
+        Point3D::new(0.0, 0.0, 0.0),
+        Normal3D::new(0.0, 1.0, 0.0),
+        2.0,
+        0.5,
+        get_material(),
+    );
+    assert_hit(
+        &torus,
+        &get_ray((2.0, 5.0, 0.0), (0.0, -1.0, 0.0)),
+        4.5,
+        (0.0, 1.0, 0.0),
+    );
+    assert_hit(
+        &torus,
+        &get_ray((5.0, 0.0, 0.0), (-1.0, 0.0, 0.0)),
+        2.5,
+        (1.0, 0.0, 0.0),
+    );
+    //Through the hole the near side's inner edge is hit
+    assert_hit(
+        &torus,
+        &get_ray((0.0, 0.0, 0.0), (1.0, 0.0, 0.0)),
+        1.5,
+        (-1.0, 0.0, 0.0),
+    );
+    assert_miss(&torus, &get_ray((0.0, 5.0, 0.0), (0.0, -1.0, 0.0)));
+}
+
+#[test]
+fn instance() {
+    let sphere = Rc::new(Sphere::new(
+        Point3D::new(0.0, 0.0, 0.0),
+        1.0,
+        get_material(),
+    ));
+    let transform =
+        Transform::scale(2.0, 2.0, 2.0).then(&Transform::translate(Vector3D::new(0.0, 3.0, 0.0)));
+    let instance = Instance::new(sphere, transform);
+    assert_hit(
+        &instance,
+        &get_ray((0.0, 3.0, 10.0), (0.0, 0.0, -1.0)),
+        8.0,
+        (0.0, 0.0, 1.0),
+    );
+    assert_hit(
+        &instance,
+        &get_ray((5.0, 3.0, 0.0), (-1.0, 0.0, 0.0)),
+        3.0,
+        (1.0, 0.0, 0.0),
+    );
+    assert_miss(&instance, &get_ray((0.0, 0.0, 10.0), (0.0, 0.0, -1.0)));
+}
+
+#[test]
+fn csg() {
+    let get_spheres = || {
+        let left: Box<dyn GeometricObject> = Box::new(Sphere::new(
+            Point3D::new(0.0, 0.0, 0.0),
+            2.0,
+            get_material(),
+        ));
+        let right: Box<dyn GeometricObject> = Box::new(Sphere::new(
+            Point3D::new(0.0, 0.0, 2.0),
+            1.0,
+            get_material(),
+        ));
+        (left, right)
+    };
+    let ray = get_ray((0.0, 0.0, 10.0), (0.0, 0.0, -1.0));
+
+    let (left, right) = get_spheres();
+    assert_hit(&Csg::union(left, right), &ray, 7.0, (0.0, 0.0, 1.0));
+    let (left, right) = get_spheres();
+    assert_hit(&Csg::intersection(left, right), &ray, 8.0, (0.0, 0.0, 1.0));
+    //The bite taken out of the front leaves the inside of the small sphere, facing out
+    let (left, right) = get_spheres();
+    assert_hit(&Csg::difference(left, right), &ray, 9.0, (0.0, 0.0, 1.0));
+    let (left, right) = get_spheres();
+    assert_miss(
+        &Csg::intersection(left, right),
+        &get_ray((0.0, 1.5, 10.0), (0.0, 0.0, -1.0)),
+    );
+}
+
+///Volume whose maths broke down for every ray, its interval starts at NaN
+struct BrokenVolume {
+    material: Rc<dyn Material>,
+}
+
+impl GeometricObject for BrokenVolume {
+    fn check_hit(&self, _ray: &Ray) -> Option<HitInfo> {
+        None
+    }
+
+    fn get_material(&self) -> &dyn Material {
+        self.material.as_ref()
+    }
+
+    fn get_intervals(&self, ray: &Ray) -> Option<Vec<Interval>> {
+        let hit_at = |t: f64| HitInfo::new(t, Normal3D::new(0.0, 0.0, 1.0), ray.get_point_at(t));
+        Some(vec![Interval::new(hit_at(f64::NAN), hit_at(1.0))])
+    }
+}
+
+#[test]
+fn csg_ignores_nan_intervals() {
+    let sphere = || -> Box<dyn GeometricObject> {
+        Box::new(Sphere::new(
+            Point3D::new(0.0, 0.0, 0.0),
+            2.0,
+            get_material(),
+        ))
+    };
+    let broken = || -> Box<dyn GeometricObject> {
+        Box::new(BrokenVolume {
+            material: get_material(),
+        })
+    };
+    let ray = get_ray((0.0, 0.0, 10.0), (0.0, 0.0, -1.0));
+    assert_hit(&Csg::union(sphere(), broken()), &ray, 8.0, (0.0, 0.0, 1.0));
+    assert_hit(
+        &Csg::difference(sphere(), broken()),
+        &ray,
+        8.0,
+        (0.0, 0.0, 1.0),
+    );
+    assert_miss(&Csg::intersection(sphere(), broken()), &ray);
+}
+
+#[test]
+fn sdf() {
+    let sphere = SdfObject::new(
+        Sdf::sphere(1.0),
+        BoundingBox::new(Point3D::new(-1.5, -1.5, -1.5), Point3D::new(1.5, 1.5, 1.5)),
+        get_material(),
+    );
+    //Sphere tracing stops close to the surface rather than on it
+    assert_hit_within(
+        &sphere,
+        &get_ray((0.0, 0.0, 5.0), (0.0, 0.0, -1.0)),
+        4.0,
+        (0.0, 0.0, 1.0),
+        1e-3,
+    );
+    assert_miss(&sphere, &get_ray((1.5, 0.0, 5.0), (0.0, 0.0, -1.0)));
+}
+
+#[test]
+fn heightfield() {
+    //Ramp rising along x from height 0 to 1
+    let heights = [0.0, 1.0, 0.0, 1.0];
+    let heightfield = Heightfield::new(
+        &heights,
+        2,
+        2,
+        Point3D::new(0.0, 0.0, 0.0),
+        Vector3D::new(1.0, 1.0, 1.0),
+        get_material(),
+    );
+    assert_hit(
+        &heightfield,
+        &get_ray((0.5, 5.0, 0.5), (0.0, -1.0, 0.0)),
+        4.5,
+        (-1.0, 1.0, 0.0),
+    );
+    assert_miss(&heightfield, &get_ray((1.5, 5.0, 0.5), (0.0, -1.0, 0.0)));
+}
+
+#[test]
+fn heightfield_from_image() {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/target/heightfield");
+    std::fs::create_dir_all(dir).unwrap();
+    let load = |width: u32, height: u32| {
+        let path = format!("{}/{}x{}.png", dir, width, height);
+        image::GrayImage::from_fn(width, height, |x, _| image::Luma([(x.min(1) * 255) as u8]))
+            .save(&path)
+            .unwrap();
+        Heightfield::from_image(
+            &path,
+            Point3D::new(0.0, 0.0, 0.0),
+            Vector3D::new(1.0, 1.0, 1.0),
+            get_material(),
+        )
+    };
+    //The same ramp as above
+    let heightfield = load(2, 2).unwrap();
+    assert_hit(
+        &heightfield,
+        &get_ray((0.5, 5.0, 0.5), (0.0, -1.0, 0.0)),
+        4.5,
+        (-1.0, 1.0, 0.0),
+    );
+    for &(width, height) in &[(1, 5), (5, 1)] {
+        match load(width, height) {
+            Err(image::ImageError::DimensionError) => {}
+            Err(e) => panic!("{}x{}: {}", width, height, e),
+            Ok(_) => panic!("{}x{} loaded", width, height),
+        }
+    }
+}
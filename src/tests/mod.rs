@@ -0,0 +1,14 @@
+mod animation;
+mod aov;
+mod checkpoint;
+mod denoise;
+mod filter;
+mod furnace;
+mod integrators;
+mod intersection;
+mod progress;
+mod regression;
+mod sampling;
+mod scene;
+mod sequence;
+mod termination;
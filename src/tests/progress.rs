@@ -0,0 +1,110 @@
+//! Progress reports and cancellation of in-memory renders
+
+use crate::graphics::*;
+use crate::shapes::*;
+use crate::tracer::{CancelToken, RenderBudget, SimpleTracer};
+use std::rc::Rc;
+
+fn get_world(_t: f64) -> World {
+    let mut world = World::new(
+        ViewPlane::new(80, 40, 1.0 / 16.0, 4),
+        RGBColor::new(0.0, 0.0, 0.0),
+    );
+    world.get_objects_mut().push(Box::new(Sphere::new(
+        Point3D::new(0.0, 0.0, 0.0),
+        1.0,
+        Rc::new(LambertianMaterial::new(RGBColor::new(0.5, 0.5, 0.5))),
+    )));
+    world
+}
+
+#[test]
+fn reports_every_tile() {
+    let budget = RenderBudget::new().with_max_samples(3);
+    let mut reports = Vec::new();
+    let film =
+        SimpleTracer::new().render_to_film(Box::new(get_world), 0.0, &budget, &mut |progress| {
+            assert!(progress.eta.is_some());
+            reports.push((progress.samples, progress.tiles_done, progress.tiles));
+        });
+
+    //80x40 pixels make 3x2 tiles of 32 pixels
+    let expected: Vec<(u32, usize, usize)> = (0..3)
+        .flat_map(|pass| {
+            (1..=6).map(move |tile| (if tile == 6 { pass + 1 } else { pass }, tile, 6))
+        })
+        .collect();
+    assert_eq!(reports, expected);
+    assert_eq!(film.get_pixels().len(), 80 * 40);
+    assert_eq!(film.get_samples(79, 39), 3);
+}
+
+#[test]
+fn eta_counts_down_to_zero() {
+    let budget = RenderBudget::new().with_max_samples(2);
+    let mut last_eta = None;
+    SimpleTracer::new().render_to_film(Box::new(get_world), 0.0, &budget, &mut |progress| {
+        last_eta = progress.eta;
+    });
+    assert_eq!(last_eta, Some(std::time::Duration::from_secs(0)));
+}
+
+#[test]
+fn cancel_stops_the_render() {
+    let cancel = CancelToken::new();
+    //No limit, the render only ends when it's cancelled
+    let budget = RenderBudget::new().with_cancel_token(cancel.clone());
+    let mut reports = 0;
+    let film =
+        SimpleTracer::new().render_to_film(Box::new(get_world), 0.0, &budget, &mut |progress| {
+            reports += 1;
+            if progress.samples == 2 {
+                cancel.cancel();
+            }
+        });
+    assert!(cancel.is_cancelled());
+    //Cancelled right after the second pass, before any tile of the third came back
+    assert_eq!(reports, 12);
+    assert_eq!(film.get_samples(0, 0), 2);
+}
+
+#[test]
+fn cancel_in_the_middle_of_a_pass() {
+    let cancel = CancelToken::new();
+    let budget = RenderBudget::new()
+        .with_max_samples(100)
+        .with_cancel_token(cancel.clone());
+    let mut reports = 0;
+    let film = SimpleTracer::new().render_to_film(Box::new(get_world), 0.0, &budget, &mut |_| {
+        reports += 1;
+        cancel.cancel();
+    });
+    //Tiles other threads finished before noticing may still be merged, none of the
+    //next pass is started
+    assert!(reports <= 6);
+    for y in 0..film.get_height() {
+        for x in 0..film.get_width() {
+            assert!(film.get_samples(x, y) <= 1);
+        }
+    }
+}
+
+///The worker threads are the only ones without a name
+fn get_world_panicking_on_workers(t: f64) -> World {
+    if std::thread::current().name().is_none() {
+        panic!("broken scene");
+    }
+    get_world(t)
+}
+
+#[test]
+#[should_panic(expected = "broken scene")]
+fn worker_panics_reach_the_caller() {
+    let budget = RenderBudget::new().with_max_samples(1);
+    SimpleTracer::new().with_threads(2).render_to_film(
+        Box::new(get_world_panicking_on_workers),
+        0.0,
+        &budget,
+        &mut |_| {},
+    );
+}
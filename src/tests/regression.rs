@@ -0,0 +1,295 @@
+//! Renders small canonical scenes with a fixed seed and compares them with the reference
+//! images in tests/references
+//!
+//! Renders and, on failure, diff images are written to target/regression. Run with
+//! UPDATE_REFERENCES=1 to store the current renders as the new references after an
+//! intended change in the output.
+
+use crate::graphics::*;
+use crate::shapes::*;
+use crate::tracer::{RenderBudget, SimpleTracer};
+use image::{ImageBuffer, Rgb, RgbImage};
+use std::rc::Rc;
+
+const REFERENCE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/references");
+const OUTPUT_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/target/regression");
+///Root mean square difference of the 8 bit channels, leaves room for floating point
+///differences between platforms
+const MAX_RMSE: f64 = 1.0;
+///Channel difference above which a pixel counts as visibly changed
+const VISIBLE_DIFFERENCE: u8 = 24;
+///Fraction of the pixels which may change visibly
+const MAX_CHANGED_PIXELS: f64 = 0.002;
+const SEED: u64 = 1;
+
+struct Comparison {
+    rmse: f64,
+    changed_pixels: f64,
+    ///Channel differences magnified 8 times
+    diff: RgbImage,
+}
+
+fn compare(rendered: &RgbImage, reference: &RgbImage) -> Comparison {
+    let (width, height) = rendered.dimensions();
+    let mut diff = ImageBuffer::new(width, height);
+    let (mut sum_sqr, mut changed) = (0.0, 0);
+    for (x, y, pixel) in rendered.enumerate_pixels() {
+        let other = reference.get_pixel(x, y);
+        let mut difference = [0u8; 3];
+        for (channel, d) in difference.iter_mut().enumerate() {
+            let channel_difference =
+                (i32::from(pixel.data[channel]) - i32::from(other.data[channel])).abs();
+            sum_sqr += f64::from(channel_difference * channel_difference);
+            *d = channel_difference as u8;
+        }
+        if difference.iter().any(|d| *d > VISIBLE_DIFFERENCE) {
+            changed += 1;
+        }
+        diff.put_pixel(
+            x,
+            y,
+            Rgb {
+                data: difference.map(|d| d.saturating_mul(8)),
+            },
+        );
+    }
+    let pixels = f64::from(width * height);
+    Comparison {
+        rmse: (sum_sqr / (3.0 * pixels)).sqrt(),
+        changed_pixels: f64::from(changed) / pixels,
+        diff,
+    }
+}
+
+fn check_scene(name: &str, worldfunc: fn(f64) -> World) {
+    std::fs::create_dir_all(OUTPUT_DIR).unwrap();
+    let output_path = format!("{}/{}.png", OUTPUT_DIR, name);
+    let rendered = SimpleTracer::new()
+        .render(Box::new(worldfunc), &output_path, 0.0, &mut |_| {})
+        .unwrap()
+        .to_image()
+        .to_rgb();
+
+    let reference_path = format!("{}/{}.png", REFERENCE_DIR, name);
+    if std::env::var_os("UPDATE_REFERENCES").is_some() {
+        rendered.save(&reference_path).unwrap();
+        return;
+    }
+    let reference = match image::open(&reference_path) {
+        Ok(reference) => reference.to_rgb(),
+        Err(e) => panic!(
+            "{}: {}, run with UPDATE_REFERENCES=1 to create it",
+            reference_path, e
+        ),
+    };
+    assert_eq!(
+        rendered.dimensions(),
+        reference.dimensions(),
+        "{} has changed size",
+        name
+    );
+
+    let comparison = compare(&rendered, &reference);
+    if comparison.rmse > MAX_RMSE || comparison.changed_pixels > MAX_CHANGED_PIXELS {
+        let diff_path = format!("{}/{}.diff.png", OUTPUT_DIR, name);
+        comparison.diff.save(&diff_path).unwrap();
+        panic!(
+            "{} differs from its reference: RMSE {:.3}, {:.2}% of pixels changed visibly, see {}",
+            name,
+            comparison.rmse,
+            comparison.changed_pixels * 100.0,
+            diff_path
+        );
+    }
+}
+
+///Pinhole at (0,0,10) looking down -z, like the main scene
+fn get_view_plane(hres: u32, vres: u32, pixel_size: f64, samples: u32) -> ViewPlane {
+    ViewPlane::new(hres, vres, pixel_size, samples).with_seed(SEED)
+}
+
+fn get_ground() -> Sphere {
+    let grey = Rc::new(LambertianMaterial::new(RGBColor::new(0.5, 0.5, 0.5)));
+    Sphere::new(Point3D::new(0.0, -42.0, 0.0), 40.0, grey)
+}
+
+fn get_spheres(_t: f64) -> World {
+    let mut world = World::new(
+        get_view_plane(64, 48, 1.0 / 12.0, 16),
+        RGBColor::new(0.0, 0.0, 0.0),
+    );
+    let objects = world.get_objects_mut();
+    objects.push(Box::new(get_ground()));
+    let albedos = [
+        RGBColor::new(0.8, 0.2, 0.2),
+        RGBColor::new(0.2, 0.8, 0.2),
+        RGBColor::new(0.2, 0.2, 0.8),
+    ];
+    for (i, albedo) in albedos.iter().enumerate() {
+        objects.push(Box::new(Sphere::new(
+            Point3D::new(2.2 * (i as f64 - 1.0), -1.0, 0.0),
+            1.0,
+            Rc::new(LambertianMaterial::new(*albedo)),
+        )));
+    }
+    world
+}
+
+fn get_glass(_t: f64) -> World {
+    let mut world = World::new(
+        get_view_plane(64, 48, 1.0 / 12.0, 16),
+        RGBColor::new(0.0, 0.0, 0.0),
+    );
+    let objects = world.get_objects_mut();
+    objects.push(Box::new(get_ground()));
+    objects.push(Box::new(Sphere::new(
+        Point3D::new(0.0, -1.0, 1.0),
+        1.0,
+        Rc::new(Dielectric::new(1.5)),
+    )));
+    objects.push(Box::new(Sphere::new(
+        Point3D::new(-1.5, -1.0, -3.0),
+        1.0,
+        Rc::new(LambertianMaterial::new(RGBColor::new(0.9, 0.6, 0.1))),
+    )));
+    objects.push(Box::new(Sphere::new(
+        Point3D::new(1.5, -1.0, -3.0),
+        1.0,
+        Rc::new(LambertianMaterial::new(RGBColor::new(0.1, 0.6, 0.9))),
+    )));
+    world
+}
+
+fn get_metals(_t: f64) -> World {
+    let mut world = World::new(
+        get_view_plane(64, 48, 1.0 / 12.0, 16),
+        RGBColor::new(0.0, 0.0, 0.0),
+    );
+    let objects = world.get_objects_mut();
+    objects.push(Box::new(get_ground()));
+    for (i, fuzziness) in [0.0, 0.3, 0.8].iter().enumerate() {
+        objects.push(Box::new(Sphere::new(
+            Point3D::new(2.2 * (i as f64 - 1.0), -1.0, 0.0),
+            1.0,
+            Rc::new(MetallicMaterial::new(
+                RGBColor::new(0.8, 0.8, 0.8),
+                *fuzziness,
+            )),
+        )));
+    }
+    objects.push(Box::new(Sphere::new(
+        Point3D::new(0.0, -1.5, 2.5),
+        0.5,
+        Rc::new(LambertianMaterial::new(RGBColor::new(0.9, 0.1, 0.1))),
+    )));
+    world
+}
+
+///Box open towards the camera lit only by a light in its ceiling
+pub(super) fn get_cornell_box(_t: f64) -> World {
+    let mut world = World::new(
+        get_view_plane(64, 64, 1.0 / 36.0, 64),
+        RGBColor::new(0.0, 0.0, 0.0),
+    )
+    .with_sky(false);
+    let white = Rc::new(LambertianMaterial::new(RGBColor::new(0.73, 0.73, 0.73)));
+    let red = Rc::new(LambertianMaterial::new(RGBColor::new(0.65, 0.05, 0.05)));
+    let green = Rc::new(LambertianMaterial::new(RGBColor::new(0.12, 0.45, 0.15)));
+    let light = Rc::new(DiffuseLight::new(RGBColor::new(10.0, 10.0, 10.0)));
+    let (x, y, z) = (
+        Vector3D::new(4.0, 0.0, 0.0),
+        Vector3D::new(0.0, 4.0, 0.0),
+        Vector3D::new(0.0, 0.0, -4.0),
+    );
+
+    //Walls spanning x and y in [-2,2] and z in [-6,-2], with their normals facing inwards
+    let objects = world.get_objects_mut();
+    objects.push(Box::new(Rectangle::new(
+        Point3D::new(-2.0, -2.0, -2.0),
+        x,
+        z,
+        white.clone(),
+    )));
+    objects.push(Box::new(Rectangle::new(
+        Point3D::new(-2.0, 2.0, -2.0),
+        z,
+        x,
+        white.clone(),
+    )));
+    objects.push(Box::new(Rectangle::new(
+        Point3D::new(-2.0, -2.0, -6.0),
+        x,
+        y,
+        white.clone(),
+    )));
+    objects.push(Box::new(Rectangle::new(
+        Point3D::new(-2.0, -2.0, -2.0),
+        z,
+        y,
+        red,
+    )));
+    objects.push(Box::new(Rectangle::new(
+        Point3D::new(2.0, -2.0, -2.0),
+        y,
+        z,
+        green,
+    )));
+    objects.push(Box::new(Rectangle::new(
+        Point3D::new(-1.0, 1.99, -3.0),
+        Vector3D::new(0.0, 0.0, -2.0),
+        Vector3D::new(2.0, 0.0, 0.0),
+        light,
+    )));
+    objects.push(Box::new(AxisAlignedBox::new(
+        Point3D::new(-1.3, -2.0, -5.0),
+        Point3D::new(-0.2, 0.4, -3.8),
+        white.clone(),
+    )));
+    objects.push(Box::new(OrientedBox::new(
+        Point3D::new(0.8, -1.4, -3.2),
+        Vector3D::new(0.6, 0.6, 0.6),
+        Vector3D::new(1.0, 0.0, 0.4),
+        Vector3D::new(0.0, 1.0, 0.0),
+        white,
+    )));
+    world
+}
+
+#[test]
+fn spheres() {
+    check_scene("spheres", get_spheres);
+}
+
+#[test]
+fn glass() {
+    check_scene("glass", get_glass);
+}
+
+#[test]
+fn metals() {
+    check_scene("metals", get_metals);
+}
+
+#[test]
+fn cornell_box() {
+    check_scene("cornell_box", get_cornell_box);
+}
+
+#[test]
+fn same_image_with_any_number_of_threads() {
+    let render = |threads: usize| {
+        SimpleTracer::new().with_threads(threads).render_to_film(
+            Box::new(get_spheres),
+            0.0,
+            &RenderBudget::new().with_max_samples(4),
+            &mut |_| {},
+        )
+    };
+    let bits = |pixels: Vec<RGBColor>| -> Vec<[u64; 3]> {
+        pixels
+            .iter()
+            .map(|c| [c.r.to_bits(), c.g.to_bits(), c.b.to_bits()])
+            .collect()
+    };
+    assert!(bits(render(1).get_pixels()) == bits(render(4).get_pixels()));
+}
@@ -0,0 +1,406 @@
+//! Statistical tests of the directions materials scatter into
+//!
+//! The directions drawn by `Material::process` are binned over the sphere and compared
+//! with the counts expected from `Material::pdf` by a chi-square test. The seeds are
+//! fixed so the tests are deterministic, the significance level only guards against
+//! a seed which happens to be unlucky.
+//!
+//! The samplers are checked for the stratification their sequences promise.
+
+use crate::graphics::*;
+use std::f64::consts::PI;
+
+const SEED: u64 = 3;
+const SAMPLES: u32 = 200_000;
+///Bins of cos(theta) and phi
+const THETA_BINS: usize = 16;
+const PHI_BINS: usize = 32;
+///Each bin's expected count is integrated on a grid of this many cells per side
+const SUBDIVISIONS: usize = 8;
+///Bins expected to get fewer samples are pooled, the chi-square statistic is off for them
+const MIN_EXPECTED: f64 = 5.0;
+const SIGNIFICANCE: f64 = 1e-3;
+
+///45 degrees onto a surface facing +z
+fn get_incoming() -> (Ray, HitInfo) {
+    let ray = Ray::new(Point3D::new(-1.0, 0.0, 1.0), Vector3D::new(1.0, 0.0, -1.0));
+    let hit_info = HitInfo::new(
+        1.0,
+        Normal3D::new(0.0, 0.0, 1.0),
+        Point3D::new(0.0, 0.0, 0.0),
+    );
+    (ray, hit_info)
+}
+
+fn get_bin(direction: &Vector3D) -> usize {
+    let direction = direction.normalize();
+    let theta_bin = ((direction.z() + 1.0) * 0.5 * THETA_BINS as f64) as usize;
+    let phi = direction.y().atan2(direction.x()) + PI;
+    let phi_bin = (phi / (2.0 * PI) * PHI_BINS as f64) as usize;
+    theta_bin.min(THETA_BINS - 1) * PHI_BINS + phi_bin.min(PHI_BINS - 1)
+}
+
+///Direction at the given cos(theta) and phi, the inverse of get_bin
+fn get_direction(cos_theta: f64, phi: f64) -> Vector3D {
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = phi - PI;
+    Vector3D::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+}
+
+fn get_observed(material: &dyn Material, ray: &Ray, hit_info: &HitInfo) -> Vec<f64> {
+    let mut sampler = SamplerKind::Independent.create(1, SEED);
+    let mut observed = vec![0.0; THETA_BINS * PHI_BINS];
+    for sample in 0..SAMPLES {
+        sampler.start_pixel_sample((0, 0), sample);
+        let (ray_out, _) = material
+            .process(ray, hit_info, sampler.as_mut())
+            .expect("material absorbed the ray");
+        observed[get_bin(&ray_out.d)] += 1.0;
+    }
+    observed
+}
+
+///Integrates the pdf over each bin by the midpoint rule on a grid of subdivisions cells
+///per side, the area element of (cos(theta),phi) is the solid angle
+fn integrate_bins(
+    material: &dyn Material,
+    ray: &Ray,
+    hit_info: &HitInfo,
+    subdivisions: usize,
+) -> Vec<f64> {
+    let (cell_cos, cell_phi) = (
+        2.0 / (THETA_BINS * subdivisions) as f64,
+        2.0 * PI / (PHI_BINS * subdivisions) as f64,
+    );
+    let mut bins = vec![0.0; THETA_BINS * PHI_BINS];
+    for i in 0..THETA_BINS * subdivisions {
+        for j in 0..PHI_BINS * subdivisions {
+            let cos_theta = -1.0 + (i as f64 + 0.5) * cell_cos;
+            let phi = (j as f64 + 0.5) * cell_phi;
+            let pdf = material
+                .pdf(ray, hit_info, &get_direction(cos_theta, phi))
+                .expect("material has no pdf");
+            bins[(i / subdivisions) * PHI_BINS + j / subdivisions] += pdf * cell_cos * cell_phi;
+        }
+    }
+    bins
+}
+
+///Complementary error function, after Numerical Recipes, relative error below 1.2e-7
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let coefficients = [
+        -1.265_512_23,
+        1.000_023_68,
+        0.374_091_96,
+        0.096_784_18,
+        -0.186_288_06,
+        0.278_868_07,
+        -1.135_203_98,
+        1.488_515_87,
+        -0.822_152_23,
+        0.170_872_77,
+    ];
+    let polynomial = coefficients.iter().rev().fold(0.0, |sum, c| sum * t + c);
+    let result = t * (-z * z + polynomial).exp();
+    if x >= 0.0 {
+        result
+    } else {
+        2.0 - result
+    }
+}
+
+///Probability of a chi-square statistic at least this large, by the Wilson-Hilferty
+///approximation which is good for the hundreds of degrees of freedom used here
+fn get_p_value(chi_square: f64, degrees_of_freedom: usize) -> f64 {
+    let k = degrees_of_freedom as f64;
+    let variance = 2.0 / (9.0 * k);
+    let z = ((chi_square / k).cbrt() - (1.0 - variance)) / variance.sqrt();
+    0.5 * erfc(z / 2.0f64.sqrt())
+}
+
+fn chi_square_test(name: &str, material: &dyn Material) {
+    let (ray, hit_info) = get_incoming();
+    let observed = get_observed(material, &ray, &hit_info);
+    let expected: Vec<f64> = integrate_bins(material, &ray, &hit_info, SUBDIVISIONS)
+        .iter()
+        .map(|p| p * f64::from(SAMPLES))
+        .collect();
+
+    //The pdf must integrate to one, or the counts can't match
+    let total: f64 = expected.iter().sum();
+    assert!(
+        (total / f64::from(SAMPLES) - 1.0).abs() < 1e-3,
+        "{}: pdf integrates to {}",
+        name,
+        total / f64::from(SAMPLES)
+    );
+
+    let (mut chi_square, mut bins) = (0.0, 0);
+    let (mut pooled_observed, mut pooled_expected) = (0.0, 0.0);
+    for (o, e) in observed.iter().zip(expected.iter()) {
+        if *e < MIN_EXPECTED {
+            pooled_observed += o;
+            pooled_expected += e;
+        } else {
+            chi_square += (o - e) * (o - e) / e;
+            bins += 1;
+        }
+    }
+    if pooled_expected > 0.0 {
+        chi_square += (pooled_observed - pooled_expected).powi(2) / pooled_expected;
+        bins += 1;
+    }
+    let p_value = get_p_value(chi_square, bins - 1);
+    assert!(
+        p_value > SIGNIFICANCE,
+        "{}: chi-square {:.1} with {} degrees of freedom, p={:.2e}",
+        name,
+        chi_square,
+        bins - 1,
+        p_value
+    );
+}
+
+#[test]
+fn lambertian() {
+    let material = LambertianMaterial::new(RGBColor::new(0.5, 0.5, 0.5));
+    chi_square_test("lambertian", &material);
+}
+
+///Below a fuzziness of 1 the density has an integrable singularity at the edge of the
+///lobe, which a grid can't integrate well enough for the chi-square test
+#[test]
+fn fuzzy_metal() {
+    for fuzziness in [1.0, 2.0].iter() {
+        let material = MetallicMaterial::new(RGBColor::new(0.5, 0.5, 0.5), *fuzziness);
+        chi_square_test(&format!("metal with fuzziness {}", fuzziness), &material);
+    }
+}
+
+#[test]
+fn fuzzy_metal_pdf_integrates_to_one() {
+    let (ray, hit_info) = get_incoming();
+    for fuzziness in [0.3, 0.8].iter() {
+        let material = MetallicMaterial::new(RGBColor::new(0.5, 0.5, 0.5), *fuzziness);
+        let total: f64 = integrate_bins(&material, &ray, &hit_info, 128).iter().sum();
+        assert!(
+            (total - 1.0).abs() < 1e-2,
+            "fuzziness {}: pdf integrates to {}",
+            fuzziness,
+            total
+        );
+    }
+}
+
+///The refracted direction obeys Snell's law and has unit length
+#[test]
+fn refraction() {
+    let normal = Normal3D::new(0.0, 0.0, 1.0);
+    for i in 1..9 {
+        let theta_in = f64::from(i) * 10f64.to_radians();
+        for refractive_index in [1.0 / 1.5, 1.0 / 1.33, 1.33].iter() {
+            let ray = Ray::new(
+                Point3D::new(0.0, 0.0, 1.0),
+                Vector3D::new(theta_in.sin(), 0.0, -theta_in.cos()),
+            );
+            let sin_out = theta_in.sin() * refractive_index;
+            match refract2(&ray, &normal, *refractive_index) {
+                Some(d) => {
+                    assert!(sin_out <= 1.0, "refracted beyond the critical angle");
+                    assert!(
+                        (d.magnitude_sqr() - 1.0).abs() < 1e-9,
+                        "length {}",
+                        d.magnitude_sqr()
+                    );
+                    assert!(
+                        (d.x() - sin_out).abs() < 1e-9,
+                        "sin {} expected {}",
+                        d.x(),
+                        sin_out
+                    );
+                    assert!(d.z() < 0.0 && d.y() == 0.0);
+                }
+                None => assert!(
+                    sin_out > 1.0,
+                    "total internal reflection below the critical angle"
+                ),
+            }
+        }
+    }
+}
+
+///Glass hit from outside reflects with Schlick's probability and refracts otherwise
+#[test]
+fn dielectric_reflectance() {
+    let (ray, hit_info) = get_incoming();
+    let refractive_index = 1.5;
+    let material = Dielectric::new(refractive_index);
+    let mut sampler = SamplerKind::Independent.create(1, SEED);
+    let mut reflected = 0;
+    for sample in 0..SAMPLES {
+        sampler.start_pixel_sample((0, 0), sample);
+        let (ray_out, attenuation) = material.process(&ray, &hit_info, sampler.as_mut()).unwrap();
+        assert_eq!(
+            (attenuation.r, attenuation.g, attenuation.b),
+            (1.0, 1.0, 1.0)
+        );
+        if ray_out.d.z() > 0.0 {
+            reflected += 1;
+        }
+    }
+
+    let cosine = 0.5f64.sqrt();
+    let r0 = ((1.0 - refractive_index) / (1.0 + refractive_index)).powi(2);
+    let reflectance = r0 + (1.0 - r0) * (1.0 - cosine).powi(5);
+    //Normal approximation of the binomial distribution, more than 4 standard deviations
+    //out happens about once in 16000 tries
+    let n = f64::from(SAMPLES);
+    let deviation = (n * reflectance * (1.0 - reflectance)).sqrt();
+    assert!(
+        (f64::from(reflected) - n * reflectance).abs() < 4.0 * deviation,
+        "{} of {} reflected, expected {:.0}",
+        reflected,
+        SAMPLES,
+        n * reflectance
+    );
+}
+
+///Asserts no two points share a cell of a grid with the given numbers of cells along x and y
+fn assert_one_per_cell(name: &str, points: &[(f64, f64)], cells: (u32, u32)) {
+    let mut seen = vec![false; (cells.0 * cells.1) as usize];
+    for &(x, y) in points {
+        assert!(
+            (0.0..1.0).contains(&x) && (0.0..1.0).contains(&y),
+            "{}: ({}, {})",
+            name,
+            x,
+            y
+        );
+        let cell = (x * f64::from(cells.0)) as u32 + cells.0 * (y * f64::from(cells.1)) as u32;
+        assert!(
+            !seen[cell as usize],
+            "{}: two points in a cell of {}x{}",
+            name, cells.0, cells.1
+        );
+        seen[cell as usize] = true;
+    }
+}
+
+#[test]
+fn stratified_sampler_fills_every_stratum() {
+    for &samples_per_pixel in &[1, 7, 16, 100] {
+        let mut sampler = StratifiedSampler::new(samples_per_pixel, SEED);
+        for &pixel in &[(0, 0), (5, 3)] {
+            let points: Vec<Vec<f64>> = (0..samples_per_pixel)
+                .map(|sample| {
+                    sampler.start_pixel_sample(pixel, sample);
+                    let (u, v) = sampler.get_2d();
+                    vec![sampler.get_1d(), u, v, sampler.get_1d()]
+                })
+                .collect();
+            for dimension in 0..4 {
+                let column: Vec<(f64, f64)> =
+                    points.iter().map(|point| (point[dimension], 0.0)).collect();
+                assert_one_per_cell("stratified", &column, (samples_per_pixel, 1));
+            }
+        }
+    }
+}
+
+///The first 2^k points of a (0,2)-sequence in base 2 have one point in each elementary
+///interval of area 2^-k, the cells of 2^a by 2^(k-a)
+#[test]
+fn sobol_points_fill_elementary_intervals() {
+    let mut sampler = SobolSampler::new(SEED);
+    for &pixel in &[(0, 0), (17, 4)] {
+        for k in 0..=10 {
+            let n = 1 << k;
+            let points: Vec<(f64, f64)> = (0..n)
+                .map(|sample| {
+                    sampler.start_pixel_sample(pixel, sample);
+                    sampler.get_2d()
+                })
+                .collect();
+            for a in 0..=k {
+                assert_one_per_cell("sobol", &points, (1 << a, 1 << (k - a)));
+            }
+        }
+    }
+}
+
+///The Halton points are shifted by a random offset per pixel, which is where sample 0 lands,
+///so the intervals are checked on the points moved back by the offset. The first
+///2^a 3^b points have one point in each cell of 2^a by 3^b.
+#[test]
+fn halton_points_fill_elementary_intervals() {
+    let mut sampler = HaltonSampler::new(SEED);
+    for &pixel in &[(0, 0), (17, 4)] {
+        sampler.start_pixel_sample(pixel, 0);
+        let offset = sampler.get_2d();
+        //Points on a cell's lower edge must not fall below it by rounding
+        let unshift = |x: f64, offset: f64| (x - offset + 1e-9).rem_euclid(1.0);
+        for a in 0..=6 {
+            for b in 0..=4 {
+                let n = (1u32 << a) * 3u32.pow(b);
+                let points: Vec<(f64, f64)> = (0..n)
+                    .map(|sample| {
+                        sampler.start_pixel_sample(pixel, sample);
+                        let (x, y) = sampler.get_2d();
+                        (unshift(x, offset.0), unshift(y, offset.1))
+                    })
+                    .collect();
+                assert_one_per_cell("halton", &points, (1 << a, 3u32.pow(b)));
+            }
+        }
+    }
+}
+
+///Blue noise has little low frequency energy, so the means of blocks of the mask stay much
+///closer to 1/2 than those of white noise, whose variance is 1/12 over the block's pixels
+#[test]
+fn blue_noise_mask_lacks_low_frequencies() {
+    let (size, block) = (32, 4);
+    let mask = get_blue_noise_mask(size, SEED);
+    let mut ranks = mask.clone();
+    ranks.sort_unstable();
+    assert!(ranks.iter().enumerate().all(|(i, &rank)| rank == i as u32));
+    let mut variance = 0.0;
+    for block_y in 0..size / block {
+        for block_x in 0..size / block {
+            let mut mean = 0.0;
+            for y in block_y * block..(block_y + 1) * block {
+                for x in block_x * block..(block_x + 1) * block {
+                    mean +=
+                        (f64::from(mask[(x + y * size) as usize]) + 0.5) / f64::from(size * size);
+                }
+            }
+            mean /= f64::from(block * block);
+            variance += (mean - 0.5) * (mean - 0.5);
+        }
+    }
+    variance /= f64::from((size / block) * (size / block));
+    let white_noise_variance = 1.0 / 12.0 / f64::from(block * block);
+    assert!(variance < 0.25 * white_noise_variance, "{}", variance);
+}
+
+///Over a tile of the mask the first samples of the pixels are the same point shifted by
+///every multiple of 1/pixels, so each falls in its own interval of that width
+#[test]
+fn blue_noise_sampler_shifts_pixels_by_the_mask() {
+    let mut sampler = BlueNoiseSampler::new(SEED);
+    let size = 32;
+    for dimension in 0..3 {
+        let points: Vec<(f64, f64)> = (0..size * size)
+            .map(|i| {
+                sampler.start_pixel_sample((i % size + 7, i / size + 3), 0);
+                for _ in 0..dimension {
+                    sampler.get_1d();
+                }
+                (sampler.get_1d(), 0.0)
+            })
+            .collect();
+        assert_one_per_cell("blue noise", &points, (size * size, 1));
+    }
+}
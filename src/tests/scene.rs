@@ -0,0 +1,104 @@
+//! Scene graphs: paths, transforms down the hierarchy and flattening into a world
+
+use crate::graphics::*;
+use crate::scene::SceneNode;
+use crate::shapes::Sphere;
+use std::f64::consts::FRAC_PI_2;
+use std::rc::Rc;
+
+fn get_material(albedo: f64) -> Rc<dyn Material> {
+    Rc::new(LambertianMaterial::new(RGBColor::new(
+        albedo, albedo, albedo,
+    )))
+}
+
+fn get_unit_sphere() -> Rc<dyn GeometricObject> {
+    Rc::new(Sphere::new(Point3D::origin(), 1.0, get_material(0.5)))
+}
+
+fn assert_point(p: Point3D, expected: (f64, f64, f64)) {
+    let expected = Point3D::new(expected.0, expected.1, expected.2);
+    assert!(
+        (p - expected).magnitude_sqr() < 1e-18,
+        "{:?} != {:?}",
+        p,
+        expected
+    );
+}
+
+///A robot lifted by 3 whose arm turns a quarter about z and whose hand sits 2 along the arm
+fn get_robot() -> SceneNode {
+    SceneNode::new("robot")
+        .with_transform(Transform::translate(Vector3D::new(0.0, 3.0, 0.0)))
+        .with_child(
+            SceneNode::new("arm")
+                .with_transform(Transform::rotate_z(FRAC_PI_2))
+                .with_child(
+                    SceneNode::new("hand")
+                        .with_transform(Transform::translate(Vector3D::new(2.0, 0.0, 0.0)))
+                        .with_object(get_unit_sphere()),
+                ),
+        )
+        .with_child(SceneNode::new("head").with_object(get_unit_sphere()))
+}
+
+#[test]
+fn find_resolves_paths() {
+    let robot = get_robot();
+    assert_eq!(robot.find("arm").unwrap().get_name(), "arm");
+    assert_eq!(robot.find("arm/hand").unwrap().get_name(), "hand");
+    assert_eq!(robot.find("/arm//hand/").unwrap().get_name(), "hand");
+    assert_eq!(robot.find("").unwrap().get_name(), "robot");
+    assert!(robot.find("hand").is_none());
+    assert!(robot.find("arm/foot").is_none());
+
+    let mut robot = robot;
+    robot.find_mut("head").unwrap().set_visible(false);
+    assert!(!robot.find("head").unwrap().is_visible());
+}
+
+#[test]
+fn nested_transforms_compose_from_the_leaf_up() {
+    //The hand is moved along the arm, the arm turned and then the whole robot lifted
+    let robot = get_robot();
+    let hand = robot.get_world_transform("arm/hand", 0.0).unwrap();
+    assert_point(hand.transform_point(&Point3D::origin()), (0.0, 5.0, 0.0));
+    assert_point(
+        hand.transform_point(&Point3D::new(1.0, 0.0, 0.0)),
+        (0.0, 6.0, 0.0),
+    );
+    assert!(robot.get_world_transform("arm/foot", 0.0).is_none());
+
+    let mut world = World::new(ViewPlane::new(1, 1, 1.0, 1), RGBColor::new(0.0, 0.0, 0.0));
+    robot.flatten_into(&mut world);
+    assert_eq!(world.get_objects().len(), 2);
+    let ray = Ray::new(Point3D::new(0.0, 5.0, 10.0), Vector3D::new(0.0, 0.0, -1.0));
+    let (index, hit_info) = world.find_hit(&ray).unwrap();
+    assert_eq!(index, 0);
+    assert!((hit_info.get_tmin() - 9.0).abs() < 1e-9);
+}
+
+#[test]
+fn flatten_hides_nodes_and_overrides_materials() {
+    let (dark, light) = (get_material(0.1), get_material(0.9));
+    let mut robot = get_robot().with_material(dark.clone());
+    robot
+        .find_mut("arm/hand")
+        .unwrap()
+        .set_material(Some(light.clone()));
+
+    let is = |object: &dyn GeometricObject, material: &Rc<dyn Material>| {
+        std::ptr::addr_eq(object.get_material(), Rc::as_ptr(material))
+    };
+    let mut world = World::new(ViewPlane::new(1, 1, 1.0, 1), RGBColor::new(0.0, 0.0, 0.0));
+    robot.flatten_into(&mut world);
+    //The hand overrides the robot's material again, the head keeps the robot's
+    assert!(is(world.get_objects()[0].as_ref(), &light));
+    assert!(is(world.get_objects()[1].as_ref(), &dark));
+
+    robot.find_mut("arm").unwrap().set_visible(false);
+    let mut world = World::new(ViewPlane::new(1, 1, 1.0, 1), RGBColor::new(0.0, 0.0, 0.0));
+    robot.flatten_into(&mut world);
+    assert_eq!(world.get_objects().len(), 1);
+    assert!(is(world.get_objects()[0].as_ref(), &dark));
+}
@@ -0,0 +1,65 @@
+//! Frame paths and the skipping and resuming of frame sequences
+
+use crate::graphics::*;
+use crate::sequence::{expand_pattern, render_sequence, SequenceSettings};
+use crate::tracer::SimpleTracer;
+use std::path::Path;
+
+const OUTPUT_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/target/sequence");
+
+fn get_world(_t: f64) -> World {
+    World::new(ViewPlane::new(8, 6, 0.5, 1), RGBColor::new(0.2, 0.4, 0.6))
+}
+
+fn get_size(path: &str) -> (u32, u32) {
+    image::open(path).unwrap().to_rgb().dimensions()
+}
+
+#[test]
+fn expand_patterns() {
+    assert_eq!(expand_pattern("img######.png", 42), "img000042.png");
+    assert_eq!(expand_pattern("./render/#.jpeg", 7), "./render/7.jpeg");
+    //Numbers wider than the run of #s aren't cut
+    assert_eq!(expand_pattern("##.png", 123), "123.png");
+    //Only the first run is replaced
+    assert_eq!(expand_pattern("a##b##.png", 3), "a03b##.png");
+    //Without #s the number goes before the extension
+    assert_eq!(expand_pattern("render/img.png", 5), "render/img000005.png");
+    assert_eq!(expand_pattern("render/img", 5), "render/img000005");
+
+    let settings = SequenceSettings::new(0, 10, 25.0, "frames/f###.gif");
+    assert_eq!(settings.get_frame_path(12), "frames/f012.gif");
+    assert_eq!(settings.get_frame_time(50), 2.0);
+    assert_eq!(settings.get_frame_interval(), 0.04);
+}
+
+#[test]
+fn finished_frames_are_skipped() {
+    let dir = format!("{}/skip", OUTPUT_DIR);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut settings = SequenceSettings::new(0, 2, 10.0, &format!("{}/f##.png", dir));
+    let frame = |n: u32| format!("{}/f{:02}.png", dir, n);
+    let tracer = SimpleTracer::new();
+
+    render_sequence(&tracer, get_world, &settings, &mut |_, _| {}).unwrap();
+    for n in 0..=2 {
+        assert_eq!(get_size(&frame(n)), (8, 6));
+        assert!(!Path::new(&format!("{}/f{:02}.partial.png", dir, n)).exists());
+    }
+
+    //A finished frame stays as it is, a missing one is rendered again, and a partial
+    //frame left by an interrupted render doesn't count as finished
+    image::RgbImage::new(1, 1).save(frame(1)).unwrap();
+    std::fs::remove_file(frame(2)).unwrap();
+    let partial = format!("{}/f02.partial.png", dir);
+    image::RgbImage::new(2, 2).save(&partial).unwrap();
+    render_sequence(&tracer, get_world, &settings, &mut |_, _| {}).unwrap();
+    assert_eq!(get_size(&frame(1)), (1, 1));
+    assert_eq!(get_size(&frame(2)), (8, 6));
+    assert!(!Path::new(&partial).exists());
+
+    settings.skip_existing = false;
+    render_sequence(&tracer, get_world, &settings, &mut |_, _| {}).unwrap();
+    assert_eq!(get_size(&frame(1)), (8, 6));
+}
@@ -0,0 +1,116 @@
+//! How paths end: at the max depth in black, earlier by Russian roulette without bias
+
+use super::regression::get_cornell_box;
+use crate::film::Film;
+use crate::graphics::*;
+use crate::integrators::PathIntegrator;
+use crate::shapes::*;
+use crate::tracer::{RenderBudget, SimpleTracer};
+use std::rc::Rc;
+
+fn render(integrator: PathIntegrator, worldfunc: fn(f64) -> World, samples: u32) -> Film {
+    SimpleTracer::new().with_integrator(integrator).render_to_film(
+        Box::new(worldfunc),
+        0.0,
+        &RenderBudget::new().with_max_samples(samples),
+        &mut |_| {},
+    )
+}
+
+fn get_mean(film: &Film) -> f64 {
+    let pixels = film.get_pixels();
+    pixels
+        .iter()
+        .map(|color| (color.r + color.g + color.b) / 3.0)
+        .sum::<f64>()
+        / pixels.len() as f64
+}
+
+///A closed box of bright walls around the view plane with no light inside, under a
+///white background no path can reach
+fn get_closed_room(_t: f64) -> World {
+    let mut world = World::new(
+        ViewPlane::new(8, 8, 1.0 / 4.0, 4),
+        RGBColor::new(1.0, 1.0, 1.0),
+    )
+    .with_sky(false);
+    let wall: Rc<dyn Material> = Rc::new(LambertianMaterial::new(RGBColor::new(0.9, 0.9, 0.9)));
+    let (x, y, z) = (
+        Vector3D::new(4.0, 0.0, 0.0),
+        Vector3D::new(0.0, 4.0, 0.0),
+        Vector3D::new(0.0, 0.0, -12.0),
+    );
+    //x and y in [-2,2] and z in [-6,6], with the normals facing inwards
+    let walls = [
+        (Point3D::new(-2.0, -2.0, 6.0), x, z),
+        (Point3D::new(-2.0, 2.0, 6.0), z, x),
+        (Point3D::new(-2.0, -2.0, -6.0), x, y),
+        (Point3D::new(-2.0, -2.0, 6.0), y, x),
+        (Point3D::new(-2.0, -2.0, 6.0), z, y),
+        (Point3D::new(2.0, -2.0, 6.0), y, z),
+    ];
+    for (corner, a, b) in walls.iter() {
+        world
+            .get_objects_mut()
+            .push(Box::new(Rectangle::new(*corner, *a, *b, wall.clone())));
+    }
+    world
+}
+
+///A grey sphere in front of a white background, every path off it escapes at once
+fn get_grey_sphere(_t: f64) -> World {
+    let mut world = World::new(
+        ViewPlane::new(8, 8, 1.0 / 8.0, 4),
+        RGBColor::new(1.0, 1.0, 1.0),
+    )
+    .with_sky(false);
+    world.get_objects_mut().push(Box::new(Sphere::new(
+        Point3D::new(0.0, 0.0, 0.0),
+        3.0,
+        Rc::new(LambertianMaterial::new(RGBColor::new(0.5, 0.5, 0.5))),
+    )));
+    world
+}
+
+#[test]
+fn closed_room_without_light_is_black() {
+    //Paths cut off at the max depth used to see the background
+    for integrator in [
+        PathIntegrator::new(),
+        PathIntegrator::new().with_roulette_depth(u32::MAX),
+    ]
+    .iter()
+    {
+        let film = render(integrator.clone(), get_closed_room, 4);
+        assert_eq!(get_mean(&film), 0.0);
+    }
+}
+
+#[test]
+fn max_depth_counts_bounces() {
+    let one_bounce = render(PathIntegrator::new().with_max_depth(1), get_grey_sphere, 2);
+    let no_bounce = render(PathIntegrator::new().with_max_depth(0), get_grey_sphere, 2);
+    for y in 0..8 {
+        for x in 0..8 {
+            assert!((one_bounce.get_color(x, y).g - 0.5).abs() < 1e-9);
+            assert_eq!(no_bounce.get_color(x, y).g, 0.0);
+        }
+    }
+}
+
+#[test]
+fn roulette_is_unbiased() {
+    let always = render(
+        PathIntegrator::new().with_roulette_depth(u32::MAX),
+        get_cornell_box,
+        16,
+    );
+    let roulette = render(PathIntegrator::new().with_roulette_depth(0), get_cornell_box, 16);
+    let (expected, mean) = (get_mean(&always), get_mean(&roulette));
+    assert!(
+        (mean - expected).abs() < 0.02 * expected,
+        "{} with roulette, {} without",
+        mean,
+        expected
+    );
+}
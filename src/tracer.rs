@@ -1,216 +1,621 @@
-use super::RGBColor;
 use crate::graphics::{
-     Point3D, Ray, Vector3D, World,
+     HitInfo, Material, Point3D, RGBColor, Ray, Sampler, World,
 };
-use image::{DynamicImage, GenericImage, Pixel};
+use crate::integrators::{Integrator, PathIntegrator};
+use crate::aov::{AovSample, Surface};
+use crate::checkpoint::{get_checkpoint_path, Checkpoint};
+use crate::film::Film;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use rand::Rng;
-use crossbeam::channel::{unbounded,Sender};
-
-pub enum PixelInfo {
-    ///x,y,pixel color, sample_no
-    Pixel(u32, u32, RGBColor,u32),
-    SampleComplete(u32,u32),
-    End(u32),
+use std::time::{Duration, Instant};
+use crossbeam::channel::unbounded;
+
+///Width and height in pixels of the tiles the image is split into for the worker threads
+const TILE_SIZE: u32 = 32;
+///Noise estimates from fewer samples per pixel are too unreliable to stop on, or to
+///stop sampling a tile on
+const MIN_NOISE_SAMPLES: u32 = 8;
+///The eye sits on the z axis and looks down it through the view plane
+const EYE_Z: f64 = 10.0;
+const VIEW_PLANE_Z: f64 = 5.0;
+
+///Stops a render from another thread, or from its progress callback
+///Clones share the same flag
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
 }
 
-pub struct SimpleTracer;
+impl CancelToken {
+    pub fn new() -> Self {
+        CancelToken::default()
+    }
 
-impl SimpleTracer {
+    ///The worker threads stop after the pixel they're on and the render returns what's
+    ///been merged so far
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+///When a progressive render stops adding passes, whichever limit is reached first
+///Without any limit the render goes on until the process is stopped or it's cancelled
+#[derive(Debug, Clone)]
+pub struct RenderBudget {
+    pub time_limit: Option<Duration>,
+    ///Samples per pixel
+    pub max_samples: Option<u32>,
+    ///Average standard error of the pixels, see `Film::get_noise`
+    pub noise_threshold: Option<f64>,
+    ///Tiles get no more samples once the standard error of each of their pixels is
+    ///below this, so the passes concentrate on the noisy parts of the image
+    ///The render stops when every tile is done
+    pub adaptive_threshold: Option<f64>,
+    ///Time between saves of the image rendered so far
+    pub snapshot_interval: Duration,
+    pub cancel: Option<CancelToken>,
+}
+
+impl RenderBudget {
     pub fn new() -> Self {
-        SimpleTracer {}
+        RenderBudget {
+            time_limit: None,
+            max_samples: None,
+            noise_threshold: None,
+            adaptive_threshold: None,
+            snapshot_interval: Duration::from_secs(10),
+            cancel: None,
+        }
     }
 
+    pub fn with_time_limit(mut self, time_limit: Duration) -> Self {
+        self.time_limit = Some(time_limit);
+        self
+    }
 
-    pub fn render(&self, worldfunc:Box<dyn Fn(f64)->World + Sync+Send>,path:&String,t:f64){
-        let (sender, recv) =unbounded();
+    pub fn with_max_samples(mut self, max_samples: u32) -> Self {
+        self.max_samples = Some(max_samples);
+        self
+    }
 
-        let world=worldfunc(t);
-        let num_cpu = num_cpus::get() as u32;
-	    //println!("cores {}",num_cpu);
-        let vres = world.get_view_plane().get_vres();
-        let hres = world.get_view_plane().get_hres();
-        let samples=world.get_view_plane().get_samples();
-        let section_height=vres/num_cpu;
-        let lowest_section_y=-(vres as i32/2);
-
-        let fn_ref=Arc::new(worldfunc);
-        for i in 0..num_cpu{
-            let sender_clone = sender.clone();
-            let ref_clone=Arc::clone(&fn_ref);
-            std::thread::spawn(move ||{
-                let world=ref_clone(t);
-                SimpleTracer::render_image_section(&world,sender_clone,lowest_section_y+(i*section_height) as i32,
-                                                   lowest_section_y+((i+1)*section_height) as i32,i);
-            });
+    pub fn with_noise_threshold(mut self, noise_threshold: f64) -> Self {
+        self.noise_threshold = Some(noise_threshold);
+        self
+    }
+
+    pub fn with_adaptive_threshold(mut self, adaptive_threshold: f64) -> Self {
+        self.adaptive_threshold = Some(adaptive_threshold);
+        self
+    }
+
+    pub fn with_snapshot_interval(mut self, snapshot_interval: Duration) -> Self {
+        self.snapshot_interval = snapshot_interval;
+        self
+    }
+
+    pub fn with_cancel_token(mut self, cancel: CancelToken) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(CancelToken::is_cancelled)
+    }
+
+    fn is_spent(&self, elapsed: Duration, samples: u32, film: &Film) -> bool {
+        self.is_cancelled()
+            || self.time_limit.is_some_and(|limit| elapsed >= limit)
+            || self.max_samples.is_some_and(|max| samples >= max)
+            || self.noise_threshold.is_some_and(|threshold| {
+                samples >= MIN_NOISE_SAMPLES && film.get_noise() <= threshold
+            })
+    }
+
+    ///Time left until the time or sample limit is reached, None without either
+    ///passes-samples per pixel taken so far, counting the current pass in part
+    ///first_pass-passes a resumed render started with, which took no time
+    fn get_eta(&self, elapsed: Duration, passes: f64, first_pass: u32) -> Option<Duration> {
+        let by_time = self.time_limit.map(|limit| limit.saturating_sub(elapsed));
+        let done = passes - f64::from(first_pass);
+        let by_samples = self.max_samples.filter(|_| done > 0.0).map(|max| {
+            let left = (f64::from(max) - passes).max(0.0);
+            Duration::from_secs_f64(elapsed.as_secs_f64() * left / done)
+        });
+        match (by_time, by_samples) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
         }
+    }
 
-        /*SimpleTracer::render_image_section(&world,sender.clone(),
-                                           -lowest_section_y-remaining_section_height as i32,
-                                           -lowest_section_y,
-                                           num_cpu);*/
-
-        let mut total_array=vec![vec![RGBColor::new(0.0,0.0,0.0)
-                                      ; vres as usize];hres as usize];
-        let mut threads_completed=0;
-        let mut samples_rendered_array=vec![0.0;num_cpu as usize];
-
-        loop{
-            match recv.recv().unwrap(){
-                PixelInfo::Pixel(x, y, color,samples_rendered)=>{
-                    total_array[x as usize][y as usize].r+=color.r;
-                    total_array[x as usize][y as usize].g+=color.g;
-                    total_array[x as usize][y as usize].b+=color.b;
-
-                },
-                PixelInfo::SampleComplete(_samples_rendered, thread_no)=>{
-                    samples_rendered_array[thread_no as usize]+=1.0;
-
-                    let avg=samples_rendered_array.iter().sum::<f64>() as f64/num_cpu as f64;
-                    let percent=avg*100.0/samples as f64;
-
-                    if percent.floor() as u32%10==0 && percent-percent.floor()<0.1{
-                        let mut img = DynamicImage::new_rgb8(hres, vres);
-                        for x in 0..hres{
-                            for y in 0..vres{
-                                let pixel=total_array[x as usize][y as usize];
-                                let thread_for_part=num_cpu as usize-1-((y as f64/vres as f64)*num_cpu as f64).floor() as usize;
-                                let new_pixel=RGBColor::new(pixel.r/samples_rendered_array
-                                                                [thread_for_part]
-                                                            ,pixel.g/samples_rendered_array
-                                                                [thread_for_part]
-                                                            ,pixel.b/samples_rendered_array
-                                                                [thread_for_part]);
-                                img.put_pixel(x, y, image::Rgba::from_channels(
-                                    new_pixel.r_in_8_bit(),
-                                    new_pixel.g_in_8_bit(),
-                                    new_pixel.b_in_8_bit(),
-                                    255,
-                                ));
-                            }
-                        }
-                        //println!("Completed {}%",percent.floor() as u32);
-                        img.save(path).unwrap();
-                    }
+    ///Whether the tile gets samples in the next pass
+    fn needs_samples(&self, tile: &Tile, samples: u32, film: &Film) -> bool {
+        match self.adaptive_threshold {
+            Some(threshold) if samples >= MIN_NOISE_SAMPLES => {
+                film.get_max_error(tile.x, tile.y, tile.width, tile.height) > threshold
+            }
+            _ => true,
+        }
+    }
+}
+
+impl Default for RenderBudget {
+    fn default() -> Self {
+        RenderBudget::new()
+    }
+}
+
+///State of a render, passed to the progress callback after every tile
+pub struct Progress<'a> {
+    ///Passes done, each adds a sample to the pixels of the tiles it renders
+    pub samples: u32,
+    ///Tiles of the current pass done and in total, all of them once a pass is done
+    pub tiles_done: usize,
+    pub tiles: usize,
+    pub elapsed: Duration,
+    ///Time left estimated from the budget's time and sample limits, None without either
+    pub eta: Option<Duration>,
+    ///Everything added to the image so far, colors are linear
+    ///Tiles are added in the order of the pass, a tile which comes back before the ones
+    ///ahead of it waits for them
+    pub film: &'a Film,
+}
+
+///What render_progressive reports as it goes, for the caller to show
+pub enum RenderEvent<'a> {
+    ///A checkpoint for the image was found and the render carries on from its samples
+    Resumed { samples: u32 },
+    ///A checkpoint was found which can't be used, the render starts over
+    CheckpointIgnored { path: &'a str, error: String },
+    ///After every tile, like the progress of render_to_film
+    Progress(&'a Progress<'a>),
+    ///The image and the checkpoint were saved at the end of a pass
+    Snapshot(&'a Progress<'a>),
+}
+
+///Part of the image rendered by a worker thread in one go
+#[derive(Debug, Copy, Clone)]
+struct Tile {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+///Samples first_sample..first_sample+samples of every pixel in the tile
+#[derive(Debug, Copy, Clone)]
+struct TileJob {
+    ///Position in the pass, tiles are added to the image in this order
+    index: usize,
+    tile: Tile,
+    first_sample: u32,
+    samples: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct SimpleTracer {
+    aovs: bool,
+    integrator: Arc<dyn Integrator>,
+    threads: usize,
+}
+
+impl SimpleTracer {
+    ///Renders with a `PathIntegrator`
+    pub fn new() -> Self {
+        SimpleTracer {
+            aovs: false,
+            integrator: Arc::new(PathIntegrator::new()),
+            threads: num_cpus::get(),
+        }
+    }
+
+    ///Whether the films rendered record AOVs besides the color, see `Film::get_aovs`
+    pub fn with_aovs(mut self, aovs: bool) -> Self {
+        self.aovs = aovs;
+        self
+    }
+
+    ///Algorithm turning the rays from the eye into colors
+    pub fn with_integrator(mut self, integrator: impl Integrator + 'static) -> Self {
+        self.integrator = Arc::new(integrator);
+        self
+    }
+
+    ///Number of worker threads, one per CPU by default
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    ///Renders the view plane's sample count, saving what's done so far every few seconds
+    pub fn render(
+        &self,
+        worldfunc: Box<dyn Fn(f64) -> World + Sync + Send>,
+        path: &str,
+        t: f64,
+        on_event: &mut dyn FnMut(&RenderEvent),
+    ) -> io::Result<Film> {
+        let samples = worldfunc(t).get_view_plane().get_samples();
+        let budget = RenderBudget::new().with_max_samples(samples);
+        self.render_progressive(worldfunc, path, t, &budget, on_event)
+    }
+
+    ///Renders progressively like render_to_film, saving the image and a checkpoint at
+    ///the end of the first pass after each snapshot interval and the image once done
+    ///A render which finds a checkpoint for the same view plane next to the image carries
+    ///on from it, the checkpoint is removed once the render is done unless it's cancelled
+    ///A snapshot which can't be saved cancels the render and its error is returned
+    ///on_event is called on this thread, nothing is printed
+    pub fn render_progressive(
+        &self,
+        worldfunc: Box<dyn Fn(f64) -> World + Sync + Send>,
+        path: &str,
+        t: f64,
+        budget: &RenderBudget,
+        on_event: &mut dyn FnMut(&RenderEvent),
+    ) -> io::Result<Film> {
+        let checkpoint_path = get_checkpoint_path(path);
+        let world = worldfunc(t);
+        let view_plane = world.get_view_plane();
+        let checkpoint = if Path::new(&checkpoint_path).exists() {
+            match Checkpoint::load(&checkpoint_path)
+                .map_err(|e| e.to_string())
+                .and_then(|checkpoint| {
+                    checkpoint
+                        .check(view_plane, self.integrator.as_ref())
+                        .map(|_| checkpoint)
+                })
+            {
+                Ok(checkpoint) => {
+                    on_event(&RenderEvent::Resumed {
+                        samples: checkpoint.samples,
+                    });
+                    Some(checkpoint)
                 }
-                PixelInfo::End(_thread_no)=>{
-                    threads_completed+=1;
-                    if threads_completed==num_cpu {
-                        break;
-                    }
+                Err(error) => {
+                    on_event(&RenderEvent::CheckpointIgnored {
+                        path: &checkpoint_path,
+                        error,
+                    });
+                    None
                 }
             }
+        } else {
+            None
+        };
+        let (film, samples) = match checkpoint {
+            Some(checkpoint) => (checkpoint.film, checkpoint.samples),
+            None => (Film::new(view_plane.get_hres(), view_plane.get_vres()), 0),
+        };
+
+        let cancel = budget.cancel.clone().unwrap_or_default();
+        let budget = &budget.clone().with_cancel_token(cancel.clone());
+        let mut last_snapshot = Duration::from_secs(0);
+        let mut snapshot_result = Ok(());
+        let film = self.render_from(worldfunc, t, budget, film, samples, &mut |progress: &Progress| {
+            on_event(&RenderEvent::Progress(progress));
+            if snapshot_result.is_ok()
+                && progress.tiles_done == progress.tiles
+                && progress.elapsed - last_snapshot >= budget.snapshot_interval
+            {
+                snapshot_result = progress.film.save(path).and_then(|_| {
+                    Checkpoint::new(
+                        progress.film.clone(),
+                        progress.samples,
+                        view_plane,
+                        self.integrator.as_ref(),
+                    )
+                    .save(&checkpoint_path)
+                });
+                if snapshot_result.is_err() {
+                    cancel.cancel();
+                    return;
+                }
+                last_snapshot = progress.elapsed;
+                on_event(&RenderEvent::Snapshot(progress));
+            }
+        });
+        snapshot_result?;
+        film.save(path)?;
+        if !budget.is_cancelled() && Path::new(&checkpoint_path).exists() {
+            std::fs::remove_file(&checkpoint_path)?;
         }
+        Ok(film)
     }
 
-    ///low_y-in world co-ordinates of row from which to render
-    ///high_y-in world co-ordinates of row upto which rendering should occur
-    fn render_image_section(world: &World, sender: Sender<PixelInfo>,
-                            low_y:i32, high_y:i32, thread_no:u32) {
-        let z_plane = 5.0;
-        let samples = world.get_view_plane().get_samples();
-        let samples_sqrt = (samples as f64).sqrt() as u32;
+    ///Adds a sample to every pixel per pass until the budget is spent and returns the
+    ///linear pixels, without writing anything
+    ///on_progress is called on this thread after every tile
+    ///With an adaptive threshold tiles which have converged are left out of the passes
+    ///Every tile of a pass is handed to whichever worker thread is free, the next pass
+    ///starts once all of them are back
+    ///The random numbers of each sample only depend on the view plane's seed and the
+    ///pixel, and each pixel adds its samples in order, so the image is the same whatever
+    ///the number of threads, unless the budget has a time limit or is cancelled
+    pub fn render_to_film(
+        &self,
+        worldfunc: Box<dyn Fn(f64) -> World + Sync + Send>,
+        t: f64,
+        budget: &RenderBudget,
+        on_progress: &mut dyn FnMut(&Progress),
+    ) -> Film {
+        let (hres, vres) = {
+            let world = worldfunc(t);
+            (world.get_view_plane().get_hres(), world.get_view_plane().get_vres())
+        };
+        self.render_from(worldfunc, t, budget, Film::new(hres, vres), 0, on_progress)
+    }
 
-        let vres = world.get_view_plane().get_vres();
-        let hres = world.get_view_plane().get_hres();
-        let pixel_size = world.get_view_plane().get_pixel_size();
-        let mut rng = rand::thread_rng();
-        let sub_pixel_size = pixel_size / samples_sqrt as f64;
-        let mut samples_rendered=0;
-
-        for sub_y in 0..samples_sqrt {
-            for sub_x in 0..samples_sqrt{
-                samples_rendered+=1;
-                for y in low_y..high_y {
-                    /*let completion = ((y + vres as i32 / 2) * 100) / vres as i32;
-                    if completion % 10 == 0 {
-                        //print!("{}% ",completion);
-                    }*/
-                    for x in -((hres / 2) as i32)..(hres / 2) as i32 {
-                        let in_world_x = x as f64 * pixel_size
-                            + sub_pixel_size * sub_x as f64
-                            + rng.gen_range(0.0, sub_pixel_size);
-                        let in_world_y = y as f64 * pixel_size
-                            + sub_pixel_size * sub_y as f64
-                            + rng.gen_range(0.0, sub_pixel_size);
-
-                        let ray_direction = Point3D::new(in_world_x, in_world_y, z_plane)
-                            - Point3D::new(0.0, 0.0, 10.0);
-                        let ray = Ray::new(
-                            Point3D::new(in_world_x, in_world_y, z_plane),
-                            //Vector3D::new(0.0,0.0,-1.0));
-                            ray_direction.normalize(),
+    ///Carries on rendering from a checkpoint like render_to_film, the result is the same
+    ///as if the render had never stopped
+    ///The budget's time limit counts from the start of this call
+    pub fn resume_to_film(
+        &self,
+        worldfunc: Box<dyn Fn(f64) -> World + Sync + Send>,
+        t: f64,
+        budget: &RenderBudget,
+        checkpoint: Checkpoint,
+        on_progress: &mut dyn FnMut(&Progress),
+    ) -> Result<Film, String> {
+        checkpoint.check(worldfunc(t).get_view_plane(), self.integrator.as_ref())?;
+        Ok(self.render_from(worldfunc, t, budget, checkpoint.film, checkpoint.samples, on_progress))
+    }
+
+    ///Renders passes on top of a film which already has samples passes
+    ///Recording AOVs on a film which has none starts them from the next pass
+    fn render_from(
+        &self,
+        worldfunc: Box<dyn Fn(f64) -> World + Sync + Send>,
+        t: f64,
+        budget: &RenderBudget,
+        mut film: Film,
+        mut samples: u32,
+        on_progress: &mut dyn FnMut(&Progress),
+    ) -> Film {
+        let (hres, vres) = (film.get_width(), film.get_height());
+        if self.aovs && film.get_aovs().is_none() {
+            film = film.with_aovs();
+        }
+        let tiles = SimpleTracer::get_tiles(hres, vres);
+        let cancel = budget.cancel.clone().unwrap_or_default();
+        let (job_sender, job_recv) = unbounded::<TileJob>();
+        //A worker which panics sends the panic instead, so it's raised again on this thread
+        let (result_sender, result_recv) =
+            unbounded::<std::thread::Result<Option<(TileJob, Film)>>>();
+
+        let fn_ref = Arc::new(worldfunc);
+        for _ in 0..self.threads {
+            let (job_recv, result_sender) = (job_recv.clone(), result_sender.clone());
+            let ref_clone = Arc::clone(&fn_ref);
+            let (cancel, tracer) = (cancel.clone(), self.clone());
+            std::thread::spawn(move || {
+                let worker = panic::catch_unwind(AssertUnwindSafe(|| {
+                    //World holds Rcs so every thread builds its own
+                    let world = ref_clone(t);
+                    let materials = if tracer.aovs { Some(world.get_materials()) } else { None };
+                    let view_plane = world.get_view_plane();
+                    let mut sampler = view_plane
+                        .get_sampler()
+                        .create(view_plane.get_samples(), view_plane.get_seed());
+                    for job in job_recv.iter() {
+                        //Cancelled tiles are reported as None so the main thread stops waiting
+                        let film = tracer.render_tile(
+                            &world,
+                            &job,
+                            sampler.as_mut(),
+                            materials.as_deref(),
+                            &cancel,
                         );
-                        let mut pixel_color = SimpleTracer::trace_ray(&ray, &world, 20);
-                        pixel_color.r = pixel_color.r.sqrt();
-                        pixel_color.g = pixel_color.g.sqrt();
-                        pixel_color.b = pixel_color.b.sqrt();
-                        
-                        let img_x = (x + (hres / 2) as i32) as u32;
-                        let img_y = ((vres / 2) as i32 - y - 1) as u32;
-                        sender
-                            .send(PixelInfo::Pixel(
-                                img_x,
-                                img_y,
-                                pixel_color,samples_rendered
-                            ))
-                            .unwrap();
+                        if result_sender.send(Ok(film.map(|film| (job, film)))).is_err() {
+                            break;
+                        }
                     }
+                }));
+                if let Err(payload) = worker {
+                    let _ = result_sender.send(Err(payload));
                 }
-                sender.send(PixelInfo::SampleComplete(samples_rendered, thread_no)).unwrap();
-            }
+            });
         }
+        //Only the workers hold senders, so the results can't be waited on forever
+        drop(result_sender);
 
-        sender.send(PixelInfo::End(thread_no)).unwrap();
+        let (start, first_pass) = (Instant::now(), samples);
+        while !budget.is_spent(start.elapsed(), samples, &film) {
+            let mut jobs = 0;
+            for tile in tiles.iter().filter(|tile| budget.needs_samples(tile, samples, &film)) {
+                job_sender
+                    .send(TileJob {
+                        index: jobs,
+                        tile: *tile,
+                        first_sample: samples,
+                        samples: 1,
+                    })
+                    .unwrap();
+                jobs += 1;
+            }
+            if jobs == 0 {
+                break;
+            }
+            //Filtered tiles overlap their neighbours, adding them in order keeps the sums
+            //the same whichever thread finishes first
+            let mut pending: Vec<Option<(TileJob, Film)>> = vec![None; jobs];
+            let mut next = 0;
+            for tiles_done in 1..=jobs {
+                let (job, tile_film) = match result_recv.recv() {
+                    Ok(Ok(Some(result))) => result,
+                    Ok(Ok(None)) => break,
+                    Ok(Err(payload)) => panic::resume_unwind(payload),
+                    Err(_) => panic!("the render threads stopped without finishing the pass"),
+                };
+                pending[job.index] = Some((job, tile_film));
+                while let Some((job, tile_film)) = pending.get_mut(next).and_then(Option::take) {
+                    let padding = (tile_film.get_width() - job.tile.width) as i32 / 2;
+                    film.add_film(
+                        job.tile.x as i32 - padding,
+                        job.tile.y as i32 - padding,
+                        &tile_film,
+                    );
+                    next += 1;
+                }
+                if tiles_done == jobs {
+                    samples += 1;
+                }
+                let elapsed = start.elapsed();
+                let passes = f64::from(samples) + (tiles_done % jobs) as f64 / jobs as f64;
+                on_progress(&Progress {
+                    samples,
+                    tiles_done,
+                    tiles: jobs,
+                    elapsed,
+                    eta: budget.get_eta(elapsed, passes, first_pass),
+                    film: &film,
+                });
+            }
+        }
+        //Ends the worker threads, the tiles still queued are skipped if cancelled
+        drop(job_sender);
+        film
     }
 
-    fn trace_ray(ray: &Ray, world: &World, depth: u32) -> RGBColor {
-        let (mut min_hitinfo, mut material) = (None, None);
-        let mut min = std::f64::MAX;
-        for object in world.get_objects() {
-            match object.check_hit(ray) {
-                Some(hitinfo) => {
-                    if hitinfo.get_tmin() < min {
-                        min = hitinfo.get_tmin();
-                        min_hitinfo = Some(hitinfo);
-                        material = Some(object.get_material());
-                    }
-                }
-                None => {}
+    ///Tiles covering the image row by row
+    fn get_tiles(hres: u32, vres: u32) -> Vec<Tile> {
+        let mut tiles = Vec::new();
+        for y in (0..vres).step_by(TILE_SIZE as usize) {
+            for x in (0..hres).step_by(TILE_SIZE as usize) {
+                tiles.push(Tile {
+                    x,
+                    y,
+                    width: TILE_SIZE.min(hres - x),
+                    height: TILE_SIZE.min(vres - y),
+                });
             }
         }
-        match min_hitinfo {
-            Some(hit_info) => {
-                if depth == 0 {
-                    let y = ray.d.y();
-                    let t = (y + 1.0) * 0.5;
-                    //assert!(t<1.0&&t>0.0);
-                    let blue = Vector3D::new(0.4, 0.4, 1.0);
-                    let white = Vector3D::new(1.0, 1.0, 1.0);
-                    let colorvec = white * (1.0 - t) + blue * t;
-                    RGBColor::from(colorvec)
-                //return material.unwrap().get_color().clone()
-                } else {
-                    let material=material.unwrap();
-                    if let Some((ray_out, attenuation)) = material.process(ray, &hit_info) {
-                        return SimpleTracer::trace_ray(&ray_out, &world, depth - 1) * attenuation
-                            + material.get_emitted();
-                    }else{
-                        return material.get_emitted();
+        tiles
+    }
+
+    ///None once cancelled, the samples of a partly rendered tile are thrown away
+    ///The film reaches as far beyond the tile on every side as the filter spreads the
+    ///samples, see `Filter::get_padding`
+    ///The film records AOVs if the world's materials are given to number them
+    fn render_tile(
+        &self,
+        world: &World,
+        job: &TileJob,
+        sampler: &mut dyn Sampler,
+        materials: Option<&[&dyn Material]>,
+        cancel: &CancelToken,
+    ) -> Option<Film> {
+        let tile = &job.tile;
+        let filter = world.get_view_plane().get_filter();
+        let padding = filter.get_padding();
+        let mut film = Film::new(tile.width + 2 * padding, tile.height + 2 * padding);
+        if materials.is_some() {
+            film = film.with_aovs();
+        }
+        for sample in job.first_sample..job.first_sample + job.samples {
+            for y in 0..tile.height {
+                for x in 0..tile.width {
+                    if cancel.is_cancelled() {
+                        return None;
+                    }
+                    let (color, offset, aov) =
+                        self.sample_pixel(world, tile.x + x, tile.y + y, sample, sampler, materials);
+                    let (x, y) = (x + padding, y + padding);
+                    film.add_filtered_sample(x, y, offset, color, &filter);
+                    if let Some(aov) = aov {
+                        film.add_aov_sample(x, y, &aov);
                     }
                 }
             }
-            None => {
-                //assert_eq!(ray.d.magnitude_sqr(),1.0);
-                let y = ray.d.y();
-                let t = (y + 1.0) * 0.5;
-                //assert!(t<1.0&&t>0.0);
-                let blue = Vector3D::new(0.4, 0.4, 1.0);
-                let white = Vector3D::new(1.0, 1.0, 1.0);
-                let colorvec = white * (1.0 - t) + blue * t;
-                RGBColor::from(colorvec)
-                //world.get_bg_color().clone()
-            }
         }
+        Some(film)
+    }
+
+    ///Linear color along a ray through the pixel at (img_x,img_y), where in the pixel the
+    ///ray passes from its top left corner, and what the ray saw first if the world's
+    ///materials are given to number them
+    fn sample_pixel(
+        &self,
+        world: &World,
+        img_x: u32,
+        img_y: u32,
+        sample: u32,
+        sampler: &mut dyn Sampler,
+        materials: Option<&[&dyn Material]>,
+    ) -> (RGBColor, (f64, f64), Option<AovSample>) {
+        let z_plane = VIEW_PLANE_Z;
+        let vres = world.get_view_plane().get_vres();
+        let hres = world.get_view_plane().get_hres();
+        let pixel_size = world.get_view_plane().get_pixel_size();
+        let (shutter_open, shutter_close) = world.get_view_plane().get_shutter();
+        sampler.start_pixel_sample((img_x, img_y), sample);
+        let (offset_x, offset_y) = sampler.get_2d();
+
+        let x = img_x as i32 - (hres / 2) as i32;
+        let y = (vres / 2) as i32 - 1 - img_y as i32;
+        let in_world_x = (x as f64 + offset_x) * pixel_size;
+        let in_world_y = (y as f64 + offset_y) * pixel_size;
+
+        let ray_direction = Point3D::new(in_world_x, in_world_y, z_plane)
+            - Point3D::new(0.0, 0.0, EYE_Z);
+        let time = shutter_open + (shutter_close - shutter_open) * sampler.get_1d();
+        let ray = Ray::with_time(
+            Point3D::new(in_world_x, in_world_y, z_plane),
+            ray_direction.normalize(),
+            time,
+        );
+        //The image's y axis points down, the world's up
+        let offset = (offset_x, 1.0 - offset_y);
+        let hit = world.find_hit(&ray);
+        let first_hit = materials
+            .map(|materials| SimpleTracer::get_first_hit(&ray, hit.as_ref(), world, materials));
+        let radiance = self.integrator.trace(&ray, hit, world, sampler);
+        let aov = first_hit.map(|(surface, albedo)| AovSample {
+            surface,
+            albedo,
+            direct: radiance.direct,
+            indirect: radiance.color - radiance.direct,
+        });
+        (radiance.color, offset, aov)
+    }
+
+    ///What a ray from the eye saw first and its albedo, the background's color if it
+    ///missed everything
+    fn get_first_hit(
+        ray: &Ray,
+        hit: Option<&(usize, HitInfo)>,
+        world: &World,
+        materials: &[&dyn Material],
+    ) -> (Option<Surface>, RGBColor) {
+        let (index, hit_info) = match hit {
+            Some(hit) => hit,
+            None => return (None, world.get_background(ray)),
+        };
+        let material = world.get_objects()[*index].get_material();
+        let material_id = materials
+            .iter()
+            .position(|other| std::ptr::addr_eq(*other, material))
+            .expect("materials of every object of the world");
+        let hit_point = *hit_info.get_hitpoint();
+        let surface = Surface {
+            depth: EYE_Z - hit_point.z(),
+            normal: hit_info.get_normal().normalize(),
+            position: hit_point,
+            object_id: *index as u32,
+            material_id: material_id as u32,
+        };
+        (Some(surface), material.get_albedo())
+    }
+}
+
+impl Default for SimpleTracer {
+    fn default() -> Self {
+        SimpleTracer::new()
     }
 }